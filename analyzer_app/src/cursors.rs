@@ -0,0 +1,37 @@
+use iced::event::Status;
+use iced::mouse::{self, Cursor};
+use iced::widget::canvas::Event;
+use iced::Rectangle;
+
+use charts::Cursors;
+
+/// Move a chart's cursors with the mouse: the hover cursor follows it over
+/// the chart, a left click drops the anchor there, and a right click
+/// removes it
+pub(crate) fn update<Message>(
+    cursors: &mut Cursors,
+    event: Event,
+    bounds: Rectangle,
+    cursor: Cursor,
+) -> (Status, Option<Message>) {
+    let position = cursor
+        .position_in(bounds)
+        .map(|p| (p.x.round() as i32, p.y.round() as i32));
+    let Event::Mouse(event) = event else {
+        return (Status::Ignored, None);
+    };
+    match event {
+        mouse::Event::CursorMoved { .. } => cursors.hover = position,
+        mouse::Event::CursorLeft => cursors.hover = None,
+        mouse::Event::ButtonPressed(button) if position.is_some() => {
+            match button {
+                mouse::Button::Left => cursors.anchor = position,
+                mouse::Button::Right => cursors.anchor = None,
+                _ => return (Status::Ignored, None),
+            }
+            return (Status::Captured, None);
+        }
+        _ => (),
+    }
+    (Status::Ignored, None)
+}
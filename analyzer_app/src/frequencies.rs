@@ -1,38 +1,675 @@
-use iced::{Element, Length};
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter};
+use std::iter::zip;
+use std::time::Duration;
+
+use iced::mouse::Cursor;
+use iced::widget::canvas;
+use iced::{event, widget, Element, Length, Rectangle};
+use plotters::coord::Shift;
+use plotters::prelude::DrawingArea;
 use plotters_iced::{Chart, ChartBuilder, ChartWidget, DrawingBackend};
 
-use audio::{FFTResult, Message};
-use charts;
+use audio::dsp::averaging::{Averaging, SpectrumAverager};
+use audio::dsp::fft::FoldedFFT;
+use audio::dsp::lpc::SpectralEnvelope;
+use audio::dsp::peaks::{harmonic_series, HarmonicSeries, Peak, PeakOptions};
+use audio::dsp::reference_trace::ReferenceTrace;
+use audio::dsp::tuning::Tuning;
+use audio::dsp::{Decibels, Hz};
+use audio::{ConstantQResult, EnvelopeResult, FFTResult, PSDResult};
+use charts::{
+    self, AutoRange, Cursors, FFTAnnotations, FFTChartOptions, FrequencyScale, MagnitudeScale,
+    PhaseDisplay,
+};
+
+#[derive(Clone, Debug)]
+pub enum Message {
+    Averaging(Averaging),
+    Display(SpectrumDisplay),
+    ShowEnvelope(bool),
+    /// Handled by the audio thread, as well as here
+    LPCOrder(usize),
+    ShowPeaks(bool),
+    PeakCount(usize),
+    ShowChannel(usize, bool),
+    Layout(ChannelLayout),
+    Phase(PhaseDisplay),
+    GroupDelay(bool),
+    FrequencyScale(FrequencyScale),
+    Zoom(Zoom),
+    MagnitudeScale(MagnitudeScale),
+    Floor(f32),
+    Range(MagnitudeRange),
+    ShowNotes(bool),
+    ReferenceName(String),
+    /// Save the first visible channel's averaged spectrum as a reference
+    /// trace, with the entered name
+    CaptureReference,
+    SelectReference(String),
+    HideReference,
+    /// Delete the selected reference trace, and its file
+    DeleteReference,
+    ShowDifference(bool),
+}
+
+/// Which kind of spectrum is shown
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpectrumDisplay {
+    /// The (averaged) amplitude spectrum of the latest FFT
+    Amplitude,
+    /// Welch power spectral density estimate
+    PowerDensity,
+    /// The amplitude spectrum of the latest constant-Q transform
+    ConstantQ,
+}
+
+impl SpectrumDisplay {
+    const ALL: [SpectrumDisplay; 3] = [
+        SpectrumDisplay::Amplitude,
+        SpectrumDisplay::PowerDensity,
+        SpectrumDisplay::ConstantQ,
+    ];
+}
+
+impl fmt::Display for SpectrumDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            SpectrumDisplay::Amplitude => "Amplitude",
+            SpectrumDisplay::PowerDensity => "Power density",
+            SpectrumDisplay::ConstantQ => "Constant-Q",
+        })
+    }
+}
+
+/// How the spectra of multiple channels are arranged
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelLayout {
+    /// On the same axes
+    Overlaid,
+    /// On separate axes, one above another
+    Stacked,
+}
+
+impl ChannelLayout {
+    const ALL: [ChannelLayout; 2] = [ChannelLayout::Overlaid, ChannelLayout::Stacked];
+}
+
+impl fmt::Display for ChannelLayout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ChannelLayout::Overlaid => "Overlaid",
+            ChannelLayout::Stacked => "Stacked",
+        })
+    }
+}
+
+/// A range of frequencies to show (Hz), or None for all of them
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Zoom(Option<(f32, f32)>);
+
+impl fmt::Display for Zoom {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some((lo, hi)) => write!(f, "{}-{} Hz", lo, hi),
+            None => f.write_str("All frequencies"),
+        }
+    }
+}
+
+/// The zooms that can be selected
+const ZOOMS: [Zoom; 5] = [
+    Zoom(None),
+    Zoom(Some((20., 20000.))),
+    Zoom(Some((20., 500.))),
+    Zoom(Some((80., 5000.))),
+    Zoom(Some((1000., 10000.))),
+];
+
+/// The top of the magnitude axis
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MagnitudeRange {
+    /// Follow the loudest bin shown
+    Auto,
+    /// In dBFS
+    Fixed(i32),
+}
+
+impl fmt::Display for MagnitudeRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MagnitudeRange::Auto => f.write_str("Auto range"),
+            MagnitudeRange::Fixed(db) => write!(f, "Top {} dBFS", db),
+        }
+    }
+}
+
+/// The magnitude ranges that can be selected
+const MAGNITUDE_RANGES: [MagnitudeRange; 4] = [
+    MagnitudeRange::Auto,
+    MagnitudeRange::Fixed(0),
+    MagnitudeRange::Fixed(-20),
+    MagnitudeRange::Fixed(-40),
+];
+
+/// The bottoms of the magnitude axis (dBFS) that can be selected
+const FLOORS: [f32; 4] = [-60., -80., -100., -120.];
+
+/// The averaging modes that can be selected
+const AVERAGING_MODES: [Averaging; 7] = [
+    Averaging::None,
+    Averaging::Linear(4),
+    Averaging::Linear(16),
+    Averaging::Exponential(Duration::from_secs(1)),
+    Averaging::Exponential(Duration::from_secs(5)),
+    Averaging::PeakHold(6.),
+    Averaging::PeakHold(20.),
+];
+
+/// The LPC model orders that can be selected
+const LPC_ORDERS: [usize; 9] = [8, 10, 12, 13, 14, 16, 18, 20, 24];
+
+/// The numbers of (the loudest) peaks that can be labelled
+const PEAK_COUNTS: [usize; 4] = [3, 5, 10, 20];
+
+/// How far peaks may be from their harmonic (cents) to count as partials
+const HARMONIC_TOLERANCE: f32 = 30.;
+
+/// Reference traces are saved in the working directory, as
+/// <prefix><name><suffix>
+const REFERENCE_PREFIX: &str = "reference_";
+const REFERENCE_SUFFIX: &str = ".csv";
 
 pub struct FrequenciesChart {
-    latest_ffts: Option<FFTResult>,
+    display: SpectrumDisplay,
+    averaging: Averaging,
+    /// Of each channel's FFTs
+    averagers: Vec<SpectrumAverager>,
+    /// Whether each channel is shown
+    visible: Vec<bool>,
+    layout: ChannelLayout,
+    fft_options: FFTChartOptions,
+    range: MagnitudeRange,
+    /// Follows the visible channels' averaged spectra
+    auto_range: AutoRange,
+    latest_psds: Option<PSDResult>,
+    latest_constant_q: Option<ConstantQResult>,
+    show_envelope: bool,
+    /// None until selected, i.e. the audio thread's default
+    lpc_order: Option<usize>,
+    /// Of the first channel
+    envelope: Option<SpectralEnvelope>,
+    show_peaks: bool,
+    peak_options: PeakOptions,
+    /// Of the first channel's averaged spectrum, loudest first
+    peaks: Vec<Peak>,
+    harmonics: Option<HarmonicSeries>,
+    /// Saved reference traces, by name
+    references: Vec<ReferenceTrace>,
+    /// The index of the reference trace shown, if any
+    reference: Option<usize>,
+    reference_name: String,
 }
 
 impl FrequenciesChart {
     pub fn new() -> FrequenciesChart {
-        FrequenciesChart { latest_ffts: None }
+        FrequenciesChart {
+            display: SpectrumDisplay::Amplitude,
+            averaging: Averaging::None,
+            averagers: Vec::new(),
+            visible: Vec::new(),
+            layout: ChannelLayout::Overlaid,
+            fft_options: FFTChartOptions::default(),
+            range: MagnitudeRange::Auto,
+            auto_range: AutoRange::new(),
+            latest_psds: None,
+            latest_constant_q: None,
+            show_envelope: true,
+            lpc_order: None,
+            envelope: None,
+            show_peaks: false,
+            peak_options: PeakOptions {
+                threshold_db: -70.,
+                min_prominence_db: 10.,
+                min_separation: Hz(20.),
+                max_peaks: Some(5),
+                ..PeakOptions::default()
+            },
+            peaks: Vec::new(),
+            harmonics: None,
+            references: read_references(),
+            reference: None,
+            reference_name: String::new(),
+        }
     }
 
     pub fn view(&self) -> Element<Message> {
-        ChartWidget::new(self)
-            .width(Length::Fill)
-            .height(Length::Fill)
-            .into()
+        widget::column![
+            widget::row![
+                widget::pick_list(
+                    &SpectrumDisplay::ALL[..],
+                    Some(self.display),
+                    Message::Display
+                ),
+                widget::pick_list(
+                    &AVERAGING_MODES[..],
+                    Some(self.averaging),
+                    Message::Averaging
+                ),
+                widget::checkbox("LPC envelope", self.show_envelope)
+                    .on_toggle(Message::ShowEnvelope),
+                widget::text("Order"),
+                widget::pick_list(&LPC_ORDERS[..], self.lpc_order, Message::LPCOrder)
+                    .placeholder("Auto"),
+                widget::checkbox("Peaks", self.show_peaks).on_toggle(Message::ShowPeaks),
+                widget::pick_list(
+                    &PEAK_COUNTS[..],
+                    self.peak_options.max_peaks,
+                    Message::PeakCount
+                ),
+                widget::text(self.fundamental()),
+            ]
+            .spacing(10)
+            .align_y(iced::Alignment::Center),
+            widget::row(
+                self.visible
+                    .iter()
+                    .enumerate()
+                    .map(|(i, show)| {
+                        widget::checkbox(format!("ch{}", i), *show)
+                            .on_toggle(move |show| Message::ShowChannel(i, show))
+                            .into()
+                    })
+                    .chain([
+                        widget::pick_list(
+                            &ChannelLayout::ALL[..],
+                            Some(self.layout),
+                            Message::Layout
+                        )
+                        .into(),
+                        widget::pick_list(
+                            &PhaseDisplay::ALL[..],
+                            Some(self.fft_options.phase),
+                            Message::Phase
+                        )
+                        .into(),
+                        widget::checkbox("Group delay", self.fft_options.group_delay)
+                            .on_toggle(Message::GroupDelay)
+                            .into(),
+                    ])
+            )
+            .spacing(10)
+            .align_y(iced::Alignment::Center),
+            widget::row![
+                widget::pick_list(
+                    &FrequencyScale::ALL[..],
+                    Some(self.fft_options.frequency_scale),
+                    Message::FrequencyScale
+                ),
+                widget::pick_list(&ZOOMS[..], Some(self.zoom()), Message::Zoom),
+                widget::checkbox("Notes", self.fft_options.notes.is_some())
+                    .on_toggle(Message::ShowNotes),
+                widget::pick_list(
+                    &MagnitudeScale::ALL[..],
+                    Some(self.fft_options.magnitude_scale),
+                    Message::MagnitudeScale
+                ),
+                widget::pick_list(&MAGNITUDE_RANGES[..], Some(self.range), Message::Range),
+                widget::text("Floor"),
+                widget::pick_list(&FLOORS[..], Some(self.fft_options.floor_db), Message::Floor),
+                widget::text("dBFS"),
+            ]
+            .spacing(10)
+            .align_y(iced::Alignment::Center),
+            self.reference_controls(),
+            ChartWidget::new(self)
+                .width(Length::Fill)
+                .height(Length::Fill),
+        ]
+        .into()
     }
 
     pub fn update(&mut self, message: FFTResult) {
-        self.latest_ffts = Some(message);
+        self.set_channels(message.ffts.len());
+        if self.averagers.len() != message.ffts.len() {
+            self.averagers = message
+                .ffts
+                .iter()
+                .map(|_| SpectrumAverager::new(self.averaging))
+                .collect();
+        }
+        for (averager, fft) in zip(&mut self.averagers, &message.ffts) {
+            averager.push(message.end_time, fft);
+        }
+        let averages = averages(&self.averagers, &self.visible_channels());
+        self.auto_range.push(&averages, &self.fft_options);
+        self.find_peaks();
+    }
+
+    fn reference_controls(&self) -> Element<Message> {
+        let names: Vec<String> = self.references.iter().map(|r| r.name.clone()).collect();
+        let selected = self.reference.map(|i| names[i].clone());
+        let capture = self.captured_spectrum().is_some() && !self.reference_name.is_empty();
+        widget::row![
+            widget::text_input("Reference", &self.reference_name)
+                .on_input(Message::ReferenceName)
+                .on_submit_maybe(capture.then_some(Message::CaptureReference))
+                .width(Length::Fixed(120.)),
+            widget::button("Capture").on_press_maybe(capture.then_some(Message::CaptureReference)),
+            widget::pick_list(names, selected, Message::SelectReference)
+                .placeholder("No reference"),
+            widget::button("Hide").on_press_maybe(self.reference.map(|_| Message::HideReference)),
+            widget::button("Delete")
+                .on_press_maybe(self.reference.map(|_| Message::DeleteReference)),
+            widget::checkbox("Difference", self.fft_options.difference)
+                .on_toggle(Message::ShowDifference),
+        ]
+        .spacing(10)
+        .align_y(iced::Alignment::Center)
+        .into()
+    }
+
+    /// The spectrum a reference trace would be captured from
+    fn captured_spectrum(&self) -> Option<&FoldedFFT> {
+        let channel = *self.visible_channels().first()?;
+        self.averagers.get(channel)?.average()
+    }
+
+    /// Capture a reference trace, replacing any with the same name, and show
+    /// it
+    fn capture_reference(&mut self) {
+        let name: String = self
+            .reference_name
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect();
+        let Some(fft) = self.captured_spectrum().filter(|_| !name.is_empty()) else {
+            return;
+        };
+        let trace = ReferenceTrace::from_fft(name, fft);
+        if let Err(e) = write_reference(&trace) {
+            println!("Failed to save reference {}: {}", trace.name, e);
+        }
+        let index = match self.references.iter().position(|r| r.name == trace.name) {
+            Some(i) => {
+                self.references[i] = trace;
+                i
+            }
+            None => {
+                self.references.push(trace);
+                self.references.len() - 1
+            }
+        };
+        self.reference = Some(index);
+    }
+
+    fn delete_reference(&mut self) {
+        let Some(index) = self.reference.take() else {
+            return;
+        };
+        let trace = self.references.remove(index);
+        if let Err(e) = fs::remove_file(reference_path(&trace.name)) {
+            println!("Failed to delete reference {}: {}", trace.name, e);
+        }
+    }
+
+    fn visible_channels(&self) -> Vec<usize> {
+        (0..self.visible.len())
+            .filter(|c| self.visible[*c])
+            .collect()
+    }
+
+    fn zoom(&self) -> Zoom {
+        match (
+            self.fft_options.min_frequency,
+            self.fft_options.max_frequency,
+        ) {
+            (Some(lo), Some(hi)) => Zoom(Some((lo, hi))),
+            _ => Zoom(None),
+        }
+    }
+
+    /// Show every channel when the number of channels changes
+    fn set_channels(&mut self, channels: usize) {
+        if self.visible.len() != channels {
+            self.visible = vec![true; channels];
+        }
     }
+
+    fn find_peaks(&mut self) {
+        let average = self.averagers.first().and_then(|a| a.average());
+        match average.filter(|_| self.show_peaks) {
+            Some(average) => {
+                self.peaks = average.peaks(&self.peak_options);
+                self.harmonics = harmonic_series(&self.peaks, HARMONIC_TOLERANCE);
+            }
+            None => {
+                self.peaks.clear();
+                self.harmonics = None;
+            }
+        }
+    }
+
+    /// The fundamental of the peaks' harmonic series, if they have one
+    fn fundamental(&self) -> String {
+        match &self.harmonics {
+            Some(harmonics) => format!("f0 {:.1} Hz", harmonics.fundamental.0),
+            None => String::new(),
+        }
+    }
+
+    pub fn update_psds(&mut self, message: PSDResult) {
+        self.set_channels(message.psds.len());
+        self.latest_psds = Some(message);
+    }
+
+    pub fn update_constant_q(&mut self, message: ConstantQResult) {
+        self.set_channels(message.spectra.len());
+        self.latest_constant_q = Some(message);
+    }
+
+    pub fn update_envelopes(&mut self, message: EnvelopeResult) {
+        self.envelope = message.envelopes.into_iter().next().flatten();
+    }
+
+    pub fn configure(&mut self, message: Message) {
+        match message {
+            Message::Averaging(mode) => {
+                self.averaging = mode;
+                for averager in &mut self.averagers {
+                    averager.set_mode(mode);
+                }
+            }
+            Message::Display(display) => self.display = display,
+            Message::ShowEnvelope(show) => self.show_envelope = show,
+            Message::LPCOrder(order) => self.lpc_order = Some(order),
+            Message::ShowPeaks(show) => {
+                self.show_peaks = show;
+                self.find_peaks();
+            }
+            Message::PeakCount(count) => {
+                self.peak_options.max_peaks = Some(count);
+                self.find_peaks();
+            }
+            Message::ShowChannel(channel, show) => {
+                if let Some(visible) = self.visible.get_mut(channel) {
+                    *visible = show;
+                }
+            }
+            Message::Layout(layout) => self.layout = layout,
+            Message::Phase(phase) => self.fft_options.phase = phase,
+            Message::GroupDelay(show) => self.fft_options.group_delay = show,
+            Message::FrequencyScale(scale) => self.fft_options.frequency_scale = scale,
+            Message::Zoom(Zoom(range)) => {
+                self.fft_options.min_frequency = range.map(|(lo, _)| lo);
+                self.fft_options.max_frequency = range.map(|(_, hi)| hi);
+                // The loudest bin may have been zoomed out of view
+                self.auto_range.reset();
+            }
+            Message::MagnitudeScale(scale) => self.fft_options.magnitude_scale = scale,
+            Message::Floor(db) => self.fft_options.floor_db = db,
+            Message::Range(range) => self.range = range,
+            Message::ShowNotes(show) => self.fft_options.notes = show.then(Tuning::default),
+            Message::ReferenceName(name) => self.reference_name = name,
+            Message::CaptureReference => self.capture_reference(),
+            Message::SelectReference(name) => {
+                self.reference = self.references.iter().position(|r| r.name == name)
+            }
+            Message::HideReference => self.reference = None,
+            Message::DeleteReference => self.delete_reference(),
+            Message::ShowDifference(show) => self.fft_options.difference = show,
+        }
+    }
+}
+
+impl FrequenciesChart {
+    /// Draw the spectra of some channels on the same axes
+    fn draw_channels<DB: DrawingBackend>(
+        &self,
+        root: &DrawingArea<DB, Shift>,
+        channels: &[usize],
+        cursors: &Cursors,
+    ) {
+        match self.display {
+            SpectrumDisplay::Amplitude => {
+                let ffts = averages(&self.averagers, channels);
+                if ffts.is_empty() {
+                    return;
+                }
+                // Peaks and envelopes are only found for the first channel
+                let reference = self.reference.map(|i| &self.references[i]);
+                let annotations = match ffts.first() {
+                    Some((0, _)) => FFTAnnotations {
+                        cursors: *cursors,
+                        envelope: self.envelope.as_ref().filter(|_| self.show_envelope),
+                        peaks: &self.peaks,
+                        harmonics: self.harmonics.as_ref(),
+                        reference,
+                    },
+                    _ => FFTAnnotations {
+                        cursors: *cursors,
+                        reference,
+                        ..FFTAnnotations::default()
+                    },
+                };
+                let options = FFTChartOptions {
+                    top: match self.range {
+                        MagnitudeRange::Auto => self.auto_range.top(),
+                        MagnitudeRange::Fixed(db) => {
+                            Some(Decibels::new(db as f32).amplitude_ratio())
+                        }
+                    },
+                    ..self.fft_options
+                };
+                charts::draw_fft_chart(root, &ffts, &annotations, &options)
+                    .expect("Failed to build chart");
+            }
+            SpectrumDisplay::PowerDensity => {
+                let Some(latest) = &self.latest_psds else {
+                    return;
+                };
+                let psds: Vec<_> = channels
+                    .iter()
+                    .filter_map(|c| Some((*c, latest.psds.get(*c)?)))
+                    .collect();
+                if !psds.is_empty() {
+                    charts::build_psd_chart(ChartBuilder::on(root), &psds)
+                        .expect("Failed to build chart");
+                }
+            }
+            SpectrumDisplay::ConstantQ => {
+                let Some(latest) = &self.latest_constant_q else {
+                    return;
+                };
+                let spectra: Vec<_> = channels
+                    .iter()
+                    .filter_map(|c| Some((*c, latest.spectra.get(*c)?)))
+                    .collect();
+                if !spectra.is_empty() {
+                    charts::build_constant_q_chart(ChartBuilder::on(root), &spectra)
+                        .expect("Failed to build chart");
+                }
+            }
+        }
+    }
+}
+
+fn reference_path(name: &str) -> String {
+    format!("{}{}{}", REFERENCE_PREFIX, name, REFERENCE_SUFFIX)
+}
+
+fn write_reference(trace: &ReferenceTrace) -> io::Result<()> {
+    trace.write_csv(BufWriter::new(File::create(reference_path(&trace.name))?))
+}
+
+/// Read the reference traces saved in the working directory, in order of
+/// name
+fn read_references() -> Vec<ReferenceTrace> {
+    let entries = match fs::read_dir(".") {
+        Ok(entries) => entries,
+        Err(e) => {
+            println!("Failed to list reference traces: {}", e);
+            return Vec::new();
+        }
+    };
+    let mut references: Vec<ReferenceTrace> = entries
+        .filter_map(|entry| {
+            let file_name = entry.ok()?.file_name().into_string().ok()?;
+            let name = file_name
+                .strip_prefix(REFERENCE_PREFIX)?
+                .strip_suffix(REFERENCE_SUFFIX)?;
+            let trace = File::open(&file_name)
+                .and_then(|file| ReferenceTrace::read_csv(name.to_string(), BufReader::new(file)));
+            trace
+                .map_err(|e| println!("Failed to read reference {}: {}", name, e))
+                .ok()
+        })
+        .collect();
+    references.sort_by(|a, b| a.name.cmp(&b.name));
+    references
+}
+
+/// The averaged spectra of some channels
+fn averages<'a>(
+    averagers: &'a [SpectrumAverager],
+    channels: &[usize],
+) -> Vec<(usize, &'a FoldedFFT)> {
+    channels
+        .iter()
+        .filter_map(|c| Some((*c, averagers.get(*c)?.average()?)))
+        .collect()
 }
 
 impl Chart<Message> for FrequenciesChart {
-    type State = ();
+    type State = Cursors;
+
+    // Everything is drawn by draw_chart, which may split the chart into rows
+    fn build_chart<DB: DrawingBackend>(&self, _state: &Self::State, _builder: ChartBuilder<DB>) {}
 
-    fn build_chart<DB: DrawingBackend>(&self, _state: &Self::State, builder: ChartBuilder<DB>) {
-        if let Some(latest) = self.latest_ffts.as_ref() {
-            // TODO: display more than the first channel (and don't show phases)
-            charts::build_fft_chart(builder, latest.ffts.first().unwrap())
-                .expect("Failed to build chart");
+    fn draw_chart<DB: DrawingBackend>(&self, state: &Self::State, root: DrawingArea<DB, Shift>) {
+        let channels = self.visible_channels();
+        let rows: Vec<Vec<usize>> = match self.layout {
+            ChannelLayout::Overlaid => vec![channels],
+            ChannelLayout::Stacked => channels.into_iter().map(|c| vec![c]).collect(),
+        };
+        if rows.is_empty() {
+            return;
+        }
+        for (area, channels) in zip(root.split_evenly((rows.len(), 1)), &rows) {
+            self.draw_channels(&area, channels, state);
         }
     }
+
+    fn update(
+        &self,
+        state: &mut Self::State,
+        event: canvas::Event,
+        bounds: Rectangle,
+        cursor: Cursor,
+    ) -> (event::Status, Option<Message>) {
+        crate::cursors::update(state, event, bounds, cursor)
+    }
 }
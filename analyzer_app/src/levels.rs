@@ -1,21 +1,87 @@
 use std::collections::VecDeque;
+use std::fmt;
 use std::iter::zip;
 use std::time::Duration;
 
-use iced::{Element, Length};
+use iced::{widget, Element, Length};
 use plotters_iced::{Chart, ChartBuilder, ChartWidget, DrawingBackend};
 
-use audio::dsp::Decibels;
+use audio::dsp::peak_meter::ChannelPeaks;
+use audio::dsp::{Decibels, Level, Reference};
 use audio::stream::input::Instant;
-use audio::{Message, RMSLevels};
+use audio::{PeakLevels, RMSLevels};
+use charts::channel_color;
 
+/// The default bottom of the charts (dBFS), which silence is drawn at
+const DEFAULT_MIN_DB: f32 = -50.;
+
+/// How long the highest true peak is held for, unless exceeded
+const PEAK_HOLD: Duration = Duration::from_secs(2);
+
+/// The time constant (seconds) with which VU ballistics smooth RMS power
+const VU_TIME_CONSTANT: f32 = 0.3;
+
+/// How fast PPM ballistics fall (dB/s), i.e. 20 dB in 1.7 s, as for a type I
+/// PPM in IEC 60268-10
+const PPM_FALL_DB_PER_SEC: f32 = 20. / 1.7;
+
+#[derive(Clone, Debug)]
+pub enum Message {
+    MinLevel(f32),
+    Ballistics(Ballistics),
+    /// Handled by the audio thread, as well as here
+    ResetClips,
+}
+
+/// How quickly the bar meters respond to changes in level
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ballistics {
+    /// Each bar shows the latest RMS level
+    Instant,
+    /// The RMS level, smoothed with a 300 ms time constant, roughly as a VU
+    /// meter integrates (RMS levels only arrive once per analysis period, so
+    /// this is only approximate)
+    VU,
+    /// Peak reading: each bar jumps to the true peak of each period, and
+    /// falls at the rate of a type I PPM
+    PPM,
+}
+
+impl Ballistics {
+    const ALL: [Ballistics; 3] = [Ballistics::Instant, Ballistics::VU, Ballistics::PPM];
+}
+
+impl fmt::Display for Ballistics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Ballistics::Instant => "Instant",
+            Ballistics::VU => "VU",
+            Ballistics::PPM => "PPM",
+        })
+    }
+}
+
+/// Bar meters and a scrolling history of the RMS level of each channel, with
+/// true peaks, peak hold and clip indicators
 pub struct LevelsChart {
+    ballistics: Ballistics,
+    bars: BarMeters,
     /// The width of the chart
     max_history: Duration,
     /// The time value for each point
     times: VecDeque<Instant>,
     /// By channel, series of levels (dBFS), corresponding to each time
-    levels: Vec<VecDeque<Decibels>>,
+    levels: Vec<VecDeque<Level>>,
+    /// The time value for each true peak
+    peak_times: VecDeque<Instant>,
+    /// By channel, series of true peaks (dBTP), corresponding to each time
+    true_peaks: Vec<VecDeque<Level>>,
+    /// By channel, the highest recent true peak and when it was reached
+    held_peaks: Vec<(Instant, Level)>,
+    /// By channel, the times at which clips were counted
+    clip_times: Vec<VecDeque<Instant>>,
+    /// By channel, the latest peaks
+    latest: Vec<ChannelPeaks>,
 }
 
 impl Chart<Message> for LevelsChart {
@@ -33,23 +99,53 @@ impl Chart<Message> for LevelsChart {
             .margin(5)
             .x_label_area_size(30)
             .y_label_area_size(40)
-            .build_cartesian_2d(tmin..tmax, -50f32..0f32)
+            .build_cartesian_2d(tmin..tmax, self.bars.min_db..0f32)
             .expect("Failed to build chart");
 
         chart.configure_mesh().draw().expect("draw mesh");
 
-        // TODO: color list limits to 4 channels (which ought to be enough for anybody?!)
-        for (i, (ch, color)) in zip(&self.levels, [BLUE, GREEN, RED, CYAN]).enumerate() {
+        for (i, ch) in self.levels.iter().enumerate() {
+            let color = channel_color(i);
             chart
                 .draw_series(LineSeries::new(
-                    zip(&self.times, ch).map(|(t, rms)| (f32::from(*t), f32::from(*rms))),
+                    zip(&self.times, ch)
+                        .map(|(t, rms)| (f32::from(*t), f32::from(rms.max(self.bars.min_db)))),
                     color,
                 ))
                 .expect("draw series")
-                .label(format!("ch{}", i))
+                .label(match self.latest.get(i) {
+                    Some(peaks) if peaks.clips > 0 => format!("ch{} ({} clips)", i, peaks.clips),
+                    _ => format!("ch{}", i),
+                })
                 .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
         }
 
+        for (i, true_peaks) in self.true_peaks.iter().enumerate() {
+            let color = channel_color(i);
+            chart
+                .draw_series(LineSeries::new(
+                    zip(&self.peak_times, true_peaks)
+                        .map(|(t, peak)| (f32::from(*t), f32::from(peak.max(self.bars.min_db)))),
+                    color.mix(0.4),
+                ))
+                .expect("draw series");
+            let (time, held) = self.held_peaks[i];
+            let held = f32::from(held.max(self.bars.min_db));
+            chart
+                .draw_series(LineSeries::new(
+                    [(f32::from(time), held), (tmax, held)],
+                    color.stroke_width(2),
+                ))
+                .expect("draw series");
+            chart
+                .draw_series(
+                    self.clip_times[i]
+                        .iter()
+                        .map(|t| TriangleMarker::new((f32::from(*t), -1.), 6, RED.filled())),
+                )
+                .expect("draw series");
+        }
+
         chart
             .configure_series_labels()
             .position(SeriesLabelPosition::LowerLeft)
@@ -60,46 +156,169 @@ impl Chart<Message> for LevelsChart {
     }
 }
 
-#[allow(dead_code)]
 impl LevelsChart {
     pub fn new(max_history: Duration) -> LevelsChart {
         LevelsChart {
+            ballistics: Ballistics::Instant,
+            bars: BarMeters {
+                min_db: DEFAULT_MIN_DB,
+                powers: Vec::new(),
+                held_peaks: Vec::new(),
+                clipped: Vec::new(),
+            },
             max_history,
             times: VecDeque::new(),
             levels: Vec::new(),
+            peak_times: VecDeque::new(),
+            true_peaks: Vec::new(),
+            held_peaks: Vec::new(),
+            clip_times: Vec::new(),
+            latest: Vec::new(),
         }
     }
 
     pub fn view(&self) -> Element<Message> {
-        ChartWidget::new(self)
-            .width(Length::Fill)
-            .height(Length::Fill)
+        let readouts = self.latest.iter().enumerate().map(|(i, peaks)| {
+            let peak = Level::dbfs(peaks.true_peak).max(self.bars.min_db);
+            let crest = Decibels::from_amplitude_ratio(peaks.crest_factor);
+            let clips = widget::text(format!("{} clips", peaks.clips));
+            widget::row![
+                widget::text(format!("ch{}", i)),
+                widget::text(format!("{:.1} dBTP", f32::from(peak))),
+                widget::text(format!("crest {}", crest)),
+                if peaks.clips > 0 {
+                    clips.color(iced::Color::from_rgb(0.8, 0., 0.))
+                } else {
+                    clips
+                },
+            ]
+            .spacing(10)
             .into()
+        });
+        widget::column![
+            widget::row![
+                widget::text("Range"),
+                widget::slider(-120f32..=-20f32, self.bars.min_db, Message::MinLevel)
+                    .width(Length::Fixed(150.)),
+                widget::text(format!("{} dB", self.bars.min_db)),
+                widget::pick_list(
+                    &Ballistics::ALL[..],
+                    Some(self.ballistics),
+                    Message::Ballistics
+                ),
+                widget::button("Reset clips").on_press(Message::ResetClips),
+            ]
+            .spacing(10)
+            .align_y(iced::Alignment::Center),
+            widget::row(readouts)
+                .spacing(20)
+                .align_y(iced::Alignment::Center),
+            widget::row![
+                ChartWidget::new(&self.bars)
+                    .width(Length::Fixed(50. + 40. * self.bars.powers.len() as f32))
+                    .height(Length::Fill),
+                ChartWidget::new(self)
+                    .width(Length::Fill)
+                    .height(Length::Fill),
+            ],
+        ]
+        .into()
+    }
+
+    pub fn configure(&mut self, message: Message) {
+        match message {
+            Message::MinLevel(db) => self.bars.min_db = db,
+            Message::Ballistics(ballistics) => self.ballistics = ballistics,
+            Message::ResetClips => {
+                for (peaks, clip_times) in zip(&mut self.latest, &mut self.clip_times) {
+                    peaks.clips = 0;
+                    clip_times.clear();
+                }
+                self.bars.clipped.fill(false);
+            }
+        }
+    }
+
+    pub fn update_peaks(&mut self, message: PeakLevels) {
+        if self.true_peaks.len() != message.peaks.len() {
+            // First update, which tells us the channel count
+            let channels = message.peaks.len();
+            self.peak_times.clear();
+            self.true_peaks = vec![VecDeque::new(); channels];
+            self.held_peaks = vec![(message.time, Level::dbfs(0.)); channels];
+            self.clip_times = vec![VecDeque::new(); channels];
+            self.latest = vec![ChannelPeaks::default(); channels];
+        }
+
+        // The time since the last update, over which PPM bars fall
+        let elapsed = self
+            .peak_times
+            .back()
+            .map_or(0., |t| (message.time - *t).as_secs_f32());
+        self.peak_times.push_back(message.time);
+        for (i, peaks) in message.peaks.into_iter().enumerate() {
+            if let (Ballistics::PPM, Some(meter)) = (self.ballistics, self.bars.powers.get_mut(i)) {
+                let fallen = *meter * Decibels::new(-PPM_FALL_DB_PER_SEC * elapsed).power_ratio();
+                *meter = fallen.max(peaks.true_peak * peaks.true_peak);
+            }
+            let true_peak = Level::dbfs(peaks.true_peak);
+            self.true_peaks[i].push_back(true_peak);
+            let (time, held) = self.held_peaks[i];
+            if f32::from(true_peak) >= f32::from(held) || message.time - time > PEAK_HOLD {
+                self.held_peaks[i] = (message.time, true_peak);
+            }
+            if peaks.clips > self.latest[i].clips {
+                self.clip_times[i].push_back(message.time);
+            }
+            self.latest[i] = peaks;
+        }
+
+        // Truncate the beginning of history as it ages out
+        while message.time - *self.peak_times.front().unwrap() > self.max_history {
+            self.peak_times.pop_front();
+            for ch in &mut self.true_peaks {
+                ch.pop_front();
+            }
+        }
+        for clip_times in &mut self.clip_times {
+            while clip_times
+                .front()
+                .is_some_and(|t| message.time - *t > self.max_history)
+            {
+                clip_times.pop_front();
+            }
+        }
+
+        self.bars.held_peaks = self.held_peaks.iter().map(|(_, held)| *held).collect();
+        self.bars.clipped = self.clip_times.iter().map(|c| !c.is_empty()).collect();
     }
 
     pub fn update(&mut self, message: RMSLevels) {
-        if self.times.len() == 0 {
+        if self.levels.len() != message.values.len() {
             // First update, which tells us the channel count
-            self.times.push_back(message.time);
-            self.levels.append(
-                &mut message
-                    .values
-                    .into_iter()
-                    .map(|l| {
-                        let mut v = VecDeque::new();
-                        v.push_back(Decibels::from_full_scale(l));
-                        v
-                    })
-                    .collect(),
-            );
-        } else {
-            // Append new point to existing channel buffers
-            assert_eq!(self.levels.len(), message.values.len());
-            self.times.push_back(message.time);
-            for (i, v) in message.values.into_iter().enumerate() {
-                self.levels[i].push_back(Decibels::from_full_scale(v));
+            self.times.clear();
+            self.levels = vec![VecDeque::new(); message.values.len()];
+            self.bars.powers = vec![0.; message.values.len()];
+        }
+
+        // The time since the last update, over which the meters move
+        let elapsed = self
+            .times
+            .back()
+            .map_or(0., |t| (message.time - *t).as_secs_f32());
+        for ((rms, ch), meter) in zip(message.values, &mut self.levels).zip(&mut self.bars.powers) {
+            ch.push_back(Level::dbfs(rms));
+            let power = rms * rms;
+            match self.ballistics {
+                Ballistics::Instant => *meter = power,
+                Ballistics::VU => {
+                    *meter += (1. - (-elapsed / VU_TIME_CONSTANT).exp()) * (power - *meter)
+                }
+                // Which follows the peaks instead
+                Ballistics::PPM => (),
             }
         }
+        self.times.push_back(message.time);
 
         // Truncate the beginning of history as it ages out
         while message.time - *self.times.front().unwrap() > self.max_history {
@@ -110,3 +329,82 @@ impl LevelsChart {
         }
     }
 }
+
+/// A bar meter for each channel
+struct BarMeters {
+    /// The bottom of the meters (dBFS), which is shared with the history
+    min_db: f32,
+    /// By channel, the power (FS) shown by each bar, after ballistics (for
+    /// PPM ballistics, of the true peak)
+    powers: Vec<f32>,
+    /// By channel, the highest recent true peak
+    held_peaks: Vec<Level>,
+    /// By channel, whether there have been clips recently
+    clipped: Vec<bool>,
+}
+
+impl Chart<Message> for BarMeters {
+    type State = ();
+
+    fn build_chart<DB: DrawingBackend>(&self, _state: &Self::State, mut builder: ChartBuilder<DB>) {
+        use plotters::prelude::*;
+
+        let channels = self.powers.len() as u32;
+        let mut chart = builder
+            .margin(5)
+            .x_label_area_size(30)
+            .y_label_area_size(40)
+            .build_cartesian_2d((0..channels).into_segmented(), self.min_db..0f32)
+            .expect("Failed to build chart");
+
+        chart
+            .configure_mesh()
+            .disable_x_mesh()
+            .x_label_formatter(&|x| match x {
+                SegmentValue::CenterOf(i) => format!("ch{}", i),
+                _ => String::new(),
+            })
+            .draw()
+            .expect("draw mesh");
+
+        for (i, power) in self.powers.iter().enumerate() {
+            let color = channel_color(i);
+            let ch = i as u32;
+            let level = f32::from(Level::from_power(*power, Reference::FullScale).max(self.min_db));
+            chart
+                .draw_series([Rectangle::new(
+                    [
+                        (SegmentValue::Exact(ch), self.min_db),
+                        (SegmentValue::Exact(ch + 1), level),
+                    ],
+                    color.filled(),
+                )])
+                .expect("draw series");
+
+            // The held true peak, and the clip indicator
+            if let Some(held) = self.held_peaks.get(i) {
+                let held = f32::from(held.max(self.min_db));
+                chart
+                    .draw_series(LineSeries::new(
+                        [
+                            (SegmentValue::Exact(ch), held),
+                            (SegmentValue::Exact(ch + 1), held),
+                        ],
+                        BLACK.stroke_width(2),
+                    ))
+                    .expect("draw series");
+            }
+            if self.clipped.get(i) == Some(&true) {
+                chart
+                    .draw_series([Rectangle::new(
+                        [
+                            (SegmentValue::Exact(ch), -0.5),
+                            (SegmentValue::Exact(ch + 1), 0.),
+                        ],
+                        RED.filled(),
+                    )])
+                    .expect("draw series");
+            }
+        }
+    }
+}
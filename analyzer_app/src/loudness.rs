@@ -0,0 +1,176 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use iced::{widget, Element, Length};
+use plotters_iced::{Chart, ChartBuilder, ChartWidget, DrawingBackend};
+
+use audio::dsp::loudness::Loudness;
+use audio::stream::input::Instant;
+use audio::LoudnessResult;
+
+/// The range of the loudness axis (LUFS)
+const MIN_LOUDNESS: f32 = -60.;
+const MAX_LOUDNESS: f32 = 0.;
+
+/// The EBU R 128 target for integrated loudness (LUFS)
+const TARGET: f32 = -23.;
+
+#[derive(Clone, Debug)]
+pub enum Message {
+    /// Handled by the audio thread, as well as here
+    Reset,
+}
+
+/// An EBU R 128 loudness meter (of all channels together), with a scrolling
+/// history of momentary and short-term loudness
+pub struct LoudnessChart {
+    /// The width of the chart
+    max_history: Duration,
+    /// The time value for each point
+    times: VecDeque<Instant>,
+    /// The momentary loudness at each time
+    momentary: VecDeque<Option<f32>>,
+    /// The short-term loudness at each time
+    short_term: VecDeque<Option<f32>>,
+    latest: Loudness,
+}
+
+impl LoudnessChart {
+    pub fn new(max_history: Duration) -> LoudnessChart {
+        LoudnessChart {
+            max_history,
+            times: VecDeque::new(),
+            momentary: VecDeque::new(),
+            short_term: VecDeque::new(),
+            latest: Loudness::default(),
+        }
+    }
+
+    pub fn view(&self) -> Element<Message> {
+        widget::column![
+            widget::row![
+                readout("M", self.latest.momentary, "LUFS"),
+                readout("S", self.latest.short_term, "LUFS"),
+                readout("I", self.latest.integrated, "LUFS"),
+                readout("LRA", self.latest.range, "LU"),
+                widget::button("Reset").on_press(Message::Reset),
+            ]
+            .spacing(20)
+            .align_y(iced::Alignment::Center),
+            ChartWidget::new(self)
+                .width(Length::Fill)
+                .height(Length::Fill),
+        ]
+        .into()
+    }
+
+    pub fn update(&mut self, message: &LoudnessResult) {
+        self.latest = message.loudness;
+        self.times.push_back(message.time);
+        self.momentary.push_back(message.loudness.momentary);
+        self.short_term.push_back(message.loudness.short_term);
+
+        // Truncate the beginning of history as it ages out
+        while message.time - *self.times.front().unwrap() > self.max_history {
+            self.times.pop_front();
+            self.momentary.pop_front();
+            self.short_term.pop_front();
+        }
+    }
+
+    pub fn configure(&mut self, message: Message) {
+        match message {
+            Message::Reset => {
+                self.times.clear();
+                self.momentary.clear();
+                self.short_term.clear();
+                self.latest = Loudness::default();
+            }
+        }
+    }
+}
+
+fn readout<'a>(name: &'a str, value: Option<f32>, unit: &'a str) -> Element<'a, Message> {
+    let value = match value.filter(|v| v.is_finite()) {
+        Some(v) => format!("{:.1}", v),
+        None => "-".to_string(),
+    };
+    widget::row![
+        widget::text(name),
+        widget::text(value).size(24),
+        widget::text(unit),
+    ]
+    .spacing(5)
+    .align_y(iced::Alignment::Center)
+    .into()
+}
+
+impl Chart<Message> for LoudnessChart {
+    type State = ();
+
+    fn build_chart<DB: DrawingBackend>(&self, _state: &Self::State, mut builder: ChartBuilder<DB>) {
+        use plotters::prelude::*;
+
+        let tmin = f32::from(*self.times.front().unwrap_or(&Instant::ZERO));
+        let tmax = f32::from(*self.times.back().unwrap_or(&Instant::ZERO))
+            .max(self.max_history.as_secs_f32());
+
+        let mut chart = builder
+            .margin(5)
+            .x_label_area_size(30)
+            .y_label_area_size(40)
+            .build_cartesian_2d(tmin..tmax, MIN_LOUDNESS..MAX_LOUDNESS)
+            .expect("Failed to build chart");
+
+        chart
+            .configure_mesh()
+            .x_desc("Time (s)")
+            .y_desc("Loudness (LUFS)")
+            .draw()
+            .expect("draw mesh");
+
+        let target = [(tmin, TARGET), (tmax, TARGET)];
+        chart
+            .draw_series(LineSeries::new(target, BLACK.mix(0.5)))
+            .expect("draw series")
+            .label("Target")
+            .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], BLACK.mix(0.5)));
+        if let Some(integrated) = self.latest.integrated {
+            let integrated = [(tmin, integrated), (tmax, integrated)];
+            chart
+                .draw_series(LineSeries::new(integrated, GREEN.stroke_width(2)))
+                .expect("draw series")
+                .label("Integrated")
+                .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], GREEN));
+        }
+
+        let series = |loudness: &VecDeque<Option<f32>>| {
+            self.times
+                .iter()
+                .zip(loudness)
+                .filter_map(|(t, l)| Some((f32::from(*t), l.as_ref()?.max(MIN_LOUDNESS))))
+                .collect::<Vec<_>>()
+        };
+        chart
+            .draw_series(LineSeries::new(series(&self.momentary), RED))
+            .expect("draw series")
+            .label("Momentary")
+            .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], RED));
+        chart
+            .draw_series(LineSeries::new(
+                series(&self.short_term),
+                BLUE.stroke_width(2),
+            ))
+            .expect("draw series")
+            .label("Short-term")
+            .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], BLUE));
+
+        chart
+            .configure_series_labels()
+            .position(SeriesLabelPosition::LowerLeft)
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .draw()
+            .expect("draw series labels");
+    }
+}
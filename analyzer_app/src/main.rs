@@ -1,20 +1,35 @@
 use std::thread::JoinHandle;
+use std::time::Duration;
 
 use async_channel;
-use async_channel::Receiver;
+use async_channel::{Receiver, Sender};
 use clap::Parser;
 use futures::sink::SinkExt;
 use iced::{widget, Element, Length, Padding, Subscription};
 
+mod cursors;
 mod frequencies;
 mod levels;
+mod loudness;
 mod mandelbrot;
+mod oscilloscope;
+mod pitch;
+mod sound_level;
+mod spectrogram;
+mod vowels;
 
-use audio::stream::executor::{Executor, CHANNEL_MAX};
+use audio::dsp::constant_q::ConstantQOptions;
+use audio::stream::executor::{Command, Executor, CHANNEL_MAX};
 use audio::stream::input::{ChannelCount, SampleRate};
 use audio::stream::Instant;
-use audio::Message;
 use frequencies::FrequenciesChart;
+use levels::LevelsChart;
+use loudness::LoudnessChart;
+use oscilloscope::OscilloscopeChart;
+use pitch::PitchChart;
+use sound_level::SoundLevelChart;
+use spectrogram::SpectrogramChart;
+use vowels::VowelChart;
 
 #[derive(Debug, Parser)]
 struct Args {
@@ -35,12 +50,38 @@ impl Default for Args {
     }
 }
 
+// The message type that is used to update iced application state
+#[derive(Debug, Clone)]
+pub enum Message {
+    /// Analysis results from the audio thread
+    Audio(audio::Message),
+    /// Stop (or resume) updating the charts, while the audio thread runs on
+    Freeze(bool),
+    Frequencies(frequencies::Message),
+    Levels(levels::Message),
+    Loudness(loudness::Message),
+    Oscilloscope(oscilloscope::Message),
+    Pitch(pitch::Message),
+    SoundLevel(sound_level::Message),
+    Spectrogram(spectrogram::Message),
+    Vowels(vowels::Message),
+}
+
 struct Analyzer {
     time: Instant,
-    rms_levels: Vec<f32>,
+    /// Whether analysis results are being dropped, to hold the charts still
+    frozen: bool,
     _audio_thread: JoinHandle<()>,
-    audio_messages: Receiver<Message>,
+    audio_messages: Receiver<audio::Message>,
+    audio_commands: Sender<Command>,
     frequencies: FrequenciesChart,
+    levels: LevelsChart,
+    loudness: LoudnessChart,
+    oscilloscope: OscilloscopeChart,
+    pitch: PitchChart,
+    sound_level: SoundLevelChart,
+    spectrogram: SpectrogramChart,
+    vowels: VowelChart,
 }
 
 #[derive(Hash)]
@@ -51,44 +92,161 @@ enum SubscriptionId {
 impl Analyzer {
     fn new(args: Args) -> Analyzer {
         let (sender, audio_messages) = async_channel::bounded(CHANNEL_MAX);
+        let (audio_commands, receiver) = async_channel::bounded(CHANNEL_MAX);
         let executor = Executor::new(
             sender,
+            receiver,
             ChannelCount::new(args.channels),
             SampleRate::new(args.sample_rate),
         );
 
         Analyzer {
             time: Instant::default(),
-            rms_levels: Vec::new(),
+            frozen: false,
             _audio_thread: executor.start(),
             audio_messages,
+            audio_commands,
             frequencies: FrequenciesChart::new(),
+            levels: LevelsChart::new(Duration::from_secs(10)),
+            loudness: LoudnessChart::new(Duration::from_secs(30)),
+            oscilloscope: OscilloscopeChart::new(),
+            pitch: PitchChart::new(Duration::from_secs(10)),
+            sound_level: SoundLevelChart::new(Duration::from_secs(60)),
+            spectrogram: SpectrogramChart::new(Duration::from_secs(10)),
+            vowels: VowelChart::new(Duration::from_secs(2)),
         }
     }
 }
 
 fn update(state: &mut Analyzer, message: Message) {
     match message {
-        Message::RMSLevels(l) => {
-            state.rms_levels = l.values.clone();
+        // Results are dropped while frozen, but not the stream closing
+        Message::Audio(m) if state.frozen && !matches!(m, audio::Message::AudioStreamClosed) => (),
+        Message::Audio(m) => update_audio(state, m),
+        Message::Freeze(frozen) => {
+            state.frozen = frozen;
+            // The audio thread disarms once it sends a Single trace, so if
+            // that was dropped while frozen, capture another
+            if !frozen && state.oscilloscope.awaiting_trace() {
+                let _ = state.audio_commands.try_send(Command::ArmTrigger);
+            }
+        }
+        Message::Frequencies(m) => {
+            if let frequencies::Message::LPCOrder(order) = m {
+                // Don't block the UI if the audio thread is behind; the
+                // order can just be selected again
+                let _ = state.audio_commands.try_send(Command::LPCOrder(order));
+            }
+            state.frequencies.configure(m)
+        }
+        Message::Levels(m) => {
+            if let levels::Message::ResetClips = m {
+                let _ = state.audio_commands.try_send(Command::ResetClips);
+            }
+            state.levels.configure(m)
+        }
+        Message::Loudness(m) => {
+            let loudness::Message::Reset = m;
+            let _ = state.audio_commands.try_send(Command::ResetLoudness);
+            state.loudness.configure(m)
+        }
+        Message::Oscilloscope(m) => {
+            let arm = matches!(m, oscilloscope::Message::Arm);
+            let display_only = matches!(m, oscilloscope::Message::Range(_));
+            state.oscilloscope.configure(m);
+            if arm {
+                let _ = state.audio_commands.try_send(Command::ArmTrigger);
+            } else if !display_only {
+                let options = *state.oscilloscope.options();
+                let _ = state
+                    .audio_commands
+                    .try_send(Command::Oscilloscope(options));
+            }
+        }
+        Message::Pitch(m) => state.pitch.configure(m),
+        Message::SoundLevel(m) => {
+            let reset = matches!(m, sound_level::Message::Reset);
+            state.sound_level.configure(m);
+            // Options are applied after configuring, e.g. for calibration
+            let command = if reset {
+                Command::ResetSoundLevel
+            } else {
+                Command::SoundLevel(*state.sound_level.options())
+            };
+            let _ = state.audio_commands.try_send(command);
+        }
+        Message::Spectrogram(m) => {
+            if let spectrogram::Message::BinsPerOctave(bins_per_octave) = m {
+                let options = ConstantQOptions {
+                    bins_per_octave,
+                    ..ConstantQOptions::default()
+                };
+                let _ = state.audio_commands.try_send(Command::ConstantQ(options));
+            }
+            state.spectrogram.configure(m)
+        }
+        Message::Vowels(m) => state.vowels.configure(m),
+    };
+}
+
+fn update_audio(state: &mut Analyzer, message: audio::Message) {
+    match message {
+        audio::Message::RMSLevels(l) => {
             state.time = l.time;
+            state.levels.update(l);
         }
-        Message::FFTResult(f) => {
+        audio::Message::PeakLevels(p) => state.levels.update_peaks(p),
+        audio::Message::FFTResult(f) => {
             state.time = f.end_time;
+            state.spectrogram.update(&f);
             state.frequencies.update(f);
         }
-        Message::AudioStreamClosed => todo!(),
+        audio::Message::ConstantQResult(c) => {
+            state.spectrogram.update_constant_q(&c);
+            state.frequencies.update_constant_q(c);
+        }
+        audio::Message::PSDResult(p) => state.frequencies.update_psds(p),
+        audio::Message::EnvelopeResult(e) => state.frequencies.update_envelopes(e),
+        audio::Message::FormantResult(f) => state.vowels.update(&f),
+        audio::Message::LoudnessResult(l) => state.loudness.update(&l),
+        audio::Message::PitchResult(p) => state.pitch.update(&p),
+        audio::Message::SoundLevelResult(l) => state.sound_level.update(&l),
+        audio::Message::ScopeTrace(t) => state.oscilloscope.update(t),
+        // Mel features are for export, and aren't displayed
+        audio::Message::MelResult(_) => (),
+        audio::Message::AudioStreamClosed => todo!(),
     };
 }
 
 fn view(state: &Analyzer) -> Element<Message> {
     // Wrap the UI in a Container that can be configured to fill whatever
     // the current window size is, and lay out children to use that space
-    widget::Container::new(widget::column![state.frequencies.view(),])
-        .width(Length::Fill)
-        .height(Length::Fill)
-        .padding(Padding::new(5.))
-        .into()
+    let freeze = if state.frozen {
+        widget::button("Resume").on_press(Message::Freeze(false))
+    } else {
+        widget::button("Freeze").on_press(Message::Freeze(true))
+    };
+    widget::Container::new(widget::column![
+        widget::row![freeze].spacing(5),
+        widget::row![
+            state.frequencies.view().map(Message::Frequencies),
+            state.vowels.view().map(Message::Vowels),
+            state.levels.view().map(Message::Levels),
+            state.oscilloscope.view().map(Message::Oscilloscope),
+        ]
+        .spacing(5),
+        widget::row![
+            state.pitch.view().map(Message::Pitch),
+            state.spectrogram.view().map(Message::Spectrogram),
+            state.loudness.view().map(Message::Loudness),
+            state.sound_level.view().map(Message::SoundLevel),
+        ]
+        .spacing(5),
+    ])
+    .width(Length::Fill)
+    .height(Length::Fill)
+    .padding(Padding::new(5.))
+    .into()
 }
 
 fn subscription(state: &Analyzer) -> Subscription<Message> {
@@ -100,9 +258,12 @@ fn subscription(state: &Analyzer) -> Subscription<Message> {
             |mut output| async move {
                 loop {
                     match audio_messages.recv().await {
-                        Ok(m) => output.send(m).await.unwrap(),
+                        Ok(m) => output.send(Message::Audio(m)).await.unwrap(),
                         Err(_) => {
-                            output.send(Message::AudioStreamClosed).await.unwrap();
+                            output
+                                .send(Message::Audio(audio::Message::AudioStreamClosed))
+                                .await
+                                .unwrap();
                             return;
                         }
                     }
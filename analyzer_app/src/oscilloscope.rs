@@ -0,0 +1,227 @@
+use std::time::Duration;
+
+use iced::mouse::Cursor;
+use iced::widget::canvas;
+use iced::{event, widget, Element, Length, Rectangle};
+use plotters_iced::{Chart, ChartBuilder, ChartWidget, DrawingBackend};
+
+use audio::dsp::oscilloscope::{Edge, ScopeOptions, TriggerMode};
+use audio::ScopeTrace;
+use charts::{channel_color, Cursors};
+
+/// The timebases that can be selected, i.e. the width of the chart (ms)
+const SWEEPS: [u64; 9] = [1, 2, 5, 10, 20, 50, 100, 200, 500];
+
+/// The vertical scales that can be selected, i.e. the top of the chart (FS)
+const RANGES: [f32; 7] = [1., 0.5, 0.2, 0.1, 0.05, 0.02, 0.01];
+
+#[derive(Clone, Debug)]
+pub enum Message {
+    /// In milliseconds
+    Sweep(u64),
+    Range(f32),
+    Mode(TriggerMode),
+    Edge(Edge),
+    Level(f32),
+    Channel(usize),
+    /// Capture the next trigger, in Single mode. This (and every message
+    /// but Range) is handled by the audio thread, as well as here
+    Arm,
+}
+
+/// An oscilloscope, showing triggered traces of all channels overlaid
+pub struct OscilloscopeChart {
+    options: ScopeOptions,
+    /// The top of the chart (FS), and minus the bottom
+    range: f32,
+    trace: Option<ScopeTrace>,
+    /// Whether a trace is awaited in Single mode
+    armed: bool,
+}
+
+impl OscilloscopeChart {
+    pub fn new() -> OscilloscopeChart {
+        OscilloscopeChart {
+            options: ScopeOptions::default(),
+            range: RANGES[0],
+            trace: None,
+            armed: true,
+        }
+    }
+
+    pub fn options(&self) -> &ScopeOptions {
+        &self.options
+    }
+
+    /// Whether a trace is awaited in Single mode
+    pub fn awaiting_trace(&self) -> bool {
+        self.options.mode == TriggerMode::Single && self.armed
+    }
+
+    pub fn view(&self) -> Element<Message> {
+        let channels: Vec<usize> = match &self.trace {
+            Some(trace) => (0..trace.channels.len()).collect(),
+            None => vec![self.options.channel],
+        };
+        let status = match self.options.mode {
+            TriggerMode::Single if !self.armed => "Stopped",
+            _ if self.trace.as_ref().is_some_and(|t| t.trigger.is_some()) => "Triggered",
+            TriggerMode::Auto if self.trace.is_some() => "Free running",
+            _ => "Waiting",
+        };
+        widget::column![
+            widget::row![
+                widget::pick_list(
+                    &SWEEPS[..],
+                    Some(self.options.sweep.as_millis() as u64),
+                    Message::Sweep
+                ),
+                widget::text("ms"),
+                widget::pick_list(&RANGES[..], Some(self.range), Message::Range),
+                widget::text("FS"),
+                widget::pick_list(
+                    &TriggerMode::ALL[..],
+                    Some(self.options.mode),
+                    Message::Mode
+                ),
+                widget::pick_list(&Edge::ALL[..], Some(self.options.edge), Message::Edge),
+                widget::text("ch"),
+                widget::pick_list(channels, Some(self.options.channel), Message::Channel),
+                widget::text("Level"),
+                widget::slider(-1f32..=1f32, self.options.level, Message::Level)
+                    .step(0.01)
+                    .width(Length::Fixed(100.)),
+                widget::text(format!("{:.2}", self.options.level)),
+                widget::button("Arm").on_press_maybe(
+                    (self.options.mode == TriggerMode::Single).then_some(Message::Arm)
+                ),
+                widget::text(status),
+            ]
+            .spacing(10)
+            .align_y(iced::Alignment::Center),
+            ChartWidget::new(self)
+                .width(Length::Fill)
+                .height(Length::Fill),
+        ]
+        .into()
+    }
+
+    pub fn update(&mut self, message: ScopeTrace) {
+        if self.options.mode == TriggerMode::Single {
+            if !self.armed {
+                return;
+            }
+            self.armed = false;
+        }
+        self.trace = Some(message);
+    }
+
+    pub fn configure(&mut self, message: Message) {
+        // As the audio thread re-arms when its options change
+        if !matches!(message, Message::Range(_)) {
+            self.armed = true;
+        }
+        match message {
+            Message::Sweep(ms) => self.options.sweep = Duration::from_millis(ms),
+            Message::Range(range) => self.range = range,
+            Message::Mode(mode) => self.options.mode = mode,
+            Message::Edge(edge) => self.options.edge = edge,
+            Message::Level(level) => self.options.level = level,
+            Message::Channel(channel) => self.options.channel = channel,
+            Message::Arm => (),
+        }
+    }
+}
+
+impl Chart<Message> for OscilloscopeChart {
+    type State = Cursors;
+
+    fn build_chart<DB: DrawingBackend>(&self, state: &Self::State, mut builder: ChartBuilder<DB>) {
+        use plotters::prelude::*;
+
+        // Times are relative to the trigger (ms)
+        let sweep = self.options.sweep.as_secs_f32() * 1000.;
+        let pre = sweep * self.options.pre_trigger;
+
+        let mut chart = builder
+            .margin(5)
+            .x_label_area_size(30)
+            .y_label_area_size(40)
+            .build_cartesian_2d(-pre..sweep - pre, -self.range..self.range)
+            .expect("Failed to build chart");
+
+        chart
+            .configure_mesh()
+            .x_desc("Time (ms)")
+            .draw()
+            .expect("draw mesh");
+
+        let level = self.options.level;
+        chart
+            .draw_series(LineSeries::new(
+                [(-pre, level), (sweep - pre, level)],
+                BLACK.mix(0.3),
+            ))
+            .expect("draw series");
+
+        charts::draw_cursors(
+            &mut chart,
+            state,
+            |(t, v)| ((t, v), format!("{:.3} ms, {:.3} FS", t, v)),
+            |(t0, v0), (t1, v1)| {
+                let dt = t1 - t0;
+                format!(
+                    "Δ {:.3} ms (= {:.1} Hz), {:+.3} FS",
+                    dt,
+                    1000. / dt.abs(),
+                    v1 - v0
+                )
+            },
+        )
+        .expect("draw cursors");
+
+        let Some(trace) = &self.trace else {
+            return;
+        };
+        let fs = f32::from(trace.sample_rate);
+        // Free running traces are drawn as if triggered at the usual time
+        let trigger = trace.trigger.map_or(pre, |t| t * 1000.);
+        for (i, samples) in trace.channels.iter().enumerate() {
+            let color = channel_color(i);
+            chart
+                .draw_series(LineSeries::new(
+                    samples
+                        .iter()
+                        .enumerate()
+                        .map(|(n, x)| (n as f32 / fs * 1000. - trigger, *x)),
+                    color,
+                ))
+                .expect("draw series")
+                .label(format!("ch{}", i))
+                .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+        }
+        if trace.trigger.is_some() {
+            chart
+                .draw_series([TriangleMarker::new((0., level), 6, RED.filled())])
+                .expect("draw series");
+        }
+
+        chart
+            .configure_series_labels()
+            .position(SeriesLabelPosition::LowerLeft)
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .draw()
+            .expect("draw series labels");
+    }
+
+    fn update(
+        &self,
+        state: &mut Self::State,
+        event: canvas::Event,
+        bounds: Rectangle,
+        cursor: Cursor,
+    ) -> (event::Status, Option<Message>) {
+        crate::cursors::update(state, event, bounds, cursor)
+    }
+}
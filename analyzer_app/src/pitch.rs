@@ -0,0 +1,211 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Duration;
+
+use iced::{widget, Element, Length};
+use plotters_iced::{Chart, ChartBuilder, ChartWidget, DrawingBackend};
+
+use audio::dsp::pitch::PitchEstimate;
+use audio::dsp::tuning::{Temperament, Tuning, NOTE_NAMES};
+use audio::dsp::Hz;
+use audio::stream::input::Instant;
+use audio::PitchResult;
+
+/// The range of the pitch axis (Hz)
+const MIN_FREQUENCY: f32 = 60.;
+const MAX_FREQUENCY: f32 = 1000.;
+
+#[derive(Clone, Debug)]
+pub enum Message {
+    Scale(PitchScale),
+    A4(f32),
+    Temperament(Temperament),
+    Tonic(&'static str),
+}
+
+/// How the pitch axis is labelled (it's logarithmic in frequency either way)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PitchScale {
+    Frequency,
+    Semitones,
+}
+
+impl PitchScale {
+    const ALL: [PitchScale; 2] = [PitchScale::Frequency, PitchScale::Semitones];
+}
+
+impl fmt::Display for PitchScale {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            PitchScale::Frequency => "Frequency",
+            PitchScale::Semitones => "Semitones",
+        })
+    }
+}
+
+/// A scrolling pitch contour (of the first channel), and a tuner readout of
+/// the latest pitch
+pub struct PitchChart {
+    /// The width of the chart
+    max_history: Duration,
+    /// The time value for each point
+    times: VecDeque<Instant>,
+    /// The fundamental frequency at each time, if voiced
+    pitches: VecDeque<Option<Hz>>,
+    latest: Option<PitchEstimate>,
+    scale: PitchScale,
+    tuning: Tuning,
+}
+
+impl PitchChart {
+    pub fn new(max_history: Duration) -> PitchChart {
+        PitchChart {
+            max_history,
+            times: VecDeque::new(),
+            pitches: VecDeque::new(),
+            latest: None,
+            scale: PitchScale::Semitones,
+            tuning: Tuning::default(),
+        }
+    }
+
+    pub fn view(&self) -> Element<Message> {
+        widget::column![
+            widget::row![
+                widget::pick_list(&PitchScale::ALL[..], Some(self.scale), Message::Scale),
+                widget::text("A4"),
+                widget::slider(400f32..=480f32, self.tuning.a4.0, Message::A4)
+                    .width(Length::Fixed(100.)),
+                widget::text(format!("{} Hz", self.tuning.a4.0)),
+                widget::pick_list(
+                    &Temperament::ALL[..],
+                    Some(self.tuning.temperament),
+                    Message::Temperament
+                ),
+                widget::text("in"),
+                widget::pick_list(
+                    &NOTE_NAMES[..],
+                    Some(NOTE_NAMES[self.tuning.tonic]),
+                    Message::Tonic
+                ),
+            ]
+            .spacing(10)
+            .align_y(iced::Alignment::Center),
+            self.tuner(),
+            ChartWidget::new(self)
+                .width(Length::Fill)
+                .height(Length::Fill),
+        ]
+        .into()
+    }
+
+    /// The nearest note to the latest pitch, and how far off it is
+    fn tuner(&self) -> Element<Message> {
+        let (note, cents, frequency) = match self.latest.filter(|p| p.voiced) {
+            Some(pitch) => {
+                let note = self.tuning.nearest(pitch.frequency);
+                (
+                    note.to_string(),
+                    format!("{:+.0} cents", note.cents),
+                    format!("{:.1} Hz", pitch.frequency.0),
+                )
+            }
+            None => ("-".to_string(), String::new(), String::new()),
+        };
+        widget::row![
+            widget::text(note).size(32),
+            widget::text(cents).size(20),
+            widget::text(frequency),
+        ]
+        .spacing(20)
+        .align_y(iced::Alignment::Center)
+        .into()
+    }
+
+    pub fn update(&mut self, message: &PitchResult) {
+        let Some(pitch) = message.pitches.first() else {
+            return;
+        };
+        self.latest = Some(*pitch);
+        self.times.push_back(message.end_time);
+        self.pitches
+            .push_back(Some(pitch.frequency).filter(|_| pitch.voiced));
+
+        // Truncate the beginning of history as it ages out
+        while message.end_time - *self.times.front().unwrap() > self.max_history {
+            self.times.pop_front();
+            self.pitches.pop_front();
+        }
+    }
+
+    pub fn configure(&mut self, message: Message) {
+        match message {
+            Message::Scale(scale) => self.scale = scale,
+            Message::A4(a4) => self.tuning.a4 = Hz(a4),
+            Message::Temperament(temperament) => self.tuning.temperament = temperament,
+            Message::Tonic(name) => {
+                self.tuning.tonic = NOTE_NAMES.iter().position(|n| *n == name).unwrap_or(0)
+            }
+        }
+    }
+}
+
+impl Chart<Message> for PitchChart {
+    type State = ();
+
+    fn build_chart<DB: DrawingBackend>(&self, _state: &Self::State, mut builder: ChartBuilder<DB>) {
+        use plotters::prelude::*;
+
+        let tmin = f32::from(*self.times.front().unwrap_or(&Instant::ZERO));
+        let tmax = f32::from(*self.times.back().unwrap_or(&Instant::ZERO))
+            .max(self.max_history.as_secs_f32());
+
+        // Plot (fractional) note numbers, which are log frequency, and label
+        // them according to the scale
+        let note = |f: f32| self.tuning.note_number(Hz(f));
+        let mut chart = builder
+            .margin(5)
+            .x_label_area_size(30)
+            .y_label_area_size(60)
+            .build_cartesian_2d(tmin..tmax, note(MIN_FREQUENCY)..note(MAX_FREQUENCY))
+            .expect("Failed to build chart");
+
+        let label = |n: &f32| match self.scale {
+            PitchScale::Frequency => {
+                format!("{:.0} Hz", self.tuning.a4.0 * 2f32.powf((n - 69.) / 12.))
+            }
+            PitchScale::Semitones => {
+                let n = n.round() as i32;
+                format!(
+                    "{}{}",
+                    NOTE_NAMES[n.rem_euclid(12) as usize],
+                    n.div_euclid(12) - 1
+                )
+            }
+        };
+        chart
+            .configure_mesh()
+            .y_labels(16)
+            .y_label_formatter(&label)
+            .x_desc("Time (s)")
+            .draw()
+            .expect("draw mesh");
+
+        // One line per voiced run of periods
+        let mut runs: Vec<Vec<(f32, f32)>> = Vec::new();
+        let mut run = Vec::new();
+        for (t, pitch) in self.times.iter().zip(&self.pitches) {
+            match pitch {
+                Some(f) => run.push((f32::from(*t), note(f.0))),
+                None if !run.is_empty() => runs.push(std::mem::take(&mut run)),
+                None => (),
+            }
+        }
+        runs.push(run);
+        for run in runs {
+            chart
+                .draw_series(LineSeries::new(run, BLUE.stroke_width(2)).point_size(2))
+                .expect("draw series");
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use iced::{widget, Element, Length};
+use plotters_iced::{Chart, ChartBuilder, ChartWidget, DrawingBackend};
+
+use audio::dsp::sound_level::{FrequencyWeighting, SoundLevel, SoundLevelOptions, TimeWeighting};
+use audio::dsp::{Level, Reference};
+use audio::stream::input::Instant;
+use audio::SoundLevelResult;
+
+/// The Leq intervals (seconds) that can be selected
+const INTERVALS: [u64; 4] = [1, 10, 60, 600];
+
+/// The level of a typical acoustic calibrator
+const CALIBRATOR_LEVEL: f32 = 94.;
+
+/// The span of the level axis, below full scale (dB)
+const DYNAMIC_RANGE: f32 = 100.;
+
+#[derive(Clone, Debug)]
+pub enum Message {
+    FrequencyWeighting(FrequencyWeighting),
+    TimeWeighting(TimeWeighting),
+    /// In seconds
+    Interval(u64),
+    /// Take the current level to be that of a calibrator
+    Calibrate,
+    /// Go back to dBFS
+    ClearCalibration,
+    /// Handled by the audio thread, as well as here
+    Reset,
+}
+
+/// A sound level meter (of the first channel), with a scrolling history of
+/// the time weighted level and Leq
+pub struct SoundLevelChart {
+    options: SoundLevelOptions,
+    /// The width of the chart
+    max_history: Duration,
+    /// The time value for each point
+    times: VecDeque<Instant>,
+    /// The time weighted level at each time
+    levels: VecDeque<Level>,
+    /// The Leq of the latest complete interval at each time
+    interval_leqs: VecDeque<Option<Level>>,
+    latest: Option<SoundLevel>,
+}
+
+impl SoundLevelChart {
+    pub fn new(max_history: Duration) -> SoundLevelChart {
+        SoundLevelChart {
+            options: SoundLevelOptions::default(),
+            max_history,
+            times: VecDeque::new(),
+            levels: VecDeque::new(),
+            interval_leqs: VecDeque::new(),
+            latest: None,
+        }
+    }
+
+    pub fn options(&self) -> &SoundLevelOptions {
+        &self.options
+    }
+
+    pub fn view(&self) -> Element<Message> {
+        let latest = self.latest.as_ref();
+        let name = format!(
+            "L{}{}",
+            self.options.frequency_weighting,
+            &self.options.time_weighting.to_string()[..1]
+        );
+        let leq = format!("L{}eq", self.options.frequency_weighting);
+        widget::column![
+            widget::row![
+                widget::pick_list(
+                    &FrequencyWeighting::ALL[..],
+                    Some(self.options.frequency_weighting),
+                    Message::FrequencyWeighting
+                ),
+                widget::pick_list(
+                    &TimeWeighting::ALL[..],
+                    Some(self.options.time_weighting),
+                    Message::TimeWeighting
+                ),
+                widget::text("Leq every"),
+                widget::pick_list(
+                    &INTERVALS[..],
+                    Some(self.options.interval.as_secs()),
+                    Message::Interval
+                ),
+                widget::text("s"),
+                widget::button(widget::text(format!(
+                    "Calibrate {} dB SPL",
+                    CALIBRATOR_LEVEL
+                )))
+                .on_press_maybe(latest.map(|_| Message::Calibrate)),
+                widget::button("Uncalibrate").on_press_maybe(
+                    (self.options.full_scale.reference() != Reference::FullScale)
+                        .then_some(Message::ClearCalibration)
+                ),
+                widget::button("Reset").on_press(Message::Reset),
+            ]
+            .spacing(10)
+            .align_y(iced::Alignment::Center),
+            widget::row![
+                readout(name, latest.map(|l| l.level)),
+                readout(leq.clone(), latest.and_then(|l| l.interval_leq)),
+                readout(format!("{} total", leq), latest.and_then(|l| l.leq)),
+                readout("L10".to_string(), latest.and_then(|l| l.l10)),
+                readout("L90".to_string(), latest.and_then(|l| l.l90)),
+                readout("Max".to_string(), latest.map(|l| l.max)),
+            ]
+            .spacing(20)
+            .align_y(iced::Alignment::Center),
+            ChartWidget::new(self)
+                .width(Length::Fill)
+                .height(Length::Fill),
+        ]
+        .into()
+    }
+
+    pub fn update(&mut self, message: &SoundLevelResult) {
+        let Some(level) = message.levels.first() else {
+            return;
+        };
+        self.latest = Some(*level);
+        self.times.push_back(message.end_time);
+        self.levels.push_back(level.level);
+        self.interval_leqs.push_back(level.interval_leq);
+
+        // Truncate the beginning of history as it ages out
+        while message.end_time - *self.times.front().unwrap() > self.max_history {
+            self.times.pop_front();
+            self.levels.pop_front();
+            self.interval_leqs.pop_front();
+        }
+    }
+
+    pub fn configure(&mut self, message: Message) {
+        match message {
+            Message::FrequencyWeighting(weighting) => {
+                self.options.frequency_weighting = weighting;
+                self.clear();
+            }
+            Message::TimeWeighting(weighting) => {
+                self.options.time_weighting = weighting;
+                self.clear();
+            }
+            Message::Interval(secs) => {
+                self.options.interval = Duration::from_secs(secs);
+                self.clear();
+            }
+            Message::Calibrate => {
+                if let Some(latest) = self.latest {
+                    // The uncalibrated level, in dBFS
+                    let measured = Level::new(0., Reference::FullScale)
+                        + (latest.level - self.options.full_scale);
+                    self.options
+                        .calibrate(measured, Level::new(CALIBRATOR_LEVEL, Reference::SPL));
+                    self.clear();
+                }
+            }
+            Message::ClearCalibration => {
+                self.options.full_scale = SoundLevelOptions::default().full_scale;
+                self.clear();
+            }
+            Message::Reset => self.clear(),
+        }
+    }
+
+    /// Forget levels that were measured with other options
+    fn clear(&mut self) {
+        self.times.clear();
+        self.levels.clear();
+        self.interval_leqs.clear();
+        self.latest = None;
+    }
+}
+
+fn readout<'a>(name: String, level: Option<Level>) -> Element<'a, Message> {
+    let value = match level.filter(|l| f32::from(*l).is_finite()) {
+        Some(l) => l.to_string(),
+        None => "-".to_string(),
+    };
+    widget::row![widget::text(name), widget::text(value).size(24)]
+        .spacing(5)
+        .align_y(iced::Alignment::Center)
+        .into()
+}
+
+impl Chart<Message> for SoundLevelChart {
+    type State = ();
+
+    fn build_chart<DB: DrawingBackend>(&self, _state: &Self::State, mut builder: ChartBuilder<DB>) {
+        use plotters::prelude::*;
+
+        let tmin = f32::from(*self.times.front().unwrap_or(&Instant::ZERO));
+        let tmax = f32::from(*self.times.back().unwrap_or(&Instant::ZERO))
+            .max(self.max_history.as_secs_f32());
+        let max_db = f32::from(self.options.full_scale);
+        let min_db = max_db - DYNAMIC_RANGE;
+
+        let mut chart = builder
+            .margin(5)
+            .x_label_area_size(30)
+            .y_label_area_size(40)
+            .build_cartesian_2d(tmin..tmax, min_db..max_db)
+            .expect("Failed to build chart");
+
+        chart
+            .configure_mesh()
+            .x_desc("Time (s)")
+            .y_desc(format!("Level ({})", self.options.full_scale.reference()))
+            .draw()
+            .expect("draw mesh");
+
+        chart
+            .draw_series(LineSeries::new(
+                self.times
+                    .iter()
+                    .zip(&self.levels)
+                    .map(|(t, l)| (f32::from(*t), f32::from(l.max(min_db)))),
+                BLUE,
+            ))
+            .expect("draw series")
+            .label(format!(
+                "L{}{}",
+                self.options.frequency_weighting, self.options.time_weighting
+            ))
+            .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], BLUE));
+        chart
+            .draw_series(LineSeries::new(
+                self.times
+                    .iter()
+                    .zip(&self.interval_leqs)
+                    .filter_map(|(t, l)| Some((f32::from(*t), f32::from(l.as_ref()?.max(min_db))))),
+                RED.stroke_width(2),
+            ))
+            .expect("draw series")
+            .label(format!("L{}eq", self.options.frequency_weighting))
+            .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], RED));
+
+        chart
+            .configure_series_labels()
+            .position(SeriesLabelPosition::LowerLeft)
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .draw()
+            .expect("draw series labels");
+    }
+}
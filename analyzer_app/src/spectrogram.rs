@@ -0,0 +1,155 @@
+use std::fmt;
+use std::time::Duration;
+
+use iced::mouse::Cursor;
+use iced::widget::canvas;
+use iced::{event, widget, Element, Length, Rectangle};
+use plotters_iced::{Chart, ChartBuilder, ChartWidget, DrawingBackend};
+
+use audio::dsp::constant_q::ConstantQOptions;
+use audio::dsp::spectrogram::Spectrogram;
+use audio::{ConstantQResult, FFTResult};
+use charts::{Cursors, FrequencyScale, SpectrogramOptions};
+
+#[derive(Clone, Debug)]
+pub enum Message {
+    MaxLevel(f32),
+    DynamicRange(f32),
+    FrequencyScale(FrequencyScale),
+    Transform(Transform),
+    /// Handled by the audio thread, as well as here
+    BinsPerOctave(usize),
+}
+
+/// Which transform's spectra are shown
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transform {
+    FFT,
+    ConstantQ,
+}
+
+impl Transform {
+    const ALL: [Transform; 2] = [Transform::FFT, Transform::ConstantQ];
+}
+
+impl fmt::Display for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Transform::FFT => "FFT",
+            Transform::ConstantQ => "Constant-Q",
+        })
+    }
+}
+
+/// The constant-Q resolutions that can be selected
+const BINS_PER_OCTAVE: [usize; 4] = [12, 24, 36, 48];
+
+/// A scrolling waterfall of recent spectra (of the first channel)
+pub struct SpectrogramChart {
+    spectrogram: Spectrogram,
+    options: SpectrogramOptions,
+    transform: Transform,
+    bins_per_octave: usize,
+}
+
+impl SpectrogramChart {
+    pub fn new(max_history: Duration) -> SpectrogramChart {
+        SpectrogramChart {
+            spectrogram: Spectrogram::new(max_history),
+            options: SpectrogramOptions::default(),
+            transform: Transform::FFT,
+            bins_per_octave: ConstantQOptions::default().bins_per_octave,
+        }
+    }
+
+    pub fn view(&self) -> Element<Message> {
+        widget::column![
+            widget::row![
+                widget::text("Level"),
+                widget::slider(-60f32..=0f32, self.options.max_db, Message::MaxLevel)
+                    .width(Length::Fixed(150.)),
+                widget::text(format!("{} dB", self.options.max_db)),
+                widget::text("Range"),
+                widget::slider(
+                    20f32..=120f32,
+                    self.options.dynamic_range_db,
+                    Message::DynamicRange
+                )
+                .width(Length::Fixed(150.)),
+                widget::text(format!("{} dB", self.options.dynamic_range_db)),
+                widget::pick_list(
+                    &FrequencyScale::ALL[..],
+                    Some(self.options.frequency_scale),
+                    Message::FrequencyScale
+                ),
+                widget::pick_list(
+                    &Transform::ALL[..],
+                    Some(self.transform),
+                    Message::Transform
+                ),
+                widget::pick_list(
+                    &BINS_PER_OCTAVE[..],
+                    Some(self.bins_per_octave),
+                    Message::BinsPerOctave
+                ),
+                widget::text("bins/octave"),
+            ]
+            .spacing(10)
+            .align_y(iced::Alignment::Center),
+            ChartWidget::new(self)
+                .width(Length::Fill)
+                .height(Length::Fill),
+        ]
+        .into()
+    }
+
+    pub fn update(&mut self, message: &FFTResult) {
+        if self.transform != Transform::FFT {
+            return;
+        }
+        if let Some(fft) = message.ffts.first() {
+            self.spectrogram.push(message.end_time, fft);
+        }
+    }
+
+    pub fn update_constant_q(&mut self, message: &ConstantQResult) {
+        if self.transform != Transform::ConstantQ {
+            return;
+        }
+        if let Some(spectrum) = message.spectra.first() {
+            self.spectrogram.push_constant_q(message.end_time, spectrum);
+        }
+    }
+
+    pub fn configure(&mut self, message: Message) {
+        match message {
+            Message::MaxLevel(db) => self.options.max_db = db,
+            Message::DynamicRange(db) => self.options.dynamic_range_db = db,
+            Message::FrequencyScale(scale) => self.options.frequency_scale = scale,
+            Message::Transform(transform) => {
+                self.transform = transform;
+                self.spectrogram.clear();
+            }
+            Message::BinsPerOctave(bins) => self.bins_per_octave = bins,
+        }
+    }
+}
+
+impl Chart<Message> for SpectrogramChart {
+    type State = Cursors;
+
+    fn build_chart<DB: DrawingBackend>(&self, state: &Self::State, builder: ChartBuilder<DB>) {
+        charts::build_spectrogram_chart(builder, &self.spectrogram, state, &self.options)
+            .expect("Failed to build chart");
+    }
+
+    fn update(
+        &self,
+        state: &mut Self::State,
+        event: canvas::Event,
+        bounds: Rectangle,
+        cursor: Cursor,
+    ) -> (event::Status, Option<Message>) {
+        crate::cursors::update(state, event, bounds, cursor)
+    }
+}
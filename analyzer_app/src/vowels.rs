@@ -0,0 +1,152 @@
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::time::Duration;
+
+use iced::{widget, Element, Length};
+use plotters_iced::{Chart, ChartBuilder, ChartWidget, DrawingBackend};
+
+use audio::dsp::vowels::{SpeakerVowels, VowelPoint, VowelReferences, VowelTrail};
+use audio::stream::input::Instant;
+use audio::FormantResult;
+
+#[derive(Clone, Debug)]
+pub enum Message {
+    ShowReferences(bool),
+    References(VowelReferences),
+    SpeakerName(String),
+    /// Save the current trail's points for the named speaker
+    Save,
+    ClearSaved,
+}
+
+/// Recent formants (of the first channel) in the F1/F2 plane, with saved
+/// sets of points for each speaker
+pub struct VowelChart {
+    trail: VowelTrail,
+    show_references: bool,
+    references: VowelReferences,
+    speaker: String,
+    speakers: Vec<SpeakerVowels>,
+    /// The time of the latest point saved, so that points still in the
+    /// trail aren't saved again
+    saved_until: Option<Instant>,
+}
+
+impl VowelChart {
+    pub fn new(max_history: Duration) -> VowelChart {
+        VowelChart {
+            trail: VowelTrail::new(max_history),
+            show_references: true,
+            references: VowelReferences::Men,
+            speaker: String::new(),
+            speakers: Vec::new(),
+            saved_until: None,
+        }
+    }
+
+    pub fn view(&self) -> Element<Message> {
+        let mut save = widget::button("Save");
+        if !self.speaker.is_empty() && self.unsaved_points().next().is_some() {
+            save = save.on_press(Message::Save);
+        }
+        widget::column![
+            widget::row![
+                widget::checkbox("Reference vowels", self.show_references)
+                    .on_toggle(Message::ShowReferences),
+                widget::pick_list(
+                    &VowelReferences::ALL[..],
+                    Some(self.references),
+                    Message::References
+                ),
+                widget::text_input("Speaker", &self.speaker)
+                    .on_input(Message::SpeakerName)
+                    .on_submit(Message::Save)
+                    .width(Length::Fixed(120.)),
+                save,
+                widget::button("Clear").on_press(Message::ClearSaved),
+            ]
+            .spacing(10)
+            .align_y(iced::Alignment::Center),
+            ChartWidget::new(self)
+                .width(Length::Fill)
+                .height(Length::Fill),
+        ]
+        .into()
+    }
+
+    pub fn update(&mut self, message: &FormantResult) {
+        if let Some(formants) = message.formants.first() {
+            self.trail.push(message.end_time, formants);
+        }
+    }
+
+    pub fn configure(&mut self, message: Message) {
+        match message {
+            Message::ShowReferences(show) => self.show_references = show,
+            Message::References(references) => self.references = references,
+            Message::SpeakerName(name) => self.speaker = name,
+            Message::Save => self.save(),
+            Message::ClearSaved => self.speakers.clear(),
+        }
+    }
+
+    /// The trail's points that are newer than any saved
+    fn unsaved_points(&self) -> impl Iterator<Item = &VowelPoint> {
+        let saved_until = self.saved_until;
+        self.trail
+            .points()
+            .filter(move |p| saved_until.is_none_or(|t| f32::from(p.time) > f32::from(t)))
+    }
+
+    /// Add the trail's unsaved points to the current speaker's set, and write
+    /// that set out to a CSV file
+    fn save(&mut self) {
+        let points: Vec<VowelPoint> = self.unsaved_points().copied().collect();
+        if self.speaker.is_empty() || points.is_empty() {
+            return;
+        }
+        self.saved_until = self.trail.latest_time();
+        let speaker = match self.speakers.iter_mut().find(|s| s.speaker == self.speaker) {
+            Some(s) => {
+                s.points.extend(points);
+                s
+            }
+            None => {
+                self.speakers.push(SpeakerVowels {
+                    speaker: self.speaker.clone(),
+                    points,
+                });
+                self.speakers.last().unwrap()
+            }
+        };
+        if let Err(e) = write_csv(speaker) {
+            println!("Failed to save vowels for {}: {}", speaker.speaker, e);
+        }
+    }
+}
+
+/// Write a speaker's points to vowels_<speaker>.csv, in the working directory
+fn write_csv(speaker: &SpeakerVowels) -> io::Result<()> {
+    let name: String = speaker
+        .speaker
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    let mut file = BufWriter::new(File::create(format!("vowels_{}.csv", name))?);
+    writeln!(file, "time,f1,f2")?;
+    for p in &speaker.points {
+        writeln!(file, "{},{},{}", f32::from(p.time), p.f1.0, p.f2.0)?;
+    }
+    file.flush()
+}
+
+impl Chart<Message> for VowelChart {
+    type State = ();
+
+    fn build_chart<DB: DrawingBackend>(&self, _state: &Self::State, builder: ChartBuilder<DB>) {
+        let references = Some(self.references).filter(|_| self.show_references);
+        charts::build_vowel_chart(builder, &self.trail, references, &self.speakers)
+            .expect("Failed to build chart");
+    }
+}
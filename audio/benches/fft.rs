@@ -0,0 +1,57 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use num_complex::Complex;
+use rustfft::FftPlanner;
+
+use audio::dsp::fft::{CartesianFFT, FFTSequence};
+use audio::stream::buffer::BufferedInput;
+use audio::stream::{ChannelCount, SampleRate};
+use audio::synth::SinIterator;
+
+const SAMPLE_RATE: u32 = 44100;
+
+fn folded_fft(c: &mut Criterion) {
+    let mut group = c.benchmark_group("folded_fft");
+    for len in [1024, 8192] {
+        let mut input = BufferedInput::from_sample_input(
+            SinIterator::new(SampleRate::new(SAMPLE_RATE), 440., 0.),
+            ChannelCount::new(1),
+            SampleRate::new(SAMPLE_RATE),
+            len,
+        )
+        .unwrap();
+        let period = input.next().unwrap();
+        let channel = period.get_channel(0);
+
+        // How FFTResults used to be computed: a full complex FFT, with half of
+        // the result thrown away by into_folded
+        let fft = FftPlanner::new().plan_fft_forward(len);
+        group.bench_with_input(BenchmarkId::new("complex", len), &channel, |b, ch| {
+            b.iter(|| {
+                let mut values: Vec<Complex<f32>> =
+                    ch.iter().map(|y| Complex { re: *y, im: 0. }).collect();
+                fft.process(&mut values);
+                let res = CartesianFFT {
+                    values,
+                    sample_rate: ch.sample_rate(),
+                };
+                black_box(res.into_polar().into_folded())
+            })
+        });
+
+        let mut seq = FFTSequence::new(len);
+        group.bench_with_input(BenchmarkId::new("real", len), &channel, |b, ch| {
+            b.iter(|| black_box(seq.folded(ch)))
+        });
+    }
+    group.finish();
+}
+
+fn sequence_construction(c: &mut Criterion) {
+    // Plans are cached by the shared planner, so this should be cheap
+    c.bench_function("FFTSequence::new(8192)", |b| {
+        b.iter(|| black_box(FFTSequence::new(8192)))
+    });
+}
+
+criterion_group!(benches, folded_fft, sequence_construction);
+criterion_main!(benches);
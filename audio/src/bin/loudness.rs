@@ -0,0 +1,66 @@
+//! Report the loudness of whole .wav files, as specified by EBU R 128:
+//! integrated loudness, loudness range and maximum momentary and short-term
+//! loudness.
+//!
+//! Usage: loudness <input.wav>...
+
+use std::env;
+use std::process;
+
+use audio::dsp::loudness::{Loudness, LoudnessMeter};
+use audio::stream::wav::read_wav;
+use audio::stream::Frame;
+
+const USAGE: &str = "Usage: loudness <input.wav>...";
+
+/// How many samples (per channel) are metered at a time
+const FRAME_LEN: usize = 4096;
+
+fn main() {
+    let paths: Vec<String> = env::args().skip(1).collect();
+    if paths.is_empty() {
+        eprintln!("{}", USAGE);
+        process::exit(1);
+    }
+
+    let mut failed = false;
+    for path in paths {
+        match read_wav(&path) {
+            Ok((channels, sample_rate, samples)) => {
+                let mut meter = LoudnessMeter::new(channels, sample_rate);
+                for chunk in samples.chunks(FRAME_LEN * usize::from(channels)) {
+                    meter.push(&Frame {
+                        channels,
+                        sample_rate,
+                        samples: chunk.to_vec(),
+                    });
+                }
+                println!("{}", path);
+                report(&meter.loudness());
+            }
+            Err(e) => {
+                eprintln!("Failed to read {:?}: {}", path, e);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+fn report(loudness: &Loudness) {
+    let rows = [
+        ("Integrated loudness", loudness.integrated, "LUFS"),
+        ("Loudness range", loudness.range, "LU"),
+        ("Max momentary", loudness.max_momentary, "LUFS"),
+        ("Max short-term", loudness.max_short_term, "LUFS"),
+    ];
+    for (name, value, unit) in rows {
+        match value {
+            Some(value) => println!("  {:<20} {:>6.1} {}", name, value, unit),
+            // i.e. too short, or silent
+            None => println!("  {:<20} {:>6}", name, "-"),
+        }
+    }
+}
@@ -0,0 +1,109 @@
+//! Compute mel features (log-mel energies, MFCCs and their deltas) of the
+//! first channel of a whole .wav file, and write them to a CSV file, one row
+//! per 25 ms frame, every 10 ms.
+//!
+//! Usage: mel_features [--htk] [--bands N] [--coefficients N] <input.wav> [output.csv]
+
+use std::env;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::process;
+
+use audio::dsp::mel::{MelAnalyzer, MelFrame, MelOptions, MelScale};
+use audio::stream::buffer::{BufferedInput, FrameAccumulator};
+use audio::stream::input::InputAdapter;
+use audio::stream::wav::read_wav;
+
+const USAGE: &str =
+    "Usage: mel_features [--htk] [--bands N] [--coefficients N] <input.wav> [output.csv]";
+
+fn main() {
+    let mut options = MelOptions::default();
+    let mut paths = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--htk" => options.scale = MelScale::HTK,
+            "--bands" => options.bands = count(args.next()),
+            "--coefficients" => options.coefficients = count(args.next()),
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    let (input, output) = match paths.as_slice() {
+        [input] => (input.clone(), input.with_extension("csv")),
+        [input, output] => (input.clone(), output.clone()),
+        _ => exit(USAGE),
+    };
+
+    let (channels, sample_rate, samples) =
+        read_wav(&input).unwrap_or_else(|e| exit(&format!("Failed to read {:?}: {}", input, e)));
+    let period_len = (f32::from(sample_rate) * 0.025).round() as usize;
+    let period_stride = (f32::from(sample_rate) * 0.010).round() as usize;
+    let frames = FrameAccumulator::new(
+        channels,
+        sample_rate,
+        FrameAccumulator::DEFAULT_FRAME_LEN * usize::from(channels),
+    );
+    let mut periods = BufferedInput::overlapping(
+        InputAdapter::new(samples.into_iter(), frames),
+        period_len,
+        period_stride,
+    )
+    .unwrap_or_else(|_| exit("The input is empty"));
+
+    let mut analyzer = MelAnalyzer::new(sample_rate, period_len, options);
+    let features = analyzer
+        .analyze_input(&mut periods)
+        .expect("reading from memory can't fail");
+    if let Err(e) = write_csv(&output, &features) {
+        exit(&format!("Failed to write {:?}: {}", output, e));
+    }
+    println!("Wrote {} frames to {:?}", features.len(), output);
+}
+
+fn count(arg: Option<String>) -> usize {
+    arg.and_then(|a| a.parse().ok())
+        .unwrap_or_else(|| exit(USAGE))
+}
+
+fn exit(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1)
+}
+
+/// One row per frame: its end time, then each log-mel energy, MFCC, delta and
+/// delta-delta
+fn write_csv(path: &PathBuf, frames: &[MelFrame]) -> std::io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    let Some(first) = frames.first() else {
+        return file.flush();
+    };
+    let mut header = vec!["time".to_string()];
+    for (name, len) in [
+        ("mel", first.log_mel.len()),
+        ("mfcc", first.mfcc.len()),
+        ("delta", first.deltas.len()),
+        ("delta2", first.delta_deltas.len()),
+    ] {
+        header.extend((0..len).map(|i| format!("{}_{}", name, i)));
+    }
+    writeln!(file, "{}", header.join(","))?;
+
+    for frame in frames {
+        let values = [
+            &frame.log_mel,
+            &frame.mfcc,
+            &frame.deltas,
+            &frame.delta_deltas,
+        ]
+        .into_iter()
+        .flatten()
+        .map(|y| y.to_string());
+        let row: Vec<String> = std::iter::once(f32::from(frame.end_time).to_string())
+            .chain(values)
+            .collect();
+        writeln!(file, "{}", row.join(","))?;
+    }
+    file.flush()
+}
@@ -0,0 +1,192 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Duration;
+
+use super::fft::FoldedFFT;
+use super::Decibels;
+use crate::stream::Instant;
+
+/// How successive spectra are combined for display
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Averaging {
+    /// Just the latest spectrum
+    None,
+    /// The (power) mean of the last N spectra
+    Linear(usize),
+    /// An exponentially weighted (power) mean, with the given time constant
+    Exponential(Duration),
+    /// The maximum of each frequency, with held values decaying at the given
+    /// rate (dB/s)
+    PeakHold(f32),
+}
+
+impl fmt::Display for Averaging {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Averaging::None => f.write_str("No averaging"),
+            Averaging::Linear(n) => write!(f, "Linear ({})", n),
+            Averaging::Exponential(tau) => write!(f, "Exponential ({}s)", tau.as_secs_f32()),
+            Averaging::PeakHold(decay) => write!(f, "Peak hold ({} dB/s)", decay),
+        }
+    }
+}
+
+/// Averages a sequence of spectra.
+/// Magnitudes are averaged as power (i.e. RMS averaging), which is what
+/// makes noise floors smooth out rather than shrink. Phases are not
+/// meaningfully averageable, so are just those of the latest spectrum.
+pub struct SpectrumAverager {
+    mode: Averaging,
+    /// For Linear averaging, the power spectra being averaged
+    history: VecDeque<Vec<f32>>,
+    /// The time of the latest spectrum
+    time: Option<Instant>,
+    /// The current average (as amplitudes, like any other FoldedFFT)
+    average: Option<FoldedFFT>,
+}
+
+impl SpectrumAverager {
+    pub fn new(mode: Averaging) -> SpectrumAverager {
+        SpectrumAverager {
+            mode,
+            history: VecDeque::new(),
+            time: None,
+            average: None,
+        }
+    }
+
+    pub fn mode(&self) -> Averaging {
+        self.mode
+    }
+
+    /// Change the averaging mode, which restarts the average
+    pub fn set_mode(&mut self, mode: Averaging) {
+        self.mode = mode;
+        self.reset();
+    }
+
+    pub fn reset(&mut self) {
+        self.history.clear();
+        self.time = None;
+        self.average = None;
+    }
+
+    /// Add a spectrum, of a period ending at the given time, and get the
+    /// updated average.
+    pub fn push(&mut self, time: Instant, fft: &FoldedFFT) -> &FoldedFFT {
+        let compatible = self
+            .average
+            .as_ref()
+            .is_some_and(|avg| avg.values.len() == fft.values.len());
+        if !compatible {
+            self.reset();
+        }
+        let elapsed = self.time.map(|t| (time - t).as_secs_f32());
+        self.time = Some(time);
+
+        let mut average = fft.clone();
+        let prev = self.average.as_ref().zip(elapsed);
+        match self.mode {
+            Averaging::None => (),
+            Averaging::Linear(n) => {
+                self.history
+                    .push_back(fft.values.iter().map(|(r, _)| r * r).collect());
+                while self.history.len() > n.max(1) {
+                    self.history.pop_front();
+                }
+                let count = self.history.len() as f32;
+                for (i, y) in average.values.iter_mut().enumerate() {
+                    let total: f32 = self.history.iter().map(|h| h[i]).sum();
+                    y.0 = (total / count).sqrt();
+                }
+            }
+            Averaging::Exponential(tau) => {
+                if let Some((prev, dt)) = prev {
+                    let alpha = 1. - (-dt / tau.as_secs_f32()).exp();
+                    for (y, (prev_r, _)) in average.values.iter_mut().zip(prev.values.iter()) {
+                        let power = alpha * y.0 * y.0 + (1. - alpha) * prev_r * prev_r;
+                        y.0 = power.sqrt();
+                    }
+                }
+            }
+            Averaging::PeakHold(decay) => {
+                if let Some((prev, dt)) = prev {
+                    let decay = Decibels::new(-decay * dt).amplitude_ratio();
+                    for (y, (prev_r, _)) in average.values.iter_mut().zip(prev.values.iter()) {
+                        y.0 = y.0.max(prev_r * decay);
+                    }
+                }
+            }
+        }
+
+        self.average.insert(average)
+    }
+
+    /// The current average, if any spectra have been pushed
+    pub fn average(&self) -> Option<&FoldedFFT> {
+        self.average.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dsp::fft::CartesianFFT;
+    use crate::stream::SampleRate;
+
+    /// A FFT with the given DC level
+    fn dc(level: f32) -> FoldedFFT {
+        CartesianFFT::from_real_signal(vec![level; 4], SampleRate::new(4))
+            .into_polar()
+            .into_folded()
+    }
+
+    fn at(secs: u64) -> Instant {
+        Instant::ZERO + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn no_averaging() {
+        let mut avg = SpectrumAverager::new(Averaging::None);
+        avg.push(at(0), &dc(1.));
+        assert_abs_diff_eq!(avg.push(at(1), &dc(0.5)).values[0].0, 0.5, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn linear() {
+        let mut avg = SpectrumAverager::new(Averaging::Linear(2));
+        assert_abs_diff_eq!(avg.push(at(0), &dc(1.)).values[0].0, 1., epsilon = 1e-6);
+        // RMS of 1 and 0 is sqrt(1/2)
+        assert_abs_diff_eq!(
+            avg.push(at(1), &dc(0.)).values[0].0,
+            0.5f32.sqrt(),
+            epsilon = 1e-6
+        );
+        // The first spectrum has aged out of the average:
+        assert_abs_diff_eq!(avg.push(at(2), &dc(0.)).values[0].0, 0., epsilon = 1e-6);
+    }
+
+    #[test]
+    fn exponential() {
+        let mut avg = SpectrumAverager::new(Averaging::Exponential(Duration::from_secs(1)));
+        avg.push(at(0), &dc(1.));
+        // After one time constant, 1/e of the previous power remains
+        let expect = (-1f32).exp().sqrt();
+        assert_abs_diff_eq!(avg.push(at(1), &dc(0.)).values[0].0, expect, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn peak_hold() {
+        let mut avg = SpectrumAverager::new(Averaging::PeakHold(20.));
+        avg.push(at(0), &dc(1.));
+        // Decays by 20 dB (i.e. amplitude / 10) per second
+        assert_abs_diff_eq!(avg.push(at(1), &dc(0.)).values[0].0, 0.1, epsilon = 1e-5);
+        // And is replaced by anything larger
+        assert_abs_diff_eq!(avg.push(at(2), &dc(0.5)).values[0].0, 0.5, epsilon = 1e-5);
+
+        // Changing mode resets
+        avg.set_mode(Averaging::None);
+        assert!(avg.average().is_none());
+    }
+}
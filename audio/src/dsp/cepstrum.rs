@@ -0,0 +1,295 @@
+use std::iter::zip;
+
+use num_complex::Complex;
+
+use super::fft::{has_conjugate, FFTSequence};
+use super::Hz;
+use crate::stream::buffer::ChannelPeriod;
+use crate::stream::SampleRate;
+
+/// Amplitudes are floored at this (FS), i.e. -240 dBFS, before taking logs
+const MIN_AMPLITUDE: f32 = 1e-12;
+
+/// How much less (per octave) autocorrelation peaks at longer lags are
+/// favoured, since a periodic signal's autocorrelation peaks just as high at
+/// every multiple of its period (as in Praat)
+const OCTAVE_COST: f32 = 0.01;
+
+/// Which quefrencies liftering keeps
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lifter {
+    /// Those below the cutoff, i.e. the smooth spectral envelope (for speech,
+    /// the vocal tract filter)
+    Low,
+    /// Those at or above the cutoff, i.e. the fine harmonic structure (for
+    /// speech, the glottal source)
+    High,
+}
+
+/// The real cepstrum of a period: the inverse FFT of its log amplitude
+/// spectrum
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cepstrum {
+    /// By quefrency (in samples). Symmetric, i.e. values[n - i] == values[i].
+    pub values: Vec<f32>,
+    sample_rate: SampleRate,
+}
+
+/// Compute the real cepstrum of a period, after tapering it with the given
+/// window (see `dsp::window`). The FFT sequence may be longer than the period,
+/// in which case the period is zero-padded.
+pub fn real_cepstrum(ffts: &mut FFTSequence, period: &ChannelPeriod, window: &[f32]) -> Cepstrum {
+    assert_eq!(period.len(), window.len());
+    let n = ffts.period_len();
+    let signal: Vec<f32> = zip(period.iter(), window).map(|(y, w)| y * w).collect();
+    let spectrum = ffts.spectrum_of(&signal);
+    for (i, y) in spectrum.iter_mut().enumerate() {
+        // Normalized like FoldedFFT, so that the log spectrum is in FS
+        let scale = if has_conjugate(i, n) { 2. } else { 1. } / n as f32;
+        *y = Complex::new((y.norm() * scale).max(MIN_AMPLITUDE).ln(), 0.);
+    }
+    Cepstrum {
+        values: ffts.invert_spectrum(),
+        sample_rate: period.sample_rate(),
+    }
+}
+
+impl Cepstrum {
+    /// (quefrency in seconds, value) pairs, up to the highest unique quefrency
+    pub fn points(&self) -> impl Iterator<Item = (f32, f32)> + '_ {
+        let fs = f32::from(self.sample_rate);
+        self.values[..=self.values.len() / 2]
+            .iter()
+            .enumerate()
+            .map(move |(i, c)| (i as f32 / fs, *c))
+    }
+
+    /// The fundamental frequency, from the largest cepstral peak in the range
+    /// of quefrencies corresponding to min_frequency..max_frequency
+    pub fn fundamental(&self, min_frequency: Hz, max_frequency: Hz) -> Option<Hz> {
+        let fs = f32::from(self.sample_rate);
+        let half = &self.values[..=self.values.len() / 2];
+        let (lag, _) = highest_peak(half, fs / max_frequency.0, fs / min_frequency.0, 0.)?;
+        Some(Hz(fs / lag))
+    }
+
+    /// Zero the quefrencies on one side of a cutoff (in seconds)
+    pub fn lifter(&mut self, cutoff: f32, lifter: Lifter) {
+        let n = self.values.len();
+        let cutoff = (cutoff * f32::from(self.sample_rate)).round() as usize;
+        for (i, c) in self.values.iter_mut().enumerate() {
+            let below = i.min(n - i) < cutoff;
+            if below != (lifter == Lifter::Low) {
+                *c = 0.;
+            }
+        }
+    }
+
+    /// The log amplitude spectrum (dBFS, for each bin of the FFT the cepstrum
+    /// was computed with) that the cepstrum represents. After low-pass
+    /// liftering, this is a smoothed spectral envelope.
+    pub fn log_spectrum(&self, ffts: &mut FFTSequence) -> Vec<f32> {
+        assert_eq!(ffts.period_len(), self.values.len());
+        // The cepstrum is real and even, so its FFT is too
+        ffts.spectrum_of(&self.values)
+            .iter()
+            .map(|y| 20. * y.re * std::f32::consts::LOG10_E)
+            .collect()
+    }
+}
+
+/// The autocorrelation of a period, normalized to 1 at lag 0
+#[derive(Clone, Debug, PartialEq)]
+pub struct Autocorrelation {
+    /// By lag (in samples)
+    pub values: Vec<f32>,
+    sample_rate: SampleRate,
+}
+
+/// Compute the normalized autocorrelation of a period, after tapering it with
+/// the given window, and correct for the window's own autocorrelation
+/// (Boersma, 1993), for lags up to half the period's length. The FFT sequence
+/// must be at least twice as long as the period, so that the autocorrelation
+/// doesn't wrap around.
+pub fn normalized_autocorrelation(
+    ffts: &mut FFTSequence,
+    period: &ChannelPeriod,
+    window: &[f32],
+) -> Autocorrelation {
+    assert_eq!(period.len(), window.len());
+    assert!(ffts.period_len() >= 2 * period.len());
+    let signal: Vec<f32> = zip(period.iter(), window).map(|(y, w)| y * w).collect();
+    let signal_r = power_autocorrelation(ffts, &signal);
+    let window_r = power_autocorrelation(ffts, window);
+
+    let max_lag = period.len() / 2;
+    let values = if signal_r[0] > 0. {
+        (0..=max_lag)
+            .map(|lag| (signal_r[lag] / signal_r[0]) / (window_r[lag] / window_r[0]))
+            .collect()
+    } else {
+        vec![0.; max_lag + 1]
+    };
+    Autocorrelation {
+        values,
+        sample_rate: period.sample_rate(),
+    }
+}
+
+/// The (unnormalized) autocorrelation of a signal, as the inverse FFT of its
+/// power spectrum
+fn power_autocorrelation(ffts: &mut FFTSequence, signal: &[f32]) -> Vec<f32> {
+    for y in ffts.spectrum_of(signal).iter_mut() {
+        *y = Complex::new(y.norm_sqr(), 0.);
+    }
+    ffts.invert_spectrum()
+}
+
+impl Autocorrelation {
+    /// (lag in seconds, value) pairs
+    pub fn points(&self) -> impl Iterator<Item = (f32, f32)> + '_ {
+        let fs = f32::from(self.sample_rate);
+        self.values
+            .iter()
+            .enumerate()
+            .map(move |(i, r)| (i as f32 / fs, *r))
+    }
+
+    /// The fundamental frequency, from the largest peak in the range of lags
+    /// corresponding to min_frequency..max_frequency, and the autocorrelation
+    /// at that peak (i.e. the periodicity: 1 for a perfectly periodic signal)
+    pub fn fundamental(&self, min_frequency: Hz, max_frequency: Hz) -> Option<(Hz, f32)> {
+        let fs = f32::from(self.sample_rate);
+        let (min_lag, max_lag) = (fs / max_frequency.0, fs / min_frequency.0);
+        let (lag, r) = highest_peak(&self.values, min_lag, max_lag, OCTAVE_COST)?;
+        Some((Hz(fs / lag), r))
+    }
+}
+
+/// The (parabolically interpolated) position and value of the highest
+/// positive local maximum between the given (fractional) indices, after
+/// subtracting octave_cost per octave of index
+fn highest_peak(values: &[f32], min: f32, max: f32, octave_cost: f32) -> Option<(f32, f32)> {
+    let min = (min.floor() as usize).max(1);
+    let max = (max.ceil() as usize).min(values.len().saturating_sub(2));
+    let score = |i: usize| values[i] - octave_cost * (i as f32).log2();
+    let i = (min..=max)
+        .filter(|i| values[*i] > 0. && values[*i] > values[i - 1] && values[*i] >= values[i + 1])
+        .max_by(|a, b| score(*a).total_cmp(&score(*b)))?;
+    let (a, b, c) = (values[i - 1], values[i], values[i + 1]);
+    let denominator = a - 2. * b + c;
+    if denominator >= 0. {
+        return Some((i as f32, b));
+    }
+    let offset = 0.5 * (a - c) / denominator;
+    Some((i as f32 + offset, b - 0.25 * (a - c) * offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::f32::consts::PI;
+
+    use crate::dsp::window::Window;
+    use crate::stream::buffer::{PeriodBuffer, SampleBuffer};
+    use crate::stream::{ChannelCount, Frame};
+
+    const FS: u32 = 16000;
+    const LEN: usize = 1024;
+
+    fn single_period(signal: Vec<f32>) -> PeriodBuffer {
+        let sample_rate = SampleRate::new(FS);
+        let len = signal.len();
+        let mut periods = PeriodBuffer::new(
+            SampleBuffer::new(ChannelCount::new(1), sample_rate, len),
+            len,
+            len,
+        );
+        periods.push(&Frame {
+            channels: ChannelCount::new(1),
+            sample_rate,
+            samples: signal,
+        });
+        periods
+    }
+
+    /// A sum of harmonics of f0, with amplitudes falling off at 6 dB/octave
+    fn harmonics(f0: f32, count: usize) -> Vec<f32> {
+        (0..LEN)
+            .map(|i| {
+                let t = i as f32 / FS as f32;
+                (1..=count)
+                    .map(|h| 0.5 / h as f32 * (2. * PI * f0 * h as f32 * t).sin())
+                    .sum()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn cepstral_fundamental() {
+        let mut periods = single_period(harmonics(200., 30));
+        let period = periods.next().unwrap();
+        let mut ffts = FFTSequence::new(LEN);
+        let window = Window::Hann.coefficients(LEN);
+        let cepstrum = real_cepstrum(&mut ffts, &period.get_channel(0), &window);
+
+        let f0 = cepstrum.fundamental(Hz(60.), Hz(1000.)).unwrap();
+        assert_relative_eq!(f0.0, 200., max_relative = 0.01);
+        assert_eq!(cepstrum.points().count(), LEN / 2 + 1);
+    }
+
+    #[test]
+    fn liftering() {
+        let mut periods = single_period(harmonics(200., 30));
+        let period = periods.next().unwrap();
+        let mut ffts = FFTSequence::new(LEN);
+        let window = Window::Hann.coefficients(LEN);
+        let cepstrum = real_cepstrum(&mut ffts, &period.get_channel(0), &window);
+
+        // Without liftering, the log spectrum is the original (floored)
+        let spectrum = ffts.folded_windowed(&period.get_channel(0), &window);
+        let log_spectrum = cepstrum.log_spectrum(&mut ffts);
+        for ((r, _), db) in spectrum.values.iter().zip(&log_spectrum) {
+            assert_abs_diff_eq!(20. * r.max(MIN_AMPLITUDE).log10(), *db, epsilon = 0.01);
+        }
+
+        // Keeping only quefrencies below the fundamental period smooths away
+        // the harmonics, leaving the -6 dB/octave slope
+        let mut envelope = cepstrum.clone();
+        envelope.lifter(0.8 / 200., Lifter::Low);
+        let envelope = envelope.log_spectrum(&mut ffts);
+        let bin = |f: f32| (f * LEN as f32 / FS as f32).round() as usize;
+        // Between harmonics, the envelope is well above the spectrum...
+        assert!(envelope[bin(1500.)] - log_spectrum[bin(1500.)] > 20.);
+        // ...and it falls about 6 dB per octave
+        assert_abs_diff_eq!(envelope[bin(800.)] - envelope[bin(1600.)], 6., epsilon = 2.);
+
+        // While the high quefrencies keep the harmonic peaks
+        let mut source = cepstrum.clone();
+        source.lifter(0.8 / 200., Lifter::High);
+        let source = source.log_spectrum(&mut ffts);
+        assert!(source[bin(1600.)] - source[bin(1500.)] > 20.);
+    }
+
+    #[test]
+    fn autocorrelation() {
+        let mut periods = single_period(harmonics(150., 10));
+        let period = periods.next().unwrap();
+        let mut ffts = FFTSequence::new(2 * LEN);
+        let window = Window::Hann.coefficients(LEN);
+        let r = normalized_autocorrelation(&mut ffts, &period.get_channel(0), &window);
+
+        assert_eq!(r.values.len(), LEN / 2 + 1);
+        assert_relative_eq!(r.values[0], 1.);
+        let (f0, periodicity) = r.fundamental(Hz(60.), Hz(1000.)).unwrap();
+        assert_relative_eq!(f0.0, 150., max_relative = 0.005);
+        assert!(periodicity > 0.99);
+
+        // Silence has no fundamental
+        let mut periods = single_period(vec![0.; LEN]);
+        let period = periods.next().unwrap();
+        let r = normalized_autocorrelation(&mut ffts, &period.get_channel(0), &window);
+        assert!(r.fundamental(Hz(60.), Hz(1000.)).is_none());
+    }
+}
@@ -0,0 +1,285 @@
+use std::iter::zip;
+
+use num_complex::Complex;
+
+use super::fft::{complex_planner, FFTSequence};
+use super::window::Window;
+use super::{Hz, Level};
+use crate::stream::buffer::ChannelPeriod;
+use crate::stream::SampleRate;
+
+/// Spectral kernel values smaller than this, relative to the kernel's
+/// largest, are dropped
+const SPARSITY: f32 = 1e-3;
+
+/// The bins of a constant-Q (or variable-Q) transform
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConstantQOptions {
+    /// The center frequency of the lowest bin
+    pub min_frequency: Hz,
+    /// No bin's center is higher than this (or nyquist, less half a bin)
+    pub max_frequency: Hz,
+    pub bins_per_octave: usize,
+    /// Added to each bin's bandwidth (Hz). 0 gives a constant-Q transform;
+    /// more widens the low bins (shortening their kernels, so improving time
+    /// resolution where it's worst), as in a variable-Q transform.
+    pub gamma: f32,
+}
+
+impl Default for ConstantQOptions {
+    fn default() -> ConstantQOptions {
+        ConstantQOptions {
+            // A1
+            min_frequency: Hz(55.),
+            max_frequency: Hz(14080.),
+            bins_per_octave: 24,
+            gamma: 0.,
+        }
+    }
+}
+
+/// The amplitude spectrum of a period at geometrically spaced frequencies
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConstantQSpectrum {
+    /// The center frequency of each bin
+    pub frequencies: Vec<Hz>,
+    /// The amplitude (FS) in each bin
+    pub values: Vec<f32>,
+}
+
+/// Computes constant-Q transforms of many periods of the same length, with
+/// the spectral kernel method (Brown & Puckette, 1992): each bin is the inner
+/// product of the period with a windowed complex sinusoid whose length is
+/// inversely proportional to its frequency, evaluated as a sparse product of
+/// their FFTs.
+///
+/// Bins whose kernels would be longer than the period are truncated to it,
+/// which widens them (i.e. the transform becomes variable-Q at the bottom).
+pub struct ConstantQ {
+    sample_rate: SampleRate,
+    frequencies: Vec<Hz>,
+    /// The length (samples) of each bin's kernel
+    kernel_lens: Vec<usize>,
+    /// The non-negligible (positive frequency) FFT bins of each kernel, as
+    /// (bin, conjugated and scaled value)
+    kernels: Vec<Vec<(usize, Complex<f32>)>>,
+    ffts: FFTSequence,
+}
+
+impl ConstantQ {
+    pub fn new(sample_rate: SampleRate, len: usize, options: &ConstantQOptions) -> ConstantQ {
+        let fs = f32::from(sample_rate);
+        let b = options.bins_per_octave as f32;
+        let ratio = 2f32.powf(1. / b);
+        // The quality factor (center frequency / bandwidth) for bins that are
+        // as wide as the spacing between them
+        let q = 1. / (ratio - 1.);
+        let max_frequency = options.max_frequency.0.min(fs / 2. / ratio.sqrt());
+        let frequencies: Vec<Hz> = (0..)
+            .map(|k| Hz(options.min_frequency.0 * ratio.powi(k)))
+            .take_while(|f| f.0 <= max_frequency)
+            .collect();
+
+        let fft = complex_planner().plan_fft_forward(len);
+        let (kernel_lens, kernels) = frequencies
+            .iter()
+            .map(|f| {
+                let bandwidth = f.0 / q + options.gamma;
+                let kernel_len = ((fs / bandwidth).round() as usize).clamp(2, len);
+                let window = Window::Hann.coefficients(kernel_len);
+                // Scaled so that a sinusoid of amplitude A at f gives A
+                let scale = 2. / window.iter().sum::<f32>();
+                // Centered in the period
+                let offset = (len - kernel_len) / 2;
+                let mut kernel = vec![Complex::default(); len];
+                for (n, w) in window.iter().enumerate() {
+                    let t = (n as f32 - kernel_len as f32 / 2.) / fs;
+                    kernel[offset + n] =
+                        Complex::from_polar(w * scale, 2. * std::f32::consts::PI * f.0 * t);
+                }
+                fft.process(&mut kernel);
+
+                // (The 1/N is from Parseval's theorem)
+                let max = kernel.iter().map(|k| k.norm()).fold(0., f32::max);
+                let sparse = kernel[..=len / 2]
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, k)| k.norm() >= SPARSITY * max)
+                    .map(|(j, k)| (j, k.conj() / len as f32))
+                    .collect();
+                (kernel_len, sparse)
+            })
+            .unzip();
+
+        ConstantQ {
+            sample_rate,
+            frequencies,
+            kernel_lens,
+            kernels,
+            ffts: FFTSequence::new(len),
+        }
+    }
+
+    pub fn sample_rate(&self) -> SampleRate {
+        self.sample_rate
+    }
+
+    /// The center frequency of each bin
+    pub fn frequencies(&self) -> &[Hz] {
+        &self.frequencies
+    }
+
+    /// The length (samples) of each bin's kernel, i.e. its time resolution
+    pub fn kernel_lens(&self) -> &[usize] {
+        &self.kernel_lens
+    }
+
+    /// The length of the periods this transforms
+    pub fn period_len(&self) -> usize {
+        self.ffts.period_len()
+    }
+
+    pub fn transform(&mut self, period: &ChannelPeriod) -> ConstantQSpectrum {
+        assert_eq!(period.len(), self.period_len());
+        let signal: Vec<f32> = period.iter().copied().collect();
+        let spectrum = self.ffts.spectrum_of(&signal);
+        let values = self
+            .kernels
+            .iter()
+            .map(|kernel| {
+                kernel
+                    .iter()
+                    .map(|(j, k)| spectrum[*j] * k)
+                    .sum::<Complex<f32>>()
+                    .norm()
+            })
+            .collect();
+        ConstantQSpectrum {
+            frequencies: self.frequencies.clone(),
+            values,
+        }
+    }
+}
+
+impl ConstantQSpectrum {
+    /// The amplitude of each bin, in dBFS
+    pub fn to_db(&self) -> impl Iterator<Item = f32> + '_ {
+        self.values.iter().map(|r| Level::dbfs(*r).into())
+    }
+
+    /// (frequency, amplitude) pairs
+    pub fn points(&self) -> impl Iterator<Item = (Hz, f32)> + '_ {
+        zip(
+            self.frequencies.iter().copied(),
+            self.values.iter().copied(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::f32::consts::PI;
+
+    use crate::stream::buffer::{PeriodBuffer, SampleBuffer};
+    use crate::stream::{ChannelCount, Frame};
+
+    const FS: u32 = 16000;
+    const LEN: usize = 8192;
+
+    fn single_period(signal: Vec<f32>) -> PeriodBuffer {
+        let sample_rate = SampleRate::new(FS);
+        let len = signal.len();
+        let mut periods = PeriodBuffer::new(
+            SampleBuffer::new(ChannelCount::new(1), sample_rate, len),
+            len,
+            len,
+        );
+        periods.push(&Frame {
+            channels: ChannelCount::new(1),
+            sample_rate,
+            samples: signal,
+        });
+        periods
+    }
+
+    fn tones(tones: &[(f32, f32)]) -> Vec<f32> {
+        (0..LEN)
+            .map(|i| {
+                let t = i as f32 / FS as f32;
+                tones.iter().map(|(f, a)| a * (2. * PI * f * t).sin()).sum()
+            })
+            .collect()
+    }
+
+    fn transform(cq: &mut ConstantQ, signal: Vec<f32>) -> ConstantQSpectrum {
+        let mut periods = single_period(signal);
+        let period = periods.next().unwrap();
+        cq.transform(&period.get_channel(0))
+    }
+
+    fn loudest(spectrum: &ConstantQSpectrum) -> usize {
+        (0..spectrum.values.len())
+            .max_by(|a, b| spectrum.values[*a].total_cmp(&spectrum.values[*b]))
+            .unwrap()
+    }
+
+    #[test]
+    fn bins() {
+        let cq = ConstantQ::new(SampleRate::new(FS), LEN, &ConstantQOptions::default());
+        let f = cq.frequencies();
+        assert_eq!(f[0], Hz(55.));
+        assert_relative_eq!(f[24].0, 110., max_relative = 1e-5);
+        // Up to (half a bin below) nyquist: 24 * log2(8000 / 55) = 172.4
+        assert!(f.last().unwrap().0 < 8000. / 2f32.powf(1. / 48.));
+        assert_eq!(f.len(), 172);
+        // Kernels get shorter with frequency, and the lowest are truncated
+        assert!(cq.kernel_lens().windows(2).all(|w| w[0] >= w[1]));
+        assert_eq!(cq.kernel_lens()[0], LEN);
+    }
+
+    #[test]
+    fn tone_amplitude() {
+        let mut cq = ConstantQ::new(SampleRate::new(FS), LEN, &ConstantQOptions::default());
+        for bin in [40, 100, 150] {
+            let f = cq.frequencies()[bin].0;
+            let spectrum = transform(&mut cq, tones(&[(f, 0.5)]));
+            assert_eq!(loudest(&spectrum), bin);
+            assert_relative_eq!(spectrum.values[bin], 0.5, max_relative = 0.01);
+            // The next bin but one is well down
+            assert!(spectrum.values[bin + 2] < 0.05);
+        }
+    }
+
+    #[test]
+    fn low_resolution() {
+        // A semitone apart at ~116 Hz is ~7 Hz, i.e. less than 4 bins of a
+        // 8192 point FFT, but four bins apart here
+        let mut cq = ConstantQ::new(SampleRate::new(FS), LEN, &ConstantQOptions::default());
+        let (a, b) = (cq.frequencies()[26].0, cq.frequencies()[30].0);
+        let spectrum = transform(&mut cq, tones(&[(a, 0.5), (b, 0.5)]));
+        assert!(spectrum.values[26] > 0.45);
+        assert!(spectrum.values[30] > 0.45);
+        assert!(spectrum.values[28] < 0.25);
+    }
+
+    #[test]
+    fn variable_q() {
+        let constant = ConstantQ::new(SampleRate::new(FS), LEN, &ConstantQOptions::default());
+        let options = ConstantQOptions {
+            gamma: 20.,
+            ..ConstantQOptions::default()
+        };
+        let mut variable = ConstantQ::new(SampleRate::new(FS), LEN, &options);
+        // Low bins are much shorter, and high bins barely change
+        assert!(variable.kernel_lens()[40] * 2 < constant.kernel_lens()[40]);
+        let last = constant.kernel_lens().len() - 1;
+        assert!(variable.kernel_lens()[last] * 10 > constant.kernel_lens()[last] * 9);
+
+        let f = variable.frequencies()[40].0;
+        let spectrum = transform(&mut variable, tones(&[(f, 0.5)]));
+        assert_eq!(loudest(&spectrum), 40);
+        assert_relative_eq!(spectrum.values[40], 0.5, max_relative = 0.01);
+    }
+}
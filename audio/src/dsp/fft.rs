@@ -1,39 +1,230 @@
 use std::f32::consts::PI;
 use std::iter::zip;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
 
 use approx::AbsDiffEq;
 use num_complex::Complex;
-use rustfft::{Fft, FftPlanner};
+use realfft::{ComplexToReal, RealFftPlanner, RealToComplex};
+use rustfft::FftPlanner;
 
+use super::peaks::{find_peaks, Peak, PeakOptions};
 use super::Hz;
 use crate::stream::buffer::ChannelPeriod;
 use crate::stream::input::SampleRate;
 
+/// FFT plans are expensive to compute, so all FFTSequences share a planner
+/// (which caches plans by length).
+fn planner() -> MutexGuard<'static, RealFftPlanner<f32>> {
+    static PLANNER: OnceLock<Mutex<RealFftPlanner<f32>>> = OnceLock::new();
+    PLANNER
+        .get_or_init(|| Mutex::new(RealFftPlanner::new()))
+        .lock()
+        .unwrap()
+}
+
+/// Likewise for complex FFTs, e.g. of ConstantQ's kernels
+pub(super) fn complex_planner() -> MutexGuard<'static, FftPlanner<f32>> {
+    static PLANNER: OnceLock<Mutex<FftPlanner<f32>>> = OnceLock::new();
+    PLANNER
+        .get_or_init(|| Mutex::new(FftPlanner::new()))
+        .lock()
+        .unwrap()
+}
+
+/// Computes FFTs of many real signals of the same length, reusing plans and
+/// buffers as much as possible.
 pub struct FFTSequence {
-    fft: Arc<dyn Fft<f32>>,
+    fft: Arc<dyn RealToComplex<f32>>,
+    ifft: Arc<dyn ComplexToReal<f32>>,
+    /// Time domain samples (input to fft, output of ifft)
+    signal: Vec<f32>,
+    /// Positive frequency half of the spectrum (output of fft, input to ifft)
+    spectrum: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
 }
 
 impl FFTSequence {
     pub fn new(period_len: usize) -> FFTSequence {
+        let mut planner = planner();
+        let fft = planner.plan_fft_forward(period_len);
+        let ifft = planner.plan_fft_inverse(period_len);
+        let scratch_len = fft.get_scratch_len().max(ifft.get_scratch_len());
         FFTSequence {
-            // nb: reusing the planner is recommended if a lot of these are
-            // going to get constructed.
-            fft: FftPlanner::new().plan_fft_forward(period_len),
+            signal: fft.make_input_vec(),
+            spectrum: fft.make_output_vec(),
+            scratch: vec![Complex::default(); scratch_len],
+            fft,
+            ifft,
         }
     }
 
-    pub fn fft(&self, period: &ChannelPeriod) -> CartesianFFT {
-        let mut values: Vec<Complex<f32>> =
-            period.iter().map(|y| Complex { re: *y, im: 0. }).collect();
-        self.fft.process(&mut values);
+    /// The full (i.e. including negative frequencies) FFT of a period, which
+    /// must be of the sequence's length
+    pub fn fft(&mut self, period: &ChannelPeriod) -> CartesianFFT {
+        assert_eq!(period.len(), self.period_len());
+        self.load(period.iter().copied());
+        self.unfold_spectrum(period.sample_rate())
+    }
+
+    /// Compute the FFT of a period, after tapering it with the given window
+    /// (see `dsp::window`)
+    pub fn fft_windowed(&mut self, period: &ChannelPeriod, window: &[f32]) -> CartesianFFT {
+        assert_eq!(period.len(), self.period_len());
+        assert_eq!(period.len(), window.len());
+        self.load(zip(period.iter(), window.iter()).map(|(y, w)| y * w));
+        self.unfold_spectrum(period.sample_rate())
+    }
+
+    /// Equivalent to `fft(period).into_polar().into_folded()`, but skips
+    /// computing the negative frequencies in the first place.
+    pub fn folded(&mut self, period: &ChannelPeriod) -> FoldedFFT {
+        assert_eq!(period.len(), self.period_len());
+        self.load(period.iter().copied());
+        self.fold_spectrum(period.sample_rate())
+    }
+
+    /// Equivalent to `fft_windowed(period, window).into_polar().into_folded()`
+    pub fn folded_windowed(&mut self, period: &ChannelPeriod, window: &[f32]) -> FoldedFFT {
+        assert_eq!(period.len(), self.period_len());
+        assert_eq!(period.len(), window.len());
+        self.load(zip(period.iter(), window.iter()).map(|(y, w)| y * w));
+        self.fold_spectrum(period.sample_rate())
+    }
+
+    /// Invert a FFT (of the same length as this sequence's periods) back into
+    /// the real signal it was computed from
+    pub fn ifft(&mut self, fft: CartesianFFT) -> Vec<f32> {
+        assert_eq!(fft.values.len(), self.signal.len());
+        // The negative frequencies are redundant (they're the conjugates of
+        // the positive frequencies) for real signals:
+        let half_len = self.spectrum.len();
+        self.spectrum.copy_from_slice(&fft.values[..half_len]);
+        self.invert()
+    }
+
+    /// Equivalent to `ifft(fft.into_unfolded().into_cartesian())`, but skips
+    /// reconstructing the negative frequencies.
+    pub fn ifft_folded(&mut self, fft: FoldedFFT) -> Vec<f32> {
+        assert_eq!(fft.unfolded_length, self.signal.len());
+        let mut values = fft.values;
+        denormalize_folded(&mut values, fft.unfolded_length);
+        for (y, (r, p)) in zip(self.spectrum.iter_mut(), values) {
+            *y = Complex::from_polar(r, p);
+        }
+        self.invert()
+    }
+
+    /// The length of the signals this sequence transforms
+    pub fn period_len(&self) -> usize {
+        self.signal.len()
+    }
+
+    /// Load a signal (zero-padded if it's shorter than the sequence's length),
+    /// and return the (unnormalized) positive frequency half of its FFT, for
+    /// modification in place before invert_spectrum
+    pub(crate) fn spectrum_of(&mut self, signal: &[f32]) -> &mut [Complex<f32>] {
+        self.load(signal.iter().copied());
+        &mut self.spectrum
+    }
+
+    /// Invert the spectrum returned by spectrum_of
+    pub(crate) fn invert_spectrum(&mut self) -> Vec<f32> {
+        self.invert()
+    }
+
+    /// Load a signal, zero-padded to the sequence's length (only spectrum_of
+    /// passes shorter signals), and transform it
+    fn load<I: Iterator<Item = f32>>(&mut self, signal: I) {
+        let mut signal = signal.fuse();
+        for y in self.signal.iter_mut() {
+            *y = signal.next().unwrap_or(0.);
+        }
+        self.fft
+            .process_with_scratch(&mut self.signal, &mut self.spectrum, &mut self.scratch)
+            .expect("buffer lengths are correct by construction");
+    }
+
+    fn unfold_spectrum(&self, sample_rate: SampleRate) -> CartesianFFT {
+        let n = self.signal.len();
+        let mut values = Vec::with_capacity(n);
+        values.extend_from_slice(&self.spectrum);
+        for k in self.spectrum.len()..n {
+            values.push(self.spectrum[n - k].conj());
+        }
         CartesianFFT {
             values,
-            sample_rate: period.sample_rate(),
+            sample_rate,
+        }
+    }
+
+    fn fold_spectrum(&self, sample_rate: SampleRate) -> FoldedFFT {
+        let mut values: Vec<(f32, f32)> = self.spectrum.iter().map(|y| y.to_polar()).collect();
+        normalize_folded(&mut values, self.signal.len());
+        FoldedFFT {
+            values,
+            sample_rate,
+            unfolded_length: self.signal.len(),
+        }
+    }
+
+    fn invert(&mut self) -> Vec<f32> {
+        // The imaginary parts of DC and nyquist must be zero for a real
+        // signal, but may not be if the spectrum has been modified. Dropping
+        // them is equivalent to discarding the imaginary part of the output.
+        let n = self.signal.len();
+        let last = self.spectrum.len() - 1;
+        self.spectrum[0].im = 0.;
+        if !has_conjugate(last, n) {
+            self.spectrum[last].im = 0.;
+        }
+        self.ifft
+            .process_with_scratch(&mut self.spectrum, &mut self.signal, &mut self.scratch)
+            .expect("buffer lengths are correct by construction");
+        self.signal.iter().map(|y| y / n as f32).collect()
+    }
+}
+
+/// Apply the 1/N normalization factor of the inverse FFT, and discard the
+/// imaginary parts (which are ~0 if the spectrum was that of a real signal)
+fn normalized_real_part(values: Vec<Complex<f32>>) -> Vec<f32> {
+    let n = values.len() as f32;
+    values.into_iter().map(|y| y.re / n).collect()
+}
+
+/// Apply the 1/N normalization factor from the inverse FFT to the magnitudes
+/// of the positive frequency half of a FFT of length n, making them
+/// interpretable as the physical amplitude of that frequency component of the
+/// signal. Values that have a conjugate are multiplied by 2 to account for the
+/// removal of its magnitude.
+fn normalize_folded(values: &mut [(f32, f32)], n: usize) {
+    for (i, y) in values.iter_mut().enumerate() {
+        if has_conjugate(i, n) {
+            y.0 *= 2. / n as f32;
+        } else {
+            y.0 /= n as f32;
         }
     }
 }
 
+/// The inverse of normalize_folded
+fn denormalize_folded(values: &mut [(f32, f32)], n: usize) {
+    for (i, y) in values.iter_mut().enumerate() {
+        if has_conjugate(i, n) {
+            y.0 *= n as f32 / 2.;
+        } else {
+            y.0 *= n as f32;
+        }
+    }
+}
+
+/// Whether bin i (of the positive half) of a FFT of length n has a negative
+/// frequency conjugate
+pub(crate) fn has_conjugate(i: usize, n: usize) -> bool {
+    // DC never has a conjugate, and if N is even, neither does the highest
+    // positive frequency
+    !(i == 0 || 2 * i == n)
+}
+
 /// The result of a FFT, in cartesian form (re + im * i)
 #[derive(Clone, Debug, PartialEq)]
 pub struct CartesianFFT {
@@ -63,6 +254,39 @@ impl CartesianFFT {
             sample_rate,
         }
     }
+
+    /// Convenient but inefficient; use FFTSequence::ifft to invert many FFTs
+    pub fn into_real_signal(self) -> Vec<f32> {
+        let mut values = self.values;
+        FftPlanner::new()
+            .plan_fft_inverse(values.len())
+            .process(&mut values);
+        normalized_real_part(values)
+    }
+}
+
+/// Unwrap the phases of (r, phase) values in place, by making each differ
+/// from the last by at most PI
+fn unwrap_phases(values: &mut [(f32, f32)]) {
+    let mut prev_wrapped = (0., 0.);
+    let mut prev = (0., 0.);
+    for cur in values {
+        // If the absolute difference betweneen the current and previous
+        // (wrapped) phases is > PI, it could be made smaller by adding
+        // or subtracting 2*PI, which is our heuristic for wrapping.
+        let mut diff = cur.1 - prev_wrapped.1;
+        if diff > PI {
+            diff -= 2. * PI;
+        } else if diff < -PI {
+            diff += 2. * PI;
+        }
+        prev_wrapped = *cur;
+        // Apply the wrap-adjusted difference to the previous unwrapped
+        // phase to get the current unwrapped phase, in order to preserve
+        // the number of rotations that's been accumulated.
+        cur.1 = prev.1 + diff;
+        prev = *cur;
+    }
 }
 
 /// The result of a FFT in polar form (r * e ^ (i * Θ))
@@ -75,24 +299,26 @@ pub struct PolarFFT {
 
 impl PolarFFT {
     pub fn unwrap_phase(&mut self) {
-        let mut prev_wrapped = (0., 0.);
-        let mut prev = (0., 0.);
+        unwrap_phases(&mut self.values);
+    }
+
+    /// Undo unwrap_phase, i.e. bring all phases back into the range -PI..=PI
+    pub fn wrap_phase(&mut self) {
         for cur in &mut self.values {
-            // If the absolute difference betweneen the current and previous
-            // (wrapped) phases is > PI, it could be made smaller by adding
-            // or subtracting 2*PI, which is our heuristic for wrapping.
-            let mut diff = cur.1 - prev_wrapped.1;
-            if diff > PI {
-                diff -= 2. * PI;
-            } else if diff < -PI {
-                diff += 2. * PI;
-            }
-            prev_wrapped = *cur;
-            // Apply the wrap-adjusted difference to the previous unwrapped
-            // phase to get the current unwrapped phase, in order to preserve
-            // the number of rotations that's been accumulated.
-            cur.1 = prev.1 + diff;
-            prev = *cur;
+            cur.1 -= 2. * PI * (cur.1 / (2. * PI)).round();
+        }
+    }
+
+    /// Convert back to cartesian form. Phases don't need to be wrapped first,
+    /// since whole rotations make no difference to the result.
+    pub fn into_cartesian(self) -> CartesianFFT {
+        CartesianFFT {
+            values: self
+                .values
+                .into_iter()
+                .map(|(r, p)| Complex::from_polar(r, p))
+                .collect(),
+            sample_rate: self.sample_rate,
         }
     }
 
@@ -107,23 +333,7 @@ impl PolarFFT {
         // Delete all negative frequency conjugates :3
         res.values.truncate(n / 2 + 1);
 
-        // Apply the 1/N normalization factor from the inverse FFT to
-        // magnitudes, making them interpretable as the physical amplitude of
-        // that frequency component of teh signal. Multiply values that have
-        // a conjugate by 2 to account for the removal of its magnitude.
-        let folded_len = res.values.len(); // for the borrow checker
-        for (i, y) in res.values.iter_mut().enumerate() {
-            if i == 0 {
-                // DC never has a conjugate
-                y.0 /= n as f32;
-            } else if (i == folded_len - 1) && (n % 2 == 0) {
-                // If width is odd, the highest positive frequency has no
-                // conjugate
-                y.0 /= n as f32;
-            } else {
-                y.0 *= 2. / n as f32;
-            }
-        }
+        normalize_folded(&mut res.values, n);
         res
     }
 }
@@ -168,6 +378,72 @@ impl FoldedFFT {
     pub fn nyquist_frequency(&self) -> Hz {
         Hz(f32::from(self.sample_rate) / 2.0)
     }
+
+    pub fn sample_rate(&self) -> SampleRate {
+        self.sample_rate
+    }
+
+    /// The length of the signal this was computed from
+    pub fn unfolded_length(&self) -> usize {
+        self.unfolded_length
+    }
+
+    /// The inverse of PolarFFT::into_folded: undo magnitude normalization and
+    /// restore the negative frequency conjugates.
+    pub fn into_unfolded(self) -> PolarFFT {
+        let n = self.unfolded_length;
+        let folded_len = self.values.len();
+        let mut values = self.values;
+        denormalize_folded(&mut values, n);
+
+        // Bin N - k is the conjugate of bin k (i.e. same magnitude, opposite
+        // phase) because the signal was real:
+        for k in folded_len..n {
+            let (r, p) = values[n - k];
+            values.push((r, -p));
+        }
+
+        PolarFFT {
+            values,
+            sample_rate: self.sample_rate,
+        }
+    }
+
+    /// Convenient but inefficient; use FFTSequence::ifft to invert many FFTs
+    pub fn into_real_signal(self) -> Vec<f32> {
+        self.into_unfolded().into_cartesian().into_real_signal()
+    }
+
+    /// The peaks of the spectrum, loudest first
+    pub fn peaks(&self, options: &PeakOptions) -> Vec<Peak> {
+        find_peaks(self, options)
+    }
+
+    /// The phases, unwrapped as by PolarFFT::unwrap_phase
+    pub fn unwrapped_phases(&self) -> Vec<f32> {
+        let mut values = self.values.clone();
+        unwrap_phases(&mut values);
+        values.into_iter().map(|(_, p)| p).collect()
+    }
+
+    /// The group delay (seconds) at each frequency, i.e. minus the slope of
+    /// the unwrapped phase against angular frequency. A pure delay has the
+    /// same group delay at every frequency.
+    pub fn group_delays(&self) -> Vec<f32> {
+        let phases = self.unwrapped_phases();
+        let bin_width = 2. * PI * f32::from(self.sample_rate) / self.unfolded_length as f32;
+        (0..phases.len())
+            .map(|k| {
+                // Central differences, except at the ends
+                let lo = k.saturating_sub(1);
+                let hi = (k + 1).min(phases.len() - 1);
+                if hi == lo {
+                    return 0.;
+                }
+                -(phases[hi] - phases[lo]) / ((hi - lo) as f32 * bin_width)
+            })
+            .collect()
+    }
 }
 
 impl AbsDiffEq for FoldedFFT {
@@ -191,7 +467,9 @@ impl AbsDiffEq for FoldedFFT {
 mod tests {
     use super::*;
 
+    use crate::stream::buffer::BufferedInput;
     use crate::stream::input::SampleRate;
+    use crate::stream::ChannelCount;
 
     #[test]
     fn polar_unwrap_positive() {
@@ -337,6 +615,125 @@ mod tests {
         );
     }
 
+    /// Something not very regular, to round-trip through FFTs
+    fn test_signal(len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| {
+                let t = i as f32;
+                0.5 * (0.3 * t).sin() + 0.2 * (1.7 * t + 1.).cos() + 0.01 * t - 0.25
+            })
+            .collect()
+    }
+
+    #[test]
+    fn wrap_phase_inverts_unwrap() {
+        let mut polar = CartesianFFT {
+            values: vec![
+                Complex { re: -1., im: 1. },
+                Complex { re: -1., im: -1. }, // wraps
+                Complex { re: 1., im: 0. },
+                Complex { re: -1., im: 1. },
+                Complex { re: -1., im: -1. }, // wraps again
+            ],
+            sample_rate: SampleRate::new(42),
+        }
+        .into_polar();
+        let wrapped = polar.clone();
+        polar.unwrap_phase();
+        assert_ne!(polar, wrapped);
+        polar.wrap_phase();
+        assert_abs_diff_eq!(polar, wrapped, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn unfold_even() {
+        let fft =
+            CartesianFFT::from_real_signal(vec![0., 1., 2., 3.], SampleRate::new(42)).into_polar();
+        assert_abs_diff_eq!(
+            fft.clone().into_folded().into_unfolded(),
+            fft,
+            epsilon = 1e-5
+        );
+    }
+
+    #[test]
+    fn unfold_odd() {
+        let fft = CartesianFFT::from_real_signal(vec![0., 1., 2., 3., 4.], SampleRate::new(42))
+            .into_polar();
+        assert_abs_diff_eq!(
+            fft.clone().into_folded().into_unfolded(),
+            fft,
+            epsilon = 1e-5
+        );
+    }
+
+    #[test]
+    fn resynthesis_round_trip() {
+        for len in [64, 65] {
+            let signal = test_signal(len);
+            let mut polar =
+                CartesianFFT::from_real_signal(signal.clone(), SampleRate::new(42)).into_polar();
+            polar.unwrap_phase();
+            let resynthesized = polar.into_folded().into_real_signal();
+            assert_eq!(resynthesized.len(), len);
+            for (a, b) in zip(signal.iter(), resynthesized.iter()) {
+                assert_abs_diff_eq!(a, b, epsilon = 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn sequence_round_trip() {
+        let signal = test_signal(100);
+        let mut input = BufferedInput::from_sample_input(
+            signal.clone().into_iter(),
+            ChannelCount::new(1),
+            SampleRate::new(100),
+            100,
+        )
+        .unwrap();
+        let period = input.next().unwrap();
+        let mut seq = FFTSequence::new(100);
+        let folded = seq.fft(&period.get_channel(0)).into_polar().into_folded();
+        let resynthesized = seq.ifft(folded.clone().into_unfolded().into_cartesian());
+        for (a, b) in zip(signal.iter(), resynthesized.iter()) {
+            assert_abs_diff_eq!(a, b, epsilon = 1e-4);
+        }
+        let resynthesized = seq.ifft_folded(folded);
+        for (a, b) in zip(signal.iter(), resynthesized.iter()) {
+            assert_abs_diff_eq!(a, b, epsilon = 1e-4);
+        }
+    }
+
+    #[test]
+    fn sequence_matches_complex_fft() {
+        for len in [64, 65] {
+            let signal = test_signal(len);
+            let mut input = BufferedInput::from_sample_input(
+                signal.clone().into_iter(),
+                ChannelCount::new(1),
+                SampleRate::new(100),
+                len,
+            )
+            .unwrap();
+            let period = input.next().unwrap();
+            let expect = CartesianFFT::from_real_signal(signal, SampleRate::new(100));
+
+            let mut seq = FFTSequence::new(len);
+            let full = seq.fft(&period.get_channel(0));
+            assert_eq!(full.values.len(), len);
+            for (a, b) in zip(full.values.iter(), expect.values.iter()) {
+                assert_abs_diff_eq!(a.re, b.re, epsilon = 1e-3);
+                assert_abs_diff_eq!(a.im, b.im, epsilon = 1e-3);
+            }
+            assert_abs_diff_eq!(
+                seq.folded(&period.get_channel(0)),
+                expect.into_polar().into_folded(),
+                epsilon = 1e-4
+            );
+        }
+    }
+
     #[test]
     fn folded_frequencies() {
         let fft = FoldedFFT {
@@ -349,4 +746,21 @@ mod tests {
             vec![Hz(0.), Hz(2.), Hz(4.), Hz(6.), Hz(8.), Hz(10.)]
         );
     }
+
+    #[test]
+    fn folded_group_delay() {
+        // An impulse delayed by 5 samples has linear phase
+        let mut signal = vec![0.; 64];
+        signal[5] = 1.;
+        let fft = CartesianFFT::from_real_signal(signal, SampleRate::new(1000))
+            .into_polar()
+            .into_folded();
+
+        for (k, phase) in fft.unwrapped_phases().into_iter().enumerate() {
+            assert_abs_diff_eq!(phase, -2. * PI * 5. * k as f32 / 64., epsilon = 1e-4);
+        }
+        for delay in fft.group_delays() {
+            assert_abs_diff_eq!(delay, 0.005, epsilon = 1e-6);
+        }
+    }
 }
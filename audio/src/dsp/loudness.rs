@@ -0,0 +1,483 @@
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+
+use super::filter::LTI;
+use super::Decibels;
+use crate::stream::pipeline::Step;
+use crate::stream::{ChannelCount, Frame, SampleRate};
+
+/// Added to the K-weighted power (in dB) to get loudness, so that a 1 kHz
+/// sine wave at -23 dBFS in both stereo channels is -23 LUFS
+const LOUDNESS_OFFSET: f32 = -0.691;
+
+/// Blocks quieter than this (LUFS) are never included in integrated loudness
+/// or loudness range
+const ABSOLUTE_GATE: f32 = -70.;
+
+/// Gating blocks this much quieter than the (absolutely gated) mean aren't
+/// included in integrated loudness
+const INTEGRATED_RELATIVE_GATE: f32 = -10.;
+
+/// Short-term blocks this much quieter than the (absolutely gated) mean
+/// aren't included in loudness range
+const RANGE_RELATIVE_GATE: f32 = -20.;
+
+/// The duration (seconds) of the sub-blocks that momentary and short-term
+/// loudness are computed from, and so how often they're updated
+const SUB_BLOCK_SECS: f32 = 0.1;
+
+/// 400 ms
+const MOMENTARY_SUB_BLOCKS: usize = 4;
+
+/// 3 s
+const SHORT_TERM_SUB_BLOCKS: usize = 30;
+
+/// The width (LU) of the bins of the histograms of block loudness that
+/// integrated loudness and loudness range are read from, which start at the
+/// absolute gate
+const HISTOGRAM_BIN_LU: f32 = 0.1;
+
+/// Blocks louder than this (LUFS) are counted in the top histogram bin
+const HISTOGRAM_MAX: f32 = 10.;
+
+/// The K-weighting pre-filter of ITU-R BS.1770: a high shelf (roughly
+/// modelling the acoustic effect of the head) followed by a high pass (the
+/// revised low-frequency B curve)
+struct KWeighting {
+    shelf: LTI,
+    high_pass: LTI,
+}
+
+impl KWeighting {
+    /// The filters' analog prototypes are specified at 48 kHz; these are
+    /// their bilinear transforms at any sample rate (so at 48 kHz they match
+    /// BS.1770's coefficients)
+    fn new(sample_rate: SampleRate) -> KWeighting {
+        let fs = f64::from(u32::from(sample_rate));
+
+        let (f0, gain_db, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+        let k = (PI * f0 / fs).tan();
+        let vh = 10f64.powf(gain_db / 20.);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1. + k / q + k * k;
+        let shelf = LTI::new(
+            coefficients(&[a0, 2. * (k * k - 1.), 1. - k / q + k * k], a0),
+            coefficients(
+                &[
+                    vh + vb * k / q + k * k,
+                    2. * (k * k - vh),
+                    vh - vb * k / q + k * k,
+                ],
+                a0,
+            ),
+        );
+
+        let (f0, q) = (38.13547087602444, 0.5003270373238773);
+        let k = (PI * f0 / fs).tan();
+        let a0 = 1. + k / q + k * k;
+        let high_pass = LTI::new(
+            coefficients(&[a0, 2. * (k * k - 1.), 1. - k / q + k * k], a0),
+            vec![1., -2., 1.],
+        );
+
+        KWeighting { shelf, high_pass }
+    }
+
+    fn filter(&mut self, x: f32) -> f32 {
+        self.shelf.push_input(x);
+        self.high_pass
+            .push_input(self.shelf.pop_output().expect("LTI outputs each input"));
+        self.high_pass.pop_output().expect("LTI outputs each input")
+    }
+
+    fn reset(&mut self) {
+        self.shelf.reset();
+        self.high_pass.reset();
+    }
+}
+
+fn coefficients(c: &[f64], a0: f64) -> Vec<f32> {
+    c.iter().map(|c| (c / a0) as f32).collect()
+}
+
+/// How much each channel contributes to loudness: surround channels are
+/// weighted up, and the LFE channel is excluded (assuming 5.1 channels are in
+/// the usual L, R, C, LFE, Ls, Rs order)
+fn channel_weights(channels: ChannelCount) -> Vec<f64> {
+    match usize::from(channels) {
+        5 => vec![1., 1., 1., 1.41, 1.41],
+        6 => vec![1., 1., 1., 0., 1.41, 1.41],
+        n => vec![1.; n],
+    }
+}
+
+/// The loudness (LUFS) of a K-weighted, channel-weighted mean square power
+fn loudness(power: f64) -> f32 {
+    LOUDNESS_OFFSET + f32::from(Decibels::from_power_ratio(power as f32))
+}
+
+/// The histogram bin of a loudness (LUFS) above the absolute gate
+fn histogram_bin(loudness: f32) -> usize {
+    let bin = ((loudness - ABSOLUTE_GATE) / HISTOGRAM_BIN_LU).floor();
+    let max = ((HISTOGRAM_MAX - ABSOLUTE_GATE) / HISTOGRAM_BIN_LU).round();
+    bin.clamp(0., max) as usize
+}
+
+/// The gating blocks of one duration since the meter was reset, as a
+/// histogram of the loudness of those that pass the absolute gate, with the
+/// total power of each bin's blocks
+struct BlockHistogram {
+    counts: Vec<usize>,
+    powers: Vec<f64>,
+    /// Of every block, gated or not
+    max: Option<f64>,
+}
+
+impl BlockHistogram {
+    fn new() -> BlockHistogram {
+        let len = histogram_bin(HISTOGRAM_MAX) + 1;
+        BlockHistogram {
+            counts: vec![0; len],
+            powers: vec![0.; len],
+            max: None,
+        }
+    }
+
+    fn push(&mut self, power: f64) {
+        self.max = Some(self.max.map_or(power, |max| max.max(power)));
+        let loudness = loudness(power);
+        if loudness > ABSOLUTE_GATE {
+            let bin = histogram_bin(loudness);
+            self.counts[bin] += 1;
+            self.powers[bin] += power;
+        }
+    }
+
+    /// The bins from the first that passes the relative gate (relative to
+    /// the mean power of the blocks that pass the absolute gate), as (count,
+    /// power) pairs, or None if no blocks pass the absolute gate
+    fn gated(&self, relative_gate: f32) -> Option<(&[usize], &[f64])> {
+        let count: usize = self.counts.iter().sum();
+        if count == 0 {
+            return None;
+        }
+        let mean = self.powers.iter().sum::<f64>() / count as f64;
+        let first = histogram_bin(loudness(mean) + relative_gate);
+        Some((&self.counts[first..], &self.powers[first..]))
+    }
+
+    /// The loudness of the mean power of the gated blocks
+    fn integrated(&self, relative_gate: f32) -> Option<f32> {
+        let (counts, powers) = self.gated(relative_gate)?;
+        let count: usize = counts.iter().sum();
+        Some(loudness(powers.iter().sum::<f64>() / count as f64))
+    }
+
+    /// The spread between the 10th and 95th percentiles of the gated blocks'
+    /// loudness
+    fn range(&self, relative_gate: f32) -> Option<f32> {
+        let (counts, _) = self.gated(relative_gate)?;
+        let offset = self.counts.len() - counts.len();
+        let count: usize = counts.iter().sum();
+        let percentile = |p: f32| {
+            let rank = ((count - 1) as f32 * p).round() as usize;
+            let mut below = 0;
+            let bin = counts
+                .iter()
+                .position(|c| {
+                    below += c;
+                    below > rank
+                })
+                .expect("rank is less than count");
+            ABSOLUTE_GATE + (offset + bin) as f32 * HISTOGRAM_BIN_LU
+        };
+        Some(percentile(0.95) - percentile(0.1))
+    }
+
+    fn clear(&mut self) {
+        self.counts.fill(0);
+        self.powers.fill(0.);
+        self.max = None;
+    }
+}
+
+/// The measurements of a LoudnessMeter, in LUFS (or LU for the range).
+/// Measurements that need more audio than has been metered are None, and
+/// silence is -inf LUFS.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Loudness {
+    /// Over the last 400 ms
+    pub momentary: Option<f32>,
+    /// Over the last 3 s
+    pub short_term: Option<f32>,
+    /// Over everything since the meter was reset, excluding silence and
+    /// relatively quiet passages
+    pub integrated: Option<f32>,
+    /// The spread (between the 10th and 95th percentiles) of the short-term
+    /// loudness since the meter was reset, excluding silence and relatively
+    /// quiet passages
+    pub range: Option<f32>,
+    pub max_momentary: Option<f32>,
+    pub max_short_term: Option<f32>,
+}
+
+/// Measures loudness as specified by ITU-R BS.1770 and EBU R 128 (with
+/// loudness range as specified by EBU Tech 3342).
+///
+/// Rather than keeping every gating block since it was reset, the meter
+/// keeps histograms of their loudness (as Tech 3342 suggests), in 0.1 LU
+/// bins. So integrated loudness is the exact mean power of the gated blocks,
+/// but the gates and the range's percentiles are resolved to a bin.
+pub struct LoudnessMeter {
+    channels: ChannelCount,
+    sample_rate: SampleRate,
+    filters: Vec<KWeighting>,
+    weights: Vec<f64>,
+    sub_block_len: usize,
+    /// The channel-weighted sum of squares so far in the current sub-block
+    sum: f64,
+    /// The number of frames so far in the current sub-block
+    count: usize,
+    /// The mean square powers of the latest sub-blocks, oldest first
+    sub_blocks: VecDeque<f64>,
+    /// Every 400 ms gating block, overlapping by 75%
+    momentary: BlockHistogram,
+    /// Every 3 s block, overlapping by all but 100 ms
+    short_term: BlockHistogram,
+}
+
+impl LoudnessMeter {
+    pub fn new(channels: ChannelCount, sample_rate: SampleRate) -> LoudnessMeter {
+        LoudnessMeter {
+            channels,
+            sample_rate,
+            filters: (0..usize::from(channels))
+                .map(|_| KWeighting::new(sample_rate))
+                .collect(),
+            weights: channel_weights(channels),
+            sub_block_len: (f32::from(sample_rate) * SUB_BLOCK_SECS).round() as usize,
+            sum: 0.,
+            count: 0,
+            sub_blocks: VecDeque::with_capacity(SHORT_TERM_SUB_BLOCKS),
+            momentary: BlockHistogram::new(),
+            short_term: BlockHistogram::new(),
+        }
+    }
+
+    /// Meter a frame of samples. Returns whether momentary and short-term
+    /// loudness were updated, which happens every 100 ms.
+    pub fn push(&mut self, frame: &Frame) -> bool {
+        assert!(frame.channels == self.channels);
+        assert_eq!(frame.sample_rate, self.sample_rate);
+        let mut updated = false;
+        for samples in frame.samples.chunks_exact(usize::from(self.channels)) {
+            for ((x, filter), weight) in samples.iter().zip(&mut self.filters).zip(&self.weights) {
+                let y = f64::from(filter.filter(*x));
+                self.sum += weight * y * y;
+            }
+            self.count += 1;
+            if self.count == self.sub_block_len {
+                self.end_sub_block();
+                updated = true;
+            }
+        }
+        updated
+    }
+
+    fn end_sub_block(&mut self) {
+        if self.sub_blocks.len() == SHORT_TERM_SUB_BLOCKS {
+            self.sub_blocks.pop_front();
+        }
+        self.sub_blocks.push_back(self.sum / self.count as f64);
+        self.sum = 0.;
+        self.count = 0;
+
+        if let Some(power) = self.latest_power(MOMENTARY_SUB_BLOCKS) {
+            self.momentary.push(power);
+        }
+        if let Some(power) = self.latest_power(SHORT_TERM_SUB_BLOCKS) {
+            self.short_term.push(power);
+        }
+    }
+
+    /// The mean power of the latest n sub-blocks
+    fn latest_power(&self, n: usize) -> Option<f64> {
+        if self.sub_blocks.len() < n {
+            return None;
+        }
+        Some(self.sub_blocks.iter().rev().take(n).sum::<f64>() / n as f64)
+    }
+
+    pub fn loudness(&self) -> Loudness {
+        Loudness {
+            momentary: self.latest_power(MOMENTARY_SUB_BLOCKS).map(loudness),
+            short_term: self.latest_power(SHORT_TERM_SUB_BLOCKS).map(loudness),
+            integrated: self.momentary.integrated(INTEGRATED_RELATIVE_GATE),
+            range: self.short_term.range(RANGE_RELATIVE_GATE),
+            max_momentary: self.momentary.max.map(loudness),
+            max_short_term: self.short_term.max.map(loudness),
+        }
+    }
+
+    /// Start metering afresh
+    pub fn reset(&mut self) {
+        for filter in &mut self.filters {
+            filter.reset();
+        }
+        self.sum = 0.;
+        self.count = 0;
+        self.sub_blocks.clear();
+        self.momentary.clear();
+        self.short_term.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::f32::consts::PI;
+
+    const FS: u32 = 48000;
+
+    /// Meter a sequence of (duration (s), peak level (dBFS)) 1 kHz stereo
+    /// sine waves, as in the EBU Tech 3341 and 3342 test signals
+    fn meter(sections: &[(f32, f32)]) -> LoudnessMeter {
+        let channels = ChannelCount::new(2);
+        let sample_rate = SampleRate::new(FS);
+        let mut meter = LoudnessMeter::new(channels, sample_rate);
+        let mut n = 0;
+        for (secs, dbfs) in sections {
+            let amplitude = Decibels::new(*dbfs).amplitude_ratio();
+            let len = (secs * FS as f32).round() as usize;
+            let samples = (n..n + len)
+                .flat_map(|i| {
+                    let x =
+                        amplitude * (2. * PI * 1000. * (i % FS as usize) as f32 / FS as f32).sin();
+                    [x, x]
+                })
+                .collect();
+            meter.push(&Frame {
+                channels,
+                sample_rate,
+                samples,
+            });
+            n += len;
+        }
+        meter
+    }
+
+    /// The first samples of an LTI's impulse response
+    fn impulse_response(lti: &mut LTI) -> Vec<f32> {
+        (0..3)
+            .map(|i| {
+                lti.push_input(if i == 0 { 1. } else { 0. });
+                lti.pop_output().unwrap()
+            })
+            .collect()
+    }
+
+    /// ...and of the filter with the given coefficients
+    fn expected_response(a: [f32; 2], b: [f32; 3]) -> Vec<f32> {
+        let y0 = b[0];
+        let y1 = b[1] - a[0] * y0;
+        let y2 = b[2] - a[0] * y1 - a[1] * y0;
+        vec![y0, y1, y2]
+    }
+
+    #[test]
+    fn k_weighting_coefficients() {
+        // As given in BS.1770 for 48 kHz
+        let mut k = KWeighting::new(SampleRate::new(48000));
+        let shelf = expected_response([-1.6906593, 0.7324808], [1.5351249, -2.6916962, 1.1983928]);
+        for (y, expected) in impulse_response(&mut k.shelf).iter().zip(shelf) {
+            assert_relative_eq!(*y, expected, max_relative = 1e-4);
+        }
+        let high_pass = expected_response([-1.9900475, 0.9900722], [1., -2., 1.]);
+        for (y, expected) in impulse_response(&mut k.high_pass).iter().zip(high_pass) {
+            assert_relative_eq!(*y, expected, max_relative = 1e-4);
+        }
+    }
+
+    #[test]
+    fn tech_3341_stationary() {
+        // Test cases 1 and 2
+        for level in [-23., -33.] {
+            let loudness = meter(&[(20., level)]).loudness();
+            assert_abs_diff_eq!(loudness.momentary.unwrap(), level, epsilon = 0.1);
+            assert_abs_diff_eq!(loudness.short_term.unwrap(), level, epsilon = 0.1);
+            assert_abs_diff_eq!(loudness.integrated.unwrap(), level, epsilon = 0.1);
+            assert_abs_diff_eq!(loudness.range.unwrap(), 0., epsilon = 0.1);
+        }
+    }
+
+    #[test]
+    fn tech_3341_relative_gate() {
+        // Test case 3
+        let loudness = meter(&[(10., -36.), (60., -23.), (10., -36.)]).loudness();
+        assert_abs_diff_eq!(loudness.integrated.unwrap(), -23., epsilon = 0.1);
+    }
+
+    #[test]
+    fn tech_3341_absolute_gate() {
+        // Test case 4
+        let loudness = meter(&[
+            (10., -72.),
+            (10., -36.),
+            (60., -23.),
+            (10., -36.),
+            (10., -72.),
+        ])
+        .loudness();
+        assert_abs_diff_eq!(loudness.integrated.unwrap(), -23., epsilon = 0.1);
+    }
+
+    #[test]
+    fn tech_3341_maxima() {
+        // Test case 5
+        let loudness = meter(&[(20., -26.), (20.1, -20.), (20., -26.)]).loudness();
+        assert_abs_diff_eq!(loudness.integrated.unwrap(), -23., epsilon = 0.1);
+        assert_abs_diff_eq!(loudness.max_momentary.unwrap(), -20., epsilon = 0.1);
+        assert_abs_diff_eq!(loudness.max_short_term.unwrap(), -20., epsilon = 0.1);
+    }
+
+    #[test]
+    fn tech_3342_steps() {
+        // Test cases 1 to 3
+        for (sections, range) in [
+            ([(20., -20.), (20., -30.)], 10.),
+            ([(20., -20.), (20., -15.)], 5.),
+            ([(20., -40.), (20., -20.)], 20.),
+        ] {
+            let loudness = meter(&sections).loudness();
+            assert_abs_diff_eq!(loudness.range.unwrap(), range, epsilon = 1.);
+        }
+    }
+
+    #[test]
+    fn tech_3342_gating() {
+        // Test case 4, in which the -50 dBFS sections are relatively gated
+        let loudness = meter(&[
+            (20., -50.),
+            (20., -35.),
+            (20., -20.),
+            (20., -35.),
+            (20., -50.),
+        ])
+        .loudness();
+        assert_abs_diff_eq!(loudness.range.unwrap(), 15., epsilon = 1.);
+    }
+
+    #[test]
+    fn silence() {
+        let mut meter = meter(&[(1., f32::NEG_INFINITY)]);
+        let loudness = meter.loudness();
+        assert_eq!(loudness.momentary, Some(f32::NEG_INFINITY));
+        assert_eq!(loudness.integrated, None);
+        assert_eq!(loudness.range, None);
+        assert_eq!(loudness.short_term, None);
+
+        meter.reset();
+        assert_eq!(meter.loudness(), Loudness::default());
+    }
+}
@@ -0,0 +1,518 @@
+use std::f64::consts::PI;
+
+use num_complex::Complex;
+
+use super::window::Window;
+use super::Hz;
+use crate::stream::buffer::ChannelPeriod;
+use crate::stream::SampleRate;
+
+/// The number of formants reported by FormantAnalyzer
+pub const MAX_FORMANTS: usize = 4;
+
+/// The sample rate that FormantAnalyzer downsamples to (roughly), which
+/// covers the first four formants of most voices with a low order model.
+const ANALYSIS_RATE: u32 = 10000;
+
+/// Resonances broader than this (Hz) aren't considered to be formants
+const MAX_BANDWIDTH: f32 = 600.;
+
+/// The autocorrelation of a signal, for lags 0..=max_lag
+pub fn autocorrelation(signal: &[f32], max_lag: usize) -> Vec<f32> {
+    (0..=max_lag)
+        .map(|lag| {
+            signal
+                .iter()
+                .zip(signal.iter().skip(lag))
+                .map(|(a, b)| a * b)
+                .sum()
+        })
+        .collect()
+}
+
+/// Apply a first order high-pass filter (y[n] = x[n] - coefficient * x[n-1])
+/// in place. This flattens the spectral tilt of voiced speech, so that the
+/// higher formants are modeled as well as the lower ones.
+pub fn pre_emphasis(signal: &mut [f32], coefficient: f32) {
+    let mut prev = 0.;
+    for y in signal.iter_mut() {
+        let x = *y;
+        *y = x - coefficient * prev;
+        prev = x;
+    }
+}
+
+/// Low-pass filter and then keep every factor'th sample of a signal
+pub fn decimate(signal: &[f32], factor: usize) -> Vec<f32> {
+    if factor <= 1 {
+        return Vec::from(signal);
+    }
+    // A windowed sinc, with its cutoff a little below the new nyquist
+    let half_len = 8 * factor;
+    let cutoff = 0.9 / factor as f32;
+    let window = Window::Hamming.coefficients(2 * half_len + 1);
+    let kernel: Vec<f32> = window
+        .iter()
+        .enumerate()
+        .map(|(i, w)| {
+            let x = std::f32::consts::PI * (i as f32 - half_len as f32);
+            let sinc = if x == 0. {
+                1.
+            } else {
+                (cutoff * x).sin() / (cutoff * x)
+            };
+            w * cutoff * sinc
+        })
+        .collect();
+    (0..signal.len())
+        .step_by(factor)
+        .map(|center| {
+            kernel
+                .iter()
+                .enumerate()
+                .filter_map(|(k, c)| {
+                    let i = (center + k).checked_sub(half_len)?;
+                    signal.get(i).map(|x| c * x)
+                })
+                .sum()
+        })
+        .collect()
+}
+
+/// A resonance of the vocal tract
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Formant {
+    pub frequency: Hz,
+    /// The -3 dB bandwidth of the resonance
+    pub bandwidth: Hz,
+}
+
+/// The spectral envelope of a signal, as modeled by LPC
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpectralEnvelope {
+    pub frequencies: Vec<Hz>,
+    /// Amplitude (FS) at each frequency. Only the shape is meaningful; the
+    /// scale depends on the window and pre-emphasis used.
+    pub values: Vec<f32>,
+    /// The formants of the model, in order of frequency
+    pub formants: Vec<Formant>,
+}
+
+impl SpectralEnvelope {
+    /// The envelope's amplitude at the frequency nearest to the given one
+    pub fn value_at(&self, frequency: Hz) -> Option<f32> {
+        let bin_width = f32::from(*self.frequencies.get(1)?) - f32::from(self.frequencies[0]);
+        let i = ((frequency.0 - self.frequencies[0].0) / bin_width).round();
+        if i < 0. {
+            return None;
+        }
+        self.values.get(i as usize).copied()
+    }
+}
+
+/// A linear prediction model of a signal, i.e. an all-pole filter
+/// 1 / A(z), where A(z) = 1 + a[1] z^-1 + ... + a[p] z^-p
+#[derive(Clone, Debug, PartialEq)]
+pub struct LPC {
+    /// The coefficients of A(z), starting with a[0] = 1
+    pub coefficients: Vec<f32>,
+    /// The power of the prediction error (i.e. of the filter's excitation)
+    pub error: f32,
+}
+
+impl LPC {
+    /// Solve for the model of order r.len() - 1, from the autocorrelation r,
+    /// with the Levinson-Durbin recursion. None if the signal is silent.
+    pub fn from_autocorrelation(r: &[f32]) -> Option<LPC> {
+        let r: Vec<f64> = r.iter().map(|x| *x as f64).collect();
+        if r.is_empty() || r[0] <= 0. {
+            return None;
+        }
+        let mut a = vec![0f64; r.len()];
+        a[0] = 1.;
+        let mut error = r[0];
+        for i in 1..r.len() {
+            let acc: f64 = (0..i).map(|j| a[j] * r[i - j]).sum();
+            let k = -acc / error;
+            let prev = a.clone();
+            for j in 1..i {
+                a[j] = prev[j] + k * prev[i - j];
+            }
+            a[i] = k;
+            error *= 1. - k * k;
+            if error <= 0. {
+                // A perfectly predictable signal; the higher order
+                // coefficients would just be noise.
+                break;
+            }
+        }
+        Some(LPC {
+            coefficients: a.iter().map(|x| *x as f32).collect(),
+            error: error.max(0.) as f32,
+        })
+    }
+
+    /// Fit a model of the given order to a (windowed) signal
+    pub fn from_signal(signal: &[f32], order: usize) -> Option<LPC> {
+        LPC::from_autocorrelation(&autocorrelation(signal, order))
+    }
+
+    pub fn order(&self) -> usize {
+        self.coefficients.len() - 1
+    }
+
+    /// The magnitude of the model's frequency response, sqrt(error) / |A|,
+    /// at the given frequency (for a signal of the given sample rate)
+    pub fn frequency_response(&self, frequency: f32, sample_rate: f32) -> f32 {
+        let w = 2. * PI * frequency as f64 / sample_rate as f64;
+        let a: Complex<f64> = self
+            .coefficients
+            .iter()
+            .enumerate()
+            .map(|(k, c)| Complex::from_polar(*c as f64, -w * k as f64))
+            .sum();
+        ((self.error as f64).sqrt() / a.norm()) as f32
+    }
+
+    /// The poles of the model, i.e. the roots of z^p A(z)
+    pub fn roots(&self) -> Vec<Complex<f64>> {
+        let a: Vec<f64> = self.coefficients.iter().map(|x| *x as f64).collect();
+        polynomial_roots(&a)
+    }
+
+    /// The resonances of the model, in order of frequency, for a signal of
+    /// the given sample rate. Broad resonances (which are more likely to be
+    /// modeling the source's spectral shape than a formant) are excluded.
+    pub fn formants(&self, sample_rate: f32) -> Vec<Formant> {
+        let fs = sample_rate as f64;
+        let mut formants: Vec<Formant> = self
+            .roots()
+            .into_iter()
+            // Each resonance is a complex conjugate pair; take the positive
+            // frequency one
+            .filter(|z| z.im > 0.)
+            .map(|z| Formant {
+                frequency: Hz((z.arg() * fs / (2. * PI)) as f32),
+                bandwidth: Hz((-z.norm().ln() * fs / PI) as f32),
+            })
+            .filter(|f| {
+                f.frequency.0 > 90.
+                    && f.frequency.0 < sample_rate / 2. - 50.
+                    && f.bandwidth.0 < MAX_BANDWIDTH
+            })
+            .collect();
+        formants.sort_by(|a, b| a.frequency.0.total_cmp(&b.frequency.0));
+        formants
+    }
+}
+
+/// Find all (complex) roots of the polynomial
+/// a[0] z^n + a[1] z^(n-1) + ... + a[n], with the Durand-Kerner method
+fn polynomial_roots(a: &[f64]) -> Vec<Complex<f64>> {
+    let n = a.len() - 1;
+    if n == 0 || a[0] == 0. {
+        return Vec::new();
+    }
+    let monic: Vec<f64> = a.iter().map(|x| x / a[0]).collect();
+    let eval = |z: Complex<f64>| {
+        monic
+            .iter()
+            .fold(Complex::new(0., 0.), |acc, c| acc * z + c)
+    };
+
+    // Initial guesses that aren't real, nor roots of unity
+    let seed = Complex::new(0.4, 0.9);
+    let mut roots: Vec<Complex<f64>> = (0..n).map(|i| seed.powu(i as u32)).collect();
+    for _ in 0..500 {
+        let mut change = 0f64;
+        for i in 0..n {
+            let z = roots[i];
+            let denominator = roots
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .fold(Complex::new(1., 0.), |acc, (_, r)| acc * (z - r));
+            let delta = eval(z) / denominator;
+            if delta.is_finite() {
+                roots[i] = z - delta;
+                change = change.max(delta.norm());
+            }
+        }
+        if change < 1e-12 {
+            break;
+        }
+    }
+    roots
+}
+
+/// Estimates formants from periods of speech, by fitting an LPC model to a
+/// downsampled, pre-emphasized and windowed copy of each period.
+pub struct FormantAnalyzer {
+    sample_rate: SampleRate,
+    /// The factor by which periods are downsampled before analysis
+    decimation: usize,
+    order: usize,
+    pre_emphasis: f32,
+}
+
+impl FormantAnalyzer {
+    pub fn new(sample_rate: SampleRate) -> FormantAnalyzer {
+        let decimation = (u32::from(sample_rate) / ANALYSIS_RATE).max(1) as usize;
+        let mut analyzer = FormantAnalyzer {
+            sample_rate,
+            decimation,
+            order: 0,
+            pre_emphasis: 0.97,
+        };
+        // The usual rule of thumb: a pole pair per kHz, plus a couple more
+        // for the glottal source and radiation
+        analyzer.order = 2 + analyzer.analysis_rate() as usize / 1000;
+        analyzer
+    }
+
+    /// The sample rate of the signal that the LPC model is fitted to
+    pub fn analysis_rate(&self) -> f32 {
+        f32::from(self.sample_rate) / self.decimation as f32
+    }
+
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    pub fn set_order(&mut self, order: usize) {
+        self.order = order.max(1);
+    }
+
+    /// Fit an LPC model to a period, at the analysis rate
+    pub fn lpc(&self, period: &ChannelPeriod) -> Option<LPC> {
+        assert_eq!(period.sample_rate(), self.sample_rate);
+        let samples: Vec<f32> = period.iter().copied().collect();
+        let mut signal = decimate(&samples, self.decimation);
+        pre_emphasis(&mut signal, self.pre_emphasis);
+        let window = Window::Hamming.coefficients(signal.len());
+        for (y, w) in signal.iter_mut().zip(window) {
+            *y *= w;
+        }
+        let mut r = autocorrelation(&signal, self.order);
+        // Add a little white noise (at -60 dB), which keeps the recursion
+        // well conditioned when the signal has little energy in some band,
+        // such as between the decimation filter's cutoff and nyquist.
+        r[0] *= 1.000001;
+        LPC::from_autocorrelation(&r)
+    }
+
+    /// The (up to MAX_FORMANTS) lowest formants in a period
+    pub fn analyze(&self, period: &ChannelPeriod) -> Vec<Formant> {
+        match self.lpc(period) {
+            Some(lpc) => self.lowest_formants(&lpc),
+            None => Vec::new(),
+        }
+    }
+
+    /// The spectral envelope of a period, at len frequencies from DC up to
+    /// the nyquist frequency of the analysis rate. The pre-emphasis is
+    /// undone, so that the envelope has the shape of the original spectrum.
+    pub fn envelope(&self, period: &ChannelPeriod, len: usize) -> Option<SpectralEnvelope> {
+        let lpc = self.lpc(period)?;
+        let rate = self.analysis_rate();
+        let frequencies: Vec<Hz> = (0..len)
+            .map(|i| Hz(i as f32 * rate / 2. / (len - 1).max(1) as f32))
+            .collect();
+        let values = frequencies
+            .iter()
+            .map(|f| {
+                let w = 2. * std::f32::consts::PI * f.0 / rate;
+                let emphasis = Complex::new(
+                    1. - self.pre_emphasis * w.cos(),
+                    self.pre_emphasis * w.sin(),
+                );
+                lpc.frequency_response(f.0, rate) / emphasis.norm()
+            })
+            .collect();
+        Some(SpectralEnvelope {
+            frequencies,
+            values,
+            formants: self.lowest_formants(&lpc),
+        })
+    }
+
+    fn lowest_formants(&self, lpc: &LPC) -> Vec<Formant> {
+        let mut formants = lpc.formants(self.analysis_rate());
+        formants.truncate(MAX_FORMANTS);
+        formants
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dsp::filter::LTI;
+    use crate::stream::buffer::{PeriodBuffer, SampleBuffer};
+    use crate::stream::pipeline::Step;
+    use crate::stream::{ChannelCount, Frame};
+
+    /// Synthesize a vowel, as a 100 Hz impulse train through a cascade of
+    /// resonators with the given (frequency, bandwidth) formants, with the
+    /// -6 dB/octave tilt of a glottal source (plus lip radiation).
+    fn vowel(sample_rate: u32, formants: &[(f32, f32)], len: usize) -> Vec<f32> {
+        let fs = sample_rate as f32;
+        let tilt = LTI::new(vec![1., -0.97], vec![1.]);
+        let mut resonators: Vec<LTI> = std::iter::once(tilt)
+            .chain(formants.iter().map(|(f, b)| {
+                let r = (-std::f32::consts::PI * b / fs).exp();
+                let c = 2. * r * (2. * std::f32::consts::PI * f / fs).cos();
+                LTI::new(vec![1., -c, r * r], vec![1. - c + r * r])
+            }))
+            .collect();
+        let period = (fs / 100.) as usize;
+        (0..len)
+            .map(|i| {
+                let mut y = if i % period == 0 { 1. } else { 0. };
+                for r in resonators.iter_mut() {
+                    r.push_input(y);
+                    y = r.pop_output().unwrap();
+                }
+                y
+            })
+            .collect()
+    }
+
+    /// A buffer containing a single period, of the whole of a signal
+    fn single_period(sample_rate: SampleRate, signal: Vec<f32>) -> PeriodBuffer {
+        let len = signal.len();
+        let mut periods = PeriodBuffer::new(
+            SampleBuffer::new(ChannelCount::new(1), sample_rate, len),
+            len,
+            len,
+        );
+        periods.push(&Frame {
+            channels: ChannelCount::new(1),
+            sample_rate,
+            samples: signal,
+        });
+        periods
+    }
+
+    fn analyze(sample_rate: u32, signal: Vec<f32>, order: Option<usize>) -> Vec<Formant> {
+        let sample_rate = SampleRate::new(sample_rate);
+        let mut periods = single_period(sample_rate, signal);
+        let mut analyzer = FormantAnalyzer::new(sample_rate);
+        if let Some(order) = order {
+            analyzer.set_order(order);
+        }
+        let period = periods.next().unwrap();
+        analyzer.analyze(&period.get_channel(0))
+    }
+
+    fn assert_formants(found: &[Formant], expect: &[(f32, f32)]) {
+        assert_eq!(found.len(), expect.len(), "{:?}", found);
+        for (f, (e, _)) in found.iter().zip(expect) {
+            assert_relative_eq!(f.frequency.0, e, max_relative = 0.05);
+        }
+    }
+
+    #[test]
+    fn levinson_durbin() {
+        // An AR(1) process y[n] = 0.5 y[n-1] + x[n] has r[k] = 0.5^k r[0]
+        let lpc = LPC::from_autocorrelation(&[1., 0.5, 0.25, 0.125]).unwrap();
+        assert_eq!(lpc.order(), 3);
+        assert_abs_diff_eq!(
+            &lpc.coefficients[..],
+            &[1., -0.5, 0., 0.][..],
+            epsilon = 1e-6
+        );
+        assert_abs_diff_eq!(lpc.error, 0.75, epsilon = 1e-6);
+
+        assert!(LPC::from_signal(&[0.; 10], 2).is_none());
+    }
+
+    #[test]
+    fn roots() {
+        // (z - 0.5)(z + 0.25) = z^2 - 0.25z - 0.125
+        let lpc = LPC {
+            coefficients: vec![1., -0.25, -0.125],
+            error: 1.,
+        };
+        let mut roots: Vec<f64> = lpc.roots().iter().map(|z| z.re).collect();
+        roots.sort_by(f64::total_cmp);
+        assert_abs_diff_eq!(&roots[..], &[-0.25, 0.5][..], epsilon = 1e-9);
+    }
+
+    #[test]
+    fn frequency_response() {
+        // A single resonator's response peaks at its frequency
+        let r: f32 = 0.95;
+        let lpc = LPC {
+            coefficients: vec![1., -2. * r * (PI as f32 / 4.).cos(), r * r],
+            error: 1.,
+        };
+        let response: Vec<f32> = (0..=100)
+            .map(|f| lpc.frequency_response(f as f32, 200.))
+            .collect();
+        let peak = response
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .unwrap()
+            .0;
+        assert!((24..=26).contains(&peak), "{}", peak);
+        // 1 / |A(1)| at DC
+        let dc = 1. / (1. - 2. * r * (PI as f32 / 4.).cos() + r * r);
+        assert_relative_eq!(response[0], dc, max_relative = 1e-5);
+    }
+
+    #[test]
+    fn pre_emphasis_filter() {
+        let mut signal = [1., 1., 1.];
+        pre_emphasis(&mut signal, 0.9);
+        assert_abs_diff_eq!(&signal[..], &[1., 0.1, 0.1][..], epsilon = 1e-6);
+    }
+
+    #[test]
+    fn vowel_a() {
+        let formants = [(730., 90.), (1090., 110.), (2440., 160.), (3400., 250.)];
+        let found = analyze(10000, vowel(10000, &formants, 400), None);
+        assert_formants(&found, &formants);
+        for (f, (_, b)) in found.iter().zip(formants) {
+            assert!(f.bandwidth.0 < 2. * b, "{:?}", f);
+        }
+    }
+
+    #[test]
+    fn envelope_peaks() {
+        let formants = [(500., 80.), (1500., 100.), (2500., 150.)];
+        let sample_rate = SampleRate::new(10000);
+        let mut periods = single_period(sample_rate, vowel(10000, &formants, 400));
+        let period = periods.next().unwrap();
+        let analyzer = FormantAnalyzer::new(sample_rate);
+        let envelope = analyzer.envelope(&period.get_channel(0), 501).unwrap();
+        assert_eq!(envelope.frequencies.len(), 501);
+        assert_eq!(envelope.frequencies[500], Hz(5000.));
+        assert_eq!(envelope.formants.len(), 3);
+        // The envelope has local maxima at the formants:
+        for f in &envelope.formants {
+            let peak = envelope.value_at(f.frequency).unwrap();
+            let below = envelope.value_at(Hz(f.frequency.0 - 200.)).unwrap();
+            let above = envelope.value_at(Hz(f.frequency.0 + 200.)).unwrap();
+            assert!(peak > below && peak > above, "{:?}", f);
+        }
+    }
+
+    #[test]
+    fn vowel_i() {
+        let formants = [(270., 60.), (2290., 100.), (3010., 150.), (3500., 200.)];
+        assert_formants(
+            &analyze(10000, vowel(10000, &formants, 400), None),
+            &formants,
+        );
+    }
+
+    #[test]
+    fn vowel_u_downsampled() {
+        // At 44.1 kHz, analyzed at a quarter of that
+        let formants = [(300., 60.), (870., 90.), (2240., 150.), (3300., 200.)];
+        let found = analyze(44100, vowel(44100, &formants, 2048), None);
+        assert_formants(&found, &formants);
+    }
+}
@@ -0,0 +1,553 @@
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::fmt;
+
+use super::fft::{FFTSequence, FoldedFFT};
+use super::window::Window;
+use super::{Decibels, Hz};
+use crate::stream::buffer::{BufferedInput, ChannelPeriod};
+use crate::stream::input::{Input, InputError};
+use crate::stream::{Frame, Instant, SampleRate};
+
+/// Mel energies are floored at this (i.e. -100 dB) before taking logs
+const MIN_ENERGY: f32 = 1e-10;
+
+/// The mel scale variant, which also determines how filters are normalized
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MelScale {
+    /// 2595 log10(1 + f / 700), with filters that peak at 1 (as in HTK)
+    HTK,
+    /// Linear below 1 kHz and logarithmic above, with filters normalized to
+    /// equal area (as in Slaney's Auditory Toolbox, and librosa's default)
+    Slaney,
+}
+
+impl MelScale {
+    pub const ALL: [MelScale; 2] = [MelScale::HTK, MelScale::Slaney];
+
+    pub fn to_mel(&self, frequency: Hz) -> f32 {
+        let f = frequency.0;
+        match self {
+            MelScale::HTK => 2595. * (1. + f / 700.).log10(),
+            MelScale::Slaney if f < 1000. => f * 3. / 200.,
+            MelScale::Slaney => 15. + 27. * (f / 1000.).ln() / 6.4f32.ln(),
+        }
+    }
+
+    pub fn to_hz(&self, mel: f32) -> Hz {
+        Hz(match self {
+            MelScale::HTK => 700. * (10f32.powf(mel / 2595.) - 1.),
+            MelScale::Slaney if mel < 15. => mel * 200. / 3.,
+            MelScale::Slaney => 1000. * (6.4f32.ln() * (mel - 15.) / 27.).exp(),
+        })
+    }
+}
+
+impl fmt::Display for MelScale {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            MelScale::HTK => "HTK",
+            MelScale::Slaney => "Slaney",
+        })
+    }
+}
+
+/// How mel features are computed
+#[derive(Clone, Debug, PartialEq)]
+pub struct MelOptions {
+    pub scale: MelScale,
+    /// The number of (triangular) mel filters
+    pub bands: usize,
+    /// The lower edge of the lowest filter
+    pub min_frequency: Hz,
+    /// The upper edge of the highest filter, or None for nyquist
+    pub max_frequency: Option<Hz>,
+    /// The number of MFCCs (including the 0th)
+    pub coefficients: usize,
+    /// The number of frames either side that deltas are regressed over
+    pub delta_width: usize,
+}
+
+impl Default for MelOptions {
+    fn default() -> MelOptions {
+        MelOptions {
+            scale: MelScale::Slaney,
+            bands: 40,
+            min_frequency: Hz(0.),
+            max_frequency: None,
+            coefficients: 13,
+            delta_width: 2,
+        }
+    }
+}
+
+/// A bank of triangular filters, evenly spaced on a mel scale, over the bins
+/// of a FFT
+#[derive(Clone, Debug, PartialEq)]
+pub struct MelFilterbank {
+    /// For each filter, its first bin, and its weights from there
+    filters: Vec<(usize, Vec<f32>)>,
+    center_frequencies: Vec<Hz>,
+}
+
+impl MelFilterbank {
+    /// Filters for FFTs of fft_len samples
+    pub fn new(options: &MelOptions, fft_len: usize, sample_rate: SampleRate) -> MelFilterbank {
+        let fs = f32::from(sample_rate);
+        let max_frequency = options.max_frequency.unwrap_or(Hz(fs / 2.));
+        let (min_mel, max_mel) = (
+            options.scale.to_mel(options.min_frequency),
+            options.scale.to_mel(max_frequency),
+        );
+        // Each filter rises from the previous filter's center to its own, and
+        // falls to the next's
+        let edges: Vec<f32> = (0..options.bands + 2)
+            .map(|i| {
+                let mel = min_mel + (max_mel - min_mel) * i as f32 / (options.bands + 1) as f32;
+                options.scale.to_hz(mel).0
+            })
+            .collect();
+        let bin_frequency = |bin: usize| bin as f32 * fs / fft_len as f32;
+
+        let filters = edges
+            .windows(3)
+            .map(|e| {
+                let (lower, center, upper) = (e[0], e[1], e[2]);
+                let norm = match options.scale {
+                    MelScale::HTK => 1.,
+                    MelScale::Slaney => 2. / (upper - lower),
+                };
+                let first = (lower * fft_len as f32 / fs).ceil() as usize;
+                let weights = (first..=fft_len / 2)
+                    .map(bin_frequency)
+                    .take_while(|f| *f < upper)
+                    .map(|f| {
+                        let rising = (f - lower) / (center - lower);
+                        let falling = (upper - f) / (upper - center);
+                        norm * rising.min(falling).max(0.)
+                    })
+                    .collect();
+                (first, weights)
+            })
+            .collect();
+        MelFilterbank {
+            filters,
+            center_frequencies: edges[1..=options.bands].iter().map(|f| Hz(*f)).collect(),
+        }
+    }
+
+    pub fn bands(&self) -> usize {
+        self.filters.len()
+    }
+
+    pub fn center_frequencies(&self) -> &[Hz] {
+        &self.center_frequencies
+    }
+
+    /// The weights of a filter, by FFT bin
+    pub fn weights(&self, band: usize) -> impl Iterator<Item = (usize, f32)> + '_ {
+        let (first, weights) = &self.filters[band];
+        weights
+            .iter()
+            .enumerate()
+            .map(move |(i, w)| (first + i, *w))
+    }
+
+    /// The power (FS^2) passed by each filter
+    pub fn energies(&self, fft: &FoldedFFT) -> Vec<f32> {
+        (0..self.bands())
+            .map(|band| {
+                self.weights(band)
+                    .filter_map(|(bin, w)| Some(w * fft.values.get(bin)?.0.powi(2)))
+                    .sum()
+            })
+            .collect()
+    }
+
+    /// The energies, in dB
+    pub fn log_energies(&self, fft: &FoldedFFT) -> Vec<f32> {
+        self.energies(fft)
+            .into_iter()
+            .map(|e| Decibels::from_power_ratio(e.max(MIN_ENERGY)).into())
+            .collect()
+    }
+}
+
+/// The mel features of one analysis period
+#[derive(Clone, Debug, PartialEq)]
+pub struct MelFrame {
+    /// The end time of the analysis period
+    pub end_time: Instant,
+    /// Mel energies (dB)
+    pub log_mel: Vec<f32>,
+    pub mfcc: Vec<f32>,
+    /// Of the MFCCs (empty until computed, see deltas and MelStream)
+    pub deltas: Vec<f32>,
+    /// Of the deltas
+    pub delta_deltas: Vec<f32>,
+}
+
+/// Computes log-mel energies and MFCCs (the orthonormal DCT-II of the log-mel
+/// energies) of periods of a fixed length
+pub struct MelAnalyzer {
+    options: MelOptions,
+    filterbank: MelFilterbank,
+    /// The DCT basis: coefficients x bands
+    dct: Vec<Vec<f32>>,
+    ffts: FFTSequence,
+    window: Vec<f32>,
+}
+
+impl MelAnalyzer {
+    pub fn new(sample_rate: SampleRate, period_len: usize, options: MelOptions) -> MelAnalyzer {
+        let bands = options.bands;
+        let dct = (0..options.coefficients)
+            .map(|k| {
+                let scale = if k == 0 { 1. } else { 2. } / bands as f32;
+                (0..bands)
+                    .map(|n| {
+                        scale.sqrt()
+                            * (PI * k as f32 * (2 * n + 1) as f32 / (2 * bands) as f32).cos()
+                    })
+                    .collect()
+            })
+            .collect();
+        MelAnalyzer {
+            filterbank: MelFilterbank::new(&options, period_len, sample_rate),
+            options,
+            dct,
+            ffts: FFTSequence::new(period_len),
+            window: Window::Hann.coefficients(period_len),
+        }
+    }
+
+    pub fn options(&self) -> &MelOptions {
+        &self.options
+    }
+
+    pub fn filterbank(&self) -> &MelFilterbank {
+        &self.filterbank
+    }
+
+    /// The features of a FFT (of the analyzer's period length), without
+    /// deltas
+    pub fn features(&self, end_time: Instant, fft: &FoldedFFT) -> MelFrame {
+        let log_mel = self.filterbank.log_energies(fft);
+        let mfcc = self
+            .dct
+            .iter()
+            .map(|basis| basis.iter().zip(&log_mel).map(|(b, y)| b * y).sum())
+            .collect();
+        MelFrame {
+            end_time,
+            log_mel,
+            mfcc,
+            deltas: Vec::new(),
+            delta_deltas: Vec::new(),
+        }
+    }
+
+    /// The features of a (Hann windowed) period, without deltas
+    pub fn analyze(&mut self, end_time: Instant, period: &ChannelPeriod) -> MelFrame {
+        let fft = self.ffts.folded_windowed(period, &self.window);
+        self.features(end_time, &fft)
+    }
+
+    /// The features of every period of an input (the first channel), with
+    /// deltas, until it ends. The input's periods should be the analyzer's
+    /// length (and will usually overlap, see BufferedInput::overlapping).
+    pub fn analyze_input<T: Input<Item = Frame>>(
+        &mut self,
+        input: &mut BufferedInput<T>,
+    ) -> Result<Vec<MelFrame>, InputError> {
+        let mut frames = Vec::new();
+        loop {
+            match input.next() {
+                Ok(period) => {
+                    let end_time = period.end_time();
+                    frames.push(self.analyze(end_time, &period.get_channel(0)));
+                }
+                Err(InputError::StreamEnded) => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        let mfccs: Vec<Vec<f32>> = frames.iter().map(|f| f.mfcc.clone()).collect();
+        let deltas = deltas(&mfccs, self.options.delta_width);
+        let delta_deltas = self::deltas(&deltas, self.options.delta_width);
+        for ((frame, d), dd) in frames.iter_mut().zip(deltas).zip(delta_deltas) {
+            frame.deltas = d;
+            frame.delta_deltas = dd;
+        }
+        Ok(frames)
+    }
+}
+
+/// The regression deltas of a sequence of feature vectors, over `width`
+/// frames either side (repeating the first and last frames at the edges):
+/// d[t] = sum(n * (c[t + n] - c[t - n]) for n in 1..=width) / (2 * sum(n^2))
+pub fn deltas(frames: &[Vec<f32>], width: usize) -> Vec<Vec<f32>> {
+    (0..frames.len())
+        .map(|t| {
+            delta_at(t, width, |i| {
+                &frames[i.clamp(0, frames.len() as isize - 1) as usize]
+            })
+        })
+        .collect()
+}
+
+/// The delta of frame t, given a function that gets frames by (possibly out
+/// of range) index
+fn delta_at<'a, F: Fn(isize) -> &'a Vec<f32>>(t: usize, width: usize, frame: F) -> Vec<f32> {
+    let denominator = 2. * (1..=width).map(|n| (n * n) as f32).sum::<f32>();
+    let mut delta = vec![0.; frame(t as isize).len()];
+    for n in 1..=width as isize {
+        let (next, previous) = (frame(t as isize + n), frame(t as isize - n));
+        for (d, (a, b)) in delta.iter_mut().zip(next.iter().zip(previous)) {
+            *d += n as f32 * (a - b) / denominator;
+        }
+    }
+    delta
+}
+
+/// Computes the deltas of a stream of feature vectors. Since they depend on
+/// following frames, they're `width` frames behind.
+pub struct DeltaStream {
+    width: usize,
+    /// Recent frames, up to 2 * width + 1
+    frames: VecDeque<Vec<f32>>,
+    /// The number of frames pushed so far
+    count: usize,
+}
+
+impl DeltaStream {
+    pub fn new(width: usize) -> DeltaStream {
+        DeltaStream {
+            width,
+            frames: VecDeque::new(),
+            count: 0,
+        }
+    }
+
+    /// Push the next frame, and get the deltas of the frame `width` frames
+    /// before it (the first frame being repeated before the start)
+    pub fn push(&mut self, frame: Vec<f32>) -> Option<Vec<f32>> {
+        self.frames.push_back(frame);
+        self.count += 1;
+        if self.frames.len() > 2 * self.width + 1 {
+            self.frames.pop_front();
+        }
+        if self.count <= self.width {
+            return None;
+        }
+        // The index of the frame whose deltas are due, in self.frames
+        let t = self.frames.len() - 1 - self.width;
+        let last = self.frames.len() as isize - 1;
+        Some(delta_at(t, self.width, |i| {
+            &self.frames[i.clamp(0, last) as usize]
+        }))
+    }
+}
+
+/// Adds deltas and delta-deltas to a stream of MelFrames, which are
+/// therefore delayed by twice the delta width
+pub struct MelStream {
+    deltas: DeltaStream,
+    delta_deltas: DeltaStream,
+    /// Frames waiting for their deltas
+    pending: VecDeque<MelFrame>,
+}
+
+impl MelStream {
+    pub fn new(delta_width: usize) -> MelStream {
+        MelStream {
+            deltas: DeltaStream::new(delta_width),
+            delta_deltas: DeltaStream::new(delta_width),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Push the next frame, and get the oldest frame that now has deltas
+    pub fn push(&mut self, frame: MelFrame) -> Option<MelFrame> {
+        let mfcc = frame.mfcc.clone();
+        self.pending.push_back(frame);
+        let deltas = self.deltas.push(mfcc)?;
+        // Deltas arrive in order, so these are for the first frame without
+        let waiting = self.pending.iter_mut().find(|f| f.deltas.is_empty())?;
+        waiting.deltas = deltas.clone();
+        let delta_deltas = self.delta_deltas.push(deltas)?;
+        let mut next = self.pending.pop_front()?;
+        next.delta_deltas = delta_deltas;
+        Some(next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::stream::buffer::FrameAccumulator;
+    use crate::stream::input::InputAdapter;
+    use crate::stream::ChannelCount;
+
+    const FS: u32 = 16000;
+    const LEN: usize = 512;
+
+    #[test]
+    fn mel_scales() {
+        assert_relative_eq!(MelScale::HTK.to_mel(Hz(1000.)), 1000., max_relative = 1e-3);
+        assert_relative_eq!(MelScale::Slaney.to_mel(Hz(1000.)), 15.);
+        assert_relative_eq!(MelScale::Slaney.to_mel(Hz(6400.)), 42., max_relative = 1e-5);
+        for scale in MelScale::ALL {
+            for f in [50., 700., 999., 1001., 4000., 8000.] {
+                assert_relative_eq!(scale.to_hz(scale.to_mel(Hz(f))).0, f, max_relative = 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn filterbanks() {
+        let options = MelOptions {
+            scale: MelScale::HTK,
+            bands: 20,
+            ..MelOptions::default()
+        };
+        let htk = MelFilterbank::new(&options, LEN, SampleRate::new(FS));
+        assert_eq!(htk.bands(), 20);
+        // Overlapping triangles sum to 1 between the first and last centers
+        let (first, last) = (
+            htk.center_frequencies()[0].0,
+            htk.center_frequencies()[19].0,
+        );
+        let mut sums = vec![0.; LEN / 2 + 1];
+        for band in 0..htk.bands() {
+            for (bin, w) in htk.weights(band) {
+                assert!((0. ..=1.).contains(&w));
+                sums[bin] += w;
+            }
+        }
+        let bin_width = FS as f32 / LEN as f32;
+        for (bin, sum) in sums.iter().enumerate() {
+            let f = bin as f32 * bin_width;
+            if f >= first && f <= last {
+                assert_relative_eq!(*sum, 1., max_relative = 1e-4);
+            }
+        }
+
+        // Slaney filters have (roughly, given the bins) unit area
+        let slaney = MelFilterbank::new(
+            &MelOptions {
+                scale: MelScale::Slaney,
+                ..options
+            },
+            LEN,
+            SampleRate::new(FS),
+        );
+        for band in 5..slaney.bands() {
+            let area: f32 = slaney.weights(band).map(|(_, w)| w * bin_width).sum();
+            assert_relative_eq!(area, 1., max_relative = 0.05);
+        }
+    }
+
+    fn sinusoid(f: f32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| 0.5 * (2. * PI * f * i as f32 / FS as f32).sin())
+            .collect()
+    }
+
+    fn analyze(signal: Vec<f32>, options: MelOptions) -> Vec<MelFrame> {
+        let sample_rate = SampleRate::new(FS);
+        let frames = FrameAccumulator::new(
+            ChannelCount::new(1),
+            sample_rate,
+            FrameAccumulator::DEFAULT_FRAME_LEN,
+        );
+        let mut input =
+            BufferedInput::overlapping(InputAdapter::new(signal.into_iter(), frames), LEN, LEN / 2)
+                .unwrap();
+        let mut analyzer = MelAnalyzer::new(sample_rate, LEN, options);
+        analyzer.analyze_input(&mut input).unwrap()
+    }
+
+    #[test]
+    fn log_mel() {
+        let frames = analyze(sinusoid(1000., 8 * LEN), MelOptions::default());
+        assert_eq!(frames.len(), 15);
+        let filterbank = MelFilterbank::new(&MelOptions::default(), LEN, SampleRate::new(FS));
+        let nearest = filterbank
+            .center_frequencies()
+            .iter()
+            .enumerate()
+            .min_by(|a, b| (a.1 .0 - 1000.).abs().total_cmp(&(b.1 .0 - 1000.).abs()))
+            .unwrap()
+            .0;
+        for frame in &frames {
+            let loudest = frame
+                .log_mel
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.total_cmp(b.1))
+                .unwrap()
+                .0;
+            assert!(loudest.abs_diff(nearest) <= 1);
+            assert_eq!(frame.mfcc.len(), 13);
+            // A steady signal has steady features
+            assert!(frame.deltas.iter().all(|d| d.abs() < 1e-3));
+        }
+    }
+
+    #[test]
+    fn dct() {
+        // The MFCCs of a flat log-mel spectrum are just the 0th
+        let analyzer = MelAnalyzer::new(SampleRate::new(FS), LEN, MelOptions::default());
+        let flat = vec![-20.; 40];
+        let mfcc: Vec<f32> = analyzer
+            .dct
+            .iter()
+            .map(|basis| basis.iter().zip(&flat).map(|(b, y)| b * y).sum())
+            .collect();
+        assert_relative_eq!(mfcc[0], -20. * 40f32.sqrt(), max_relative = 1e-5);
+        assert!(mfcc[1..].iter().all(|c| c.abs() < 1e-3));
+    }
+
+    #[test]
+    fn delta_regression() {
+        // The deltas of a ramp are its slope (except near the edges)
+        let frames: Vec<Vec<f32>> = (0..10).map(|t| vec![t as f32, -2. * t as f32]).collect();
+        let d = deltas(&frames, 2);
+        for delta in &d[2..8] {
+            assert_relative_eq!(delta[0], 1.);
+            assert_relative_eq!(delta[1], -2.);
+        }
+        assert_relative_eq!(d[0][0], 0.5);
+
+        // And streaming gives the same results, width frames later
+        let mut stream = DeltaStream::new(2);
+        let streamed: Vec<Vec<f32>> = frames
+            .iter()
+            .filter_map(|f| stream.push(f.clone()))
+            .collect();
+        assert_eq!(streamed.len(), 8);
+        assert_eq!(streamed[..], d[..8]);
+    }
+
+    #[test]
+    fn streaming_frames() {
+        let mut stream = MelStream::new(1);
+        let frame = |t: f32| MelFrame {
+            end_time: Instant::ZERO,
+            log_mel: Vec::new(),
+            mfcc: vec![t * t],
+            deltas: Vec::new(),
+            delta_deltas: Vec::new(),
+        };
+        let out: Vec<MelFrame> = (0..6)
+            .filter_map(|t| stream.push(frame(t as f32)))
+            .collect();
+        assert_eq!(out.len(), 4);
+        // The second derivative of t^2 is 2
+        assert_eq!(out[1].mfcc, vec![1.]);
+        assert_eq!(out[1].deltas, vec![2.]);
+        assert_eq!(out[2].delta_deltas, vec![2.]);
+    }
+}
@@ -1,10 +1,30 @@
 use std::fmt;
 use std::fmt::{Display, Formatter};
+use std::ops::{Add, Sub};
 
 use crate::stream::buffer::ChannelPeriod;
 
+pub mod averaging;
+pub mod cepstrum;
+pub mod constant_q;
 pub mod fft;
 pub mod filter;
+pub mod loudness;
+pub mod lpc;
+pub mod mel;
+pub mod oscilloscope;
+pub mod peak_meter;
+pub mod peaks;
+pub mod pitch;
+pub mod psd;
+pub mod reference_trace;
+pub mod sound_level;
+pub mod spectrogram;
+pub mod stft;
+pub mod tuning;
+pub mod voice;
+pub mod vowels;
+pub mod window;
 
 pub fn rms(period: &ChannelPeriod) -> f32 {
     let sum_sq = period.iter().fold(0.0, |acc, x| acc + x * x);
@@ -12,6 +32,11 @@ pub fn rms(period: &ChannelPeriod) -> f32 {
     mean_sq.sqrt()
 }
 
+/// A ratio in decibels, e.g. a gain or the difference between two levels.
+///
+/// Amplitudes (and other root-power quantities, like voltages and pressures)
+/// and powers convert to decibels differently (20 log10 vs 10 log10), so
+/// conversions say which they are. A ratio of 0 (e.g. silence) is -inf dB.
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
 pub struct Decibels(f32);
 
@@ -20,13 +45,29 @@ impl Decibels {
         Decibels(db)
     }
 
-    pub fn from_full_scale(fs: f32) -> Decibels {
-        Decibels(10. * fs.log10())
+    /// The decibels of a ratio of amplitudes
+    pub fn from_amplitude_ratio(ratio: f32) -> Decibels {
+        Decibels(20. * ratio.abs().log10())
     }
 
-    pub fn into_full_scale(self) -> f32 {
+    /// The decibels of a ratio of powers
+    pub fn from_power_ratio(ratio: f32) -> Decibels {
+        Decibels(10. * ratio.abs().log10())
+    }
+
+    pub fn amplitude_ratio(self) -> f32 {
+        10f32.powf(self.0 / 20.)
+    }
+
+    pub fn power_ratio(self) -> f32 {
         10f32.powf(self.0 / 10.)
     }
+
+    /// Clamp to at least the given number of dB, e.g. so that silence can
+    /// be plotted
+    pub fn max(self, db: f32) -> Decibels {
+        Decibels(self.0.max(db))
+    }
 }
 
 impl Display for Decibels {
@@ -42,6 +83,172 @@ impl From<Decibels> for f32 {
     }
 }
 
+impl Add for Decibels {
+    type Output = Decibels;
+
+    fn add(self, rhs: Decibels) -> Decibels {
+        Decibels(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Decibels {
+    type Output = Decibels;
+
+    fn sub(self, rhs: Decibels) -> Decibels {
+        Decibels(self.0 - rhs.0)
+    }
+}
+
+/// What 0 dB is for a Level
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reference {
+    /// An amplitude of 1 sample unit, i.e. dBFS (so the RMS of a full scale
+    /// sine wave is -3 dBFS)
+    FullScale,
+    /// 0.7746 V, i.e. 1 mW into 600 ohms
+    DBu,
+    /// 1 V
+    DBV,
+    /// 20 µPa, roughly the threshold of hearing
+    SPL,
+}
+
+impl Reference {
+    pub const ALL: [Reference; 4] = [
+        Reference::FullScale,
+        Reference::DBu,
+        Reference::DBV,
+        Reference::SPL,
+    ];
+
+    /// The amplitude of 0 dB, in sample units, volts or pascals
+    pub fn amplitude(self) -> f32 {
+        match self {
+            Reference::FullScale => 1.,
+            Reference::DBu => 0.7746,
+            Reference::DBV => 1.,
+            Reference::SPL => 20e-6,
+        }
+    }
+}
+
+impl Display for Reference {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        f.write_str(match self {
+            Reference::FullScale => "dBFS",
+            Reference::DBu => "dBu",
+            Reference::DBV => "dBV",
+            Reference::SPL => "dB SPL",
+        })
+    }
+}
+
+/// An absolute level in decibels, relative to a reference amplitude
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Level {
+    decibels: Decibels,
+    reference: Reference,
+}
+
+impl Level {
+    pub fn new(db: f32, reference: Reference) -> Level {
+        Level {
+            decibels: Decibels(db),
+            reference,
+        }
+    }
+
+    /// The level of an amplitude, in the reference's units
+    pub fn from_amplitude(amplitude: f32, reference: Reference) -> Level {
+        Level {
+            decibels: Decibels::from_amplitude_ratio(amplitude / reference.amplitude()),
+            reference,
+        }
+    }
+
+    /// The level of a power (i.e. a mean square), in the square of the
+    /// reference's units
+    pub fn from_power(power: f32, reference: Reference) -> Level {
+        Level {
+            decibels: Decibels::from_power_ratio(power / reference.amplitude().powi(2)),
+            reference,
+        }
+    }
+
+    /// The level of an amplitude (e.g. RMS or peak) in dBFS
+    pub fn dbfs(amplitude: f32) -> Level {
+        Level::from_amplitude(amplitude, Reference::FullScale)
+    }
+
+    pub fn reference(self) -> Reference {
+        self.reference
+    }
+
+    pub fn amplitude(self) -> f32 {
+        self.decibels.amplitude_ratio() * self.reference.amplitude()
+    }
+
+    pub fn power(self) -> f32 {
+        self.decibels.power_ratio() * self.reference.amplitude().powi(2)
+    }
+
+    pub fn is_silent(self) -> bool {
+        self.decibels.0 == f32::NEG_INFINITY
+    }
+
+    /// Clamp to at least the given number of dB, e.g. so that silence can
+    /// be plotted
+    pub fn max(self, db: f32) -> Level {
+        Level {
+            decibels: self.decibels.max(db),
+            reference: self.reference,
+        }
+    }
+
+    /// Convert a dBFS level to another reference, given the level of full
+    /// scale in that reference (e.g. +18 dBu for an EBU-aligned interface)
+    pub fn calibrate(self, full_scale: Level) -> Level {
+        assert_eq!(self.reference, Reference::FullScale);
+        Level {
+            decibels: self.decibels + full_scale.decibels,
+            reference: full_scale.reference,
+        }
+    }
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{:.1} {}", self.decibels.0, self.reference)
+    }
+}
+
+impl From<Level> for f32 {
+    fn from(level: Level) -> f32 {
+        level.decibels.0
+    }
+}
+
+impl Add<Decibels> for Level {
+    type Output = Level;
+
+    fn add(self, rhs: Decibels) -> Level {
+        Level {
+            decibels: self.decibels + rhs,
+            reference: self.reference,
+        }
+    }
+}
+
+impl Sub for Level {
+    type Output = Decibels;
+
+    /// The difference between levels with the same reference
+    fn sub(self, rhs: Level) -> Decibels {
+        assert_eq!(self.reference, rhs.reference);
+        self.decibels - rhs.decibels
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Hz(pub f32);
 
@@ -78,12 +285,66 @@ mod tests {
     }
 
     #[test]
-    fn db_from_full_scale() {
-        assert_eq!(Decibels::from_full_scale(0.1), Decibels(-10.))
+    fn decibels() {
+        assert_eq!(Decibels::from_amplitude_ratio(0.1), Decibels(-20.));
+        assert_eq!(Decibels::from_power_ratio(0.1), Decibels(-10.));
+        assert_relative_eq!(Decibels::new(-20.).amplitude_ratio(), 0.1);
+        assert_relative_eq!(Decibels::new(-10.).power_ratio(), 0.1);
+        assert_relative_eq!(Decibels::new(6.).amplitude_ratio(), 2., max_relative = 0.01);
+        assert_eq!(
+            Decibels::from_amplitude_ratio(0.),
+            Decibels(f32::NEG_INFINITY)
+        );
+        assert_eq!(
+            Decibels::from_amplitude_ratio(0.).max(-120.),
+            Decibels(-120.)
+        );
     }
 
     #[test]
-    fn full_scale_from_db() {
-        assert_eq!(Decibels::new(-10.).into_full_scale(), 0.1);
+    fn levels() {
+        // A full scale sine wave's RMS amplitude is 1/sqrt(2), i.e. -3 dBFS
+        let rms = Level::dbfs(1. / 2f32.sqrt());
+        assert_relative_eq!(f32::from(rms), -3.0103, max_relative = 1e-4);
+        assert_relative_eq!(rms.power(), 0.5);
+        // ...whatever it's computed from
+        assert_relative_eq!(
+            f32::from(Level::from_power(0.5, Reference::FullScale)),
+            f32::from(rms)
+        );
+        assert_eq!(rms.to_string(), "-3.0 dBFS");
+
+        assert!(Level::dbfs(0.).is_silent());
+        assert!(!Level::dbfs(0.).max(-120.).is_silent());
+
+        assert_relative_eq!(
+            f32::from(Level::from_amplitude(1., Reference::DBu)),
+            2.2185,
+            max_relative = 1e-4
+        );
+        assert_relative_eq!(
+            f32::from(Level::from_amplitude(1., Reference::SPL)),
+            93.979,
+            max_relative = 1e-4
+        );
+        assert_relative_eq!(
+            Level::new(94., Reference::SPL).amplitude(),
+            1.0024,
+            max_relative = 1e-4
+        );
+
+        // With full scale at +18 dBu, -18 dBFS is 0 dBu
+        let aligned = Level::dbfs(0.125892).calibrate(Level::new(18., Reference::DBu));
+        assert_eq!(aligned.reference(), Reference::DBu);
+        assert_abs_diff_eq!(f32::from(aligned), 0., epsilon = 1e-4);
+
+        assert_eq!(
+            Level::new(-6., Reference::DBV) - Level::new(-20., Reference::DBV),
+            Decibels(14.)
+        );
+        assert_eq!(
+            Level::new(-6., Reference::DBV) + Decibels(-3.),
+            Level::new(-9., Reference::DBV)
+        );
     }
 }
@@ -0,0 +1,325 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+use crate::stream::buffer::Period;
+use crate::stream::{Instant, SampleRate};
+
+/// The longest trace that can be captured
+pub const MAX_SWEEP: Duration = Duration::from_millis(500);
+
+/// How many traces are captured per second, at most
+const REFRESH_RATE: usize = 25;
+
+/// In Auto mode, how long to wait for a trigger before free running
+const AUTO_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Which direction the trigger channel crosses the trigger level in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edge {
+    Rising,
+    Falling,
+}
+
+impl Edge {
+    pub const ALL: [Edge; 2] = [Edge::Rising, Edge::Falling];
+}
+
+impl Display for Edge {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        f.write_str(match self {
+            Edge::Rising => "Rising",
+            Edge::Falling => "Falling",
+        })
+    }
+}
+
+/// When traces are captured
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TriggerMode {
+    /// On each trigger, or free running if there hasn't been one for a while
+    /// (so there is always something to see)
+    Auto,
+    /// Only on triggers
+    Normal,
+    /// On the first trigger after being armed
+    Single,
+}
+
+impl TriggerMode {
+    pub const ALL: [TriggerMode; 3] = [TriggerMode::Auto, TriggerMode::Normal, TriggerMode::Single];
+}
+
+impl Display for TriggerMode {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        f.write_str(match self {
+            TriggerMode::Auto => "Auto",
+            TriggerMode::Normal => "Normal",
+            TriggerMode::Single => "Single",
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScopeOptions {
+    /// The duration of each trace (up to MAX_SWEEP), i.e. the timebase
+    pub sweep: Duration,
+    /// The fraction of each trace before the trigger
+    pub pre_trigger: f32,
+    pub mode: TriggerMode,
+    pub edge: Edge,
+    /// The trigger level (FS)
+    pub level: f32,
+    /// The channel that triggers traces (of all channels)
+    pub channel: usize,
+}
+
+impl Default for ScopeOptions {
+    fn default() -> ScopeOptions {
+        ScopeOptions {
+            sweep: Duration::from_millis(20),
+            pre_trigger: 0.5,
+            mode: TriggerMode::Auto,
+            edge: Edge::Rising,
+            level: 0.,
+            channel: 0,
+        }
+    }
+}
+
+/// The samples of every channel over one sweep
+#[derive(Clone, Debug)]
+pub struct ScopeTrace {
+    /// The time of the first sample
+    pub start_time: Instant,
+    pub sample_rate: SampleRate,
+    /// The time (seconds) from the first sample to the trigger, interpolated
+    /// between samples, or None for a free running trace
+    pub trigger: Option<f32>,
+    /// For each channel
+    pub channels: Vec<Vec<f32>>,
+}
+
+/// Captures triggered traces from a stream of periods, like an oscilloscope.
+///
+/// Periods should be period_len() samples long, and overlap so that each
+/// starts period_stride() samples after the last. Triggers are only looked
+/// for in the newest stride of each period (less the part of the sweep after
+/// the trigger), so the same trigger is never captured twice.
+pub struct Oscilloscope {
+    sample_rate: SampleRate,
+    options: ScopeOptions,
+    stride: usize,
+    /// For Single mode
+    armed: bool,
+    /// The number of samples since the last trace
+    since_trace: usize,
+}
+
+impl Oscilloscope {
+    pub fn new(sample_rate: SampleRate, options: &ScopeOptions) -> Oscilloscope {
+        Oscilloscope {
+            sample_rate,
+            options: *options,
+            stride: usize::from(sample_rate) / REFRESH_RATE,
+            armed: true,
+            since_trace: 0,
+        }
+    }
+
+    pub fn period_len(&self) -> usize {
+        self.sweep_len(MAX_SWEEP) + self.stride
+    }
+
+    pub fn period_stride(&self) -> usize {
+        self.stride
+    }
+
+    pub fn options(&self) -> &ScopeOptions {
+        &self.options
+    }
+
+    /// Change the options, which re-arms Single mode
+    pub fn set_options(&mut self, options: &ScopeOptions) {
+        self.options = *options;
+        self.armed = true;
+    }
+
+    /// Capture the next trigger, in Single mode
+    pub fn arm(&mut self) {
+        self.armed = true;
+    }
+
+    fn sweep_len(&self, sweep: Duration) -> usize {
+        (sweep.min(MAX_SWEEP).as_secs_f32() * f32::from(self.sample_rate)).round() as usize
+    }
+
+    /// The trace to display after the given period, if any
+    pub fn capture(&mut self, period: &Period) -> Option<ScopeTrace> {
+        assert_eq!(period.len(), self.period_len());
+        self.since_trace += self.stride;
+        if self.options.mode == TriggerMode::Single && !self.armed {
+            return None;
+        }
+
+        let len = self.sweep_len(self.options.sweep).max(2);
+        let pre = ((len as f32 * self.options.pre_trigger).round() as usize).min(len - 1);
+        let channels = period.channels();
+        let channel = self.options.channel.min(channels.len() - 1);
+        let samples: Vec<f32> = channels[channel].iter().copied().collect();
+
+        // The latest crossing in the newest stride whose sweep fits in the period
+        let last = period.len() - (len - pre);
+        let trigger = (last + 1 - self.stride..=last).rev().find_map(|i| {
+            let (a, b) = (samples[i - 1], samples[i]);
+            let level = self.options.level;
+            let crossed = match self.options.edge {
+                Edge::Rising => a < level && b >= level,
+                Edge::Falling => a > level && b <= level,
+            };
+            crossed.then(|| (i, (level - a) / (b - a)))
+        });
+
+        let start = match trigger {
+            Some((i, _)) => i - pre,
+            None if self.options.mode == TriggerMode::Auto
+                && self.since_trace >= self.sweep_len(AUTO_TIMEOUT) + len =>
+            {
+                period.len() - len
+            }
+            None => return None,
+        };
+        if trigger.is_some() && self.options.mode == TriggerMode::Single {
+            self.armed = false;
+        }
+        self.since_trace = 0;
+        let fs = f32::from(self.sample_rate);
+        Some(ScopeTrace {
+            start_time: period.start_time() + Duration::from_secs_f32(start as f32 / fs),
+            sample_rate: self.sample_rate,
+            // The crossing is between samples i - 1 and i
+            trigger: trigger.map(|(i, fraction)| (i - 1 - start) as f32 / fs + fraction / fs),
+            channels: channels
+                .iter()
+                .map(|ch| ch.iter().skip(start).take(len).copied().collect())
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::f32::consts::PI;
+
+    use crate::stream::buffer::{PeriodBuffer, SampleBuffer};
+    use crate::stream::{ChannelCount, Frame};
+
+    const FS: u32 = 48000;
+
+    /// The traces captured from a signal, after it's filled the first period
+    fn capture(scope: &mut Oscilloscope, signal: &[f32]) -> Vec<ScopeTrace> {
+        let sample_rate = SampleRate::new(FS);
+        let mut periods = PeriodBuffer::new(
+            SampleBuffer::new(ChannelCount::new(1), sample_rate, 2 * scope.period_len()),
+            scope.period_len(),
+            scope.period_stride(),
+        );
+        let mut traces = Vec::new();
+        for chunk in signal.chunks(1024) {
+            periods.push(&Frame {
+                channels: ChannelCount::new(1),
+                sample_rate,
+                samples: chunk.to_vec(),
+            });
+            while let Some(p) = periods.next() {
+                traces.extend(scope.capture(&p));
+            }
+        }
+        traces
+    }
+
+    fn sine(frequency: f32, secs: f32) -> Vec<f32> {
+        (0..(secs * FS as f32) as usize)
+            .map(|i| 0.5 * (2. * PI * frequency * i as f32 / FS as f32).sin())
+            .collect()
+    }
+
+    /// The interpolated value of a trace at its trigger
+    fn at_trigger(trace: &ScopeTrace) -> f32 {
+        let position = trace.trigger.unwrap() * FS as f32;
+        let (i, fraction) = (position.floor() as usize, position.fract());
+        let samples = &trace.channels[0];
+        samples[i] + fraction * (samples[i + 1] - samples[i])
+    }
+
+    #[test]
+    fn edges() {
+        let signal = sine(110., 2.);
+        for (edge, slope) in [(Edge::Rising, 1.), (Edge::Falling, -1.)] {
+            let options = ScopeOptions {
+                edge,
+                level: 0.2,
+                ..ScopeOptions::default()
+            };
+            let mut scope = Oscilloscope::new(SampleRate::new(FS), &options);
+            let traces = capture(&mut scope, &signal);
+            // A 110 Hz sine crosses every 40 ms stride
+            assert!(traces.len() > 20);
+            for trace in traces {
+                assert_eq!(trace.channels[0].len(), 960);
+                assert_relative_eq!(trace.trigger.unwrap(), 0.01, epsilon = 1. / FS as f32);
+                assert_relative_eq!(at_trigger(&trace), 0.2, epsilon = 1e-4);
+                // The signal is going the right way at the trigger
+                let i = 480;
+                assert!((trace.channels[0][i + 1] - trace.channels[0][i - 1]) * slope > 0.);
+            }
+        }
+    }
+
+    #[test]
+    fn modes() {
+        let silence = vec![0.; FS as usize];
+        let mut signal = silence.clone();
+        signal.extend(sine(110., 1.));
+
+        // Normal only captures triggered traces
+        let options = ScopeOptions {
+            mode: TriggerMode::Normal,
+            level: 0.1,
+            ..ScopeOptions::default()
+        };
+        let mut scope = Oscilloscope::new(SampleRate::new(FS), &options);
+        assert!(capture(&mut scope, &silence).is_empty());
+        let traces = capture(&mut scope, &signal);
+        assert!(!traces.is_empty());
+        assert!(traces.iter().all(|t| t.trigger.is_some()));
+
+        // Auto free runs without triggers
+        let mut scope = Oscilloscope::new(
+            SampleRate::new(FS),
+            &ScopeOptions {
+                mode: TriggerMode::Auto,
+                ..options
+            },
+        );
+        let traces = capture(&mut scope, &silence);
+        assert!(!traces.is_empty());
+        assert!(traces.iter().all(|t| t.trigger.is_none()));
+
+        // Single captures one trace until it's armed again
+        let mut scope = Oscilloscope::new(
+            SampleRate::new(FS),
+            &ScopeOptions {
+                mode: TriggerMode::Single,
+                ..options
+            },
+        );
+        let traces = capture(&mut scope, &signal);
+        assert_eq!(traces.len(), 1);
+        assert!(capture(&mut scope, &signal).is_empty());
+        scope.arm();
+        assert_eq!(capture(&mut scope, &signal).len(), 1);
+    }
+}
@@ -0,0 +1,283 @@
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+
+use super::window::Window;
+use crate::stream::{ChannelCount, Frame, SampleRate};
+
+/// Samples at least this far from zero are at full scale, i.e. the largest
+/// 16 bit sample, which is the coarsest resolution that inputs have
+pub const CLIP_LEVEL: f32 = 32767. / 32768.;
+
+/// How many consecutive full scale samples count as a clip (rather than a
+/// legitimate peak that happens to reach full scale)
+pub const MIN_CLIP_RUN: usize = 3;
+
+/// The length of each phase of the interpolation filter, as in BS.1770
+const TAPS_PER_PHASE: usize = 12;
+
+/// The peaks of a channel over a measurement interval
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ChannelPeaks {
+    /// The largest absolute sample value (FS)
+    pub sample_peak: f32,
+    /// The largest absolute value of the oversampled signal (FS), which
+    /// estimates the peak of the reconstructed analog signal, i.e. dBTP
+    pub true_peak: f32,
+    /// The ratio of the sample peak to the RMS (e.g. sqrt(2) for a sine wave)
+    pub crest_factor: f32,
+    /// The total number of clips since the meter was reset
+    pub clips: usize,
+}
+
+/// Interpolates a channel by an integer factor, with a windowed-sinc
+/// polyphase FIR filter, to find the peaks between its samples (as in
+/// ITU-R BS.1770 annex 2)
+struct Oversampler {
+    /// The filter coefficients of each phase, most recent input first
+    phases: Vec<Vec<f32>>,
+    /// The latest inputs, most recent first
+    inputs: VecDeque<f32>,
+}
+
+impl Oversampler {
+    fn new(factor: usize) -> Oversampler {
+        // An odd length, so that the interpolated samples include the input
+        // samples (rather than all being between them)
+        let len = factor * TAPS_PER_PHASE + 1;
+        let center = (len / 2) as f32;
+        // A symmetric window of len points
+        let window = &Window::Hann.coefficients(len + 1)[1..];
+        let h: Vec<f32> = (0..len)
+            .map(|m| {
+                let x = (m as f32 - center) / factor as f32;
+                let sinc = if x == 0. {
+                    1.
+                } else {
+                    (PI * x).sin() / (PI * x)
+                };
+                sinc * window[m]
+            })
+            .collect();
+        let phases = (0..factor)
+            .map(|p| {
+                let phase: Vec<f32> = h.iter().skip(p).step_by(factor).copied().collect();
+                // Normalized so that each phase passes DC at unity gain
+                let sum: f32 = phase.iter().sum();
+                phase.iter().map(|c| c / sum).collect()
+            })
+            .collect();
+        Oversampler {
+            phases,
+            inputs: vec![0.; TAPS_PER_PHASE + 1].into(),
+        }
+    }
+
+    /// The largest absolute value of the interpolated samples up to (and
+    /// just before) the given input, which is delayed by the filter
+    fn push(&mut self, x: f32) -> f32 {
+        self.inputs.pop_back();
+        self.inputs.push_front(x);
+        self.phases
+            .iter()
+            .map(|phase| {
+                phase
+                    .iter()
+                    .zip(&self.inputs)
+                    .map(|(h, x)| h * x)
+                    .sum::<f32>()
+                    .abs()
+            })
+            .fold(0., f32::max)
+    }
+}
+
+struct ChannelMeter {
+    oversampler: Option<Oversampler>,
+    sample_peak: f32,
+    true_peak: f32,
+    sum_squares: f64,
+    count: usize,
+    /// The length of the current run of full scale samples
+    run: usize,
+    clips: usize,
+}
+
+impl ChannelMeter {
+    fn push(&mut self, x: f32) {
+        let magnitude = x.abs();
+        self.sample_peak = self.sample_peak.max(magnitude);
+        let interpolated = match &mut self.oversampler {
+            Some(oversampler) => oversampler.push(x),
+            None => magnitude,
+        };
+        self.true_peak = self.true_peak.max(interpolated).max(magnitude);
+        self.sum_squares += f64::from(x * x);
+        self.count += 1;
+
+        if magnitude >= CLIP_LEVEL {
+            self.run += 1;
+            if self.run == MIN_CLIP_RUN {
+                self.clips += 1;
+            }
+        } else {
+            self.run = 0;
+        }
+    }
+
+    fn take(&mut self) -> ChannelPeaks {
+        let rms = (self.sum_squares / self.count.max(1) as f64).sqrt() as f32;
+        let peaks = ChannelPeaks {
+            sample_peak: self.sample_peak,
+            true_peak: self.true_peak,
+            crest_factor: if rms > 0. { self.sample_peak / rms } else { 1. },
+            clips: self.clips,
+        };
+        self.sample_peak = 0.;
+        self.true_peak = 0.;
+        self.sum_squares = 0.;
+        self.count = 0;
+        peaks
+    }
+}
+
+/// Measures the sample peak, true peak and crest factor of each channel over
+/// successive intervals, and counts clips.
+///
+/// True peaks are found by oversampling 4 times (at sample rates below 96
+/// kHz) or twice (below 192 kHz), as BS.1770 recommends.
+pub struct PeakMeter {
+    channels: ChannelCount,
+    meters: Vec<ChannelMeter>,
+}
+
+impl PeakMeter {
+    pub fn new(channels: ChannelCount, sample_rate: SampleRate) -> PeakMeter {
+        let factor = match u32::from(sample_rate) {
+            ..96000 => 4,
+            96000..192000 => 2,
+            _ => 1,
+        };
+        PeakMeter {
+            channels,
+            meters: (0..usize::from(channels))
+                .map(|_| ChannelMeter {
+                    oversampler: (factor > 1).then(|| Oversampler::new(factor)),
+                    sample_peak: 0.,
+                    true_peak: 0.,
+                    sum_squares: 0.,
+                    count: 0,
+                    run: 0,
+                    clips: 0,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn push(&mut self, frame: &Frame) {
+        assert!(frame.channels == self.channels);
+        for samples in frame.samples.chunks_exact(usize::from(self.channels)) {
+            for (x, meter) in samples.iter().zip(&mut self.meters) {
+                meter.push(*x);
+            }
+        }
+    }
+
+    /// The peaks of each channel since the last call, or since the meter was
+    /// created
+    pub fn take(&mut self) -> Vec<ChannelPeaks> {
+        self.meters.iter_mut().map(|m| m.take()).collect()
+    }
+
+    /// Restart counting clips
+    pub fn reset_clips(&mut self) {
+        for meter in &mut self.meters {
+            meter.clips = 0;
+            meter.run = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FS: u32 = 48000;
+
+    /// The peaks of a signal, after the first 100 samples (so that the
+    /// interpolation filter doesn't ring at the signal's abrupt start)
+    fn meter(signal: &[f32]) -> ChannelPeaks {
+        let mut meter = PeakMeter::new(ChannelCount::new(1), SampleRate::new(FS));
+        let (start, rest) = signal.split_at(100);
+        for samples in [start, rest] {
+            meter.take();
+            meter.push(&Frame {
+                channels: ChannelCount::new(1),
+                sample_rate: SampleRate::new(FS),
+                samples: samples.to_vec(),
+            });
+        }
+        meter.take()[0]
+    }
+
+    fn sine(frequency: f32, phase: f32, amplitude: f32) -> Vec<f32> {
+        (0..4800)
+            .map(|i| amplitude * (2. * PI * frequency * i as f32 / FS as f32 + phase).sin())
+            .collect()
+    }
+
+    #[test]
+    fn inter_sample_peaks() {
+        // At fs/4, 45 degrees out of phase with the samples, every sample is
+        // 3 dB below the peak
+        let peaks = meter(&sine(12000., PI / 4., 0.5));
+        assert_relative_eq!(peaks.sample_peak, 0.5 / 2f32.sqrt(), max_relative = 1e-3);
+        assert_relative_eq!(peaks.true_peak, 0.5, max_relative = 0.01);
+        assert_relative_eq!(peaks.crest_factor, 1., max_relative = 1e-3);
+
+        // Low frequencies are barely between samples
+        let peaks = meter(&sine(997., 0.3, 0.5));
+        assert_relative_eq!(peaks.sample_peak, 0.5, max_relative = 1e-3);
+        assert_relative_eq!(peaks.true_peak, 0.5, max_relative = 0.01);
+        assert_relative_eq!(peaks.crest_factor, 2f32.sqrt(), max_relative = 1e-2);
+
+        // Oversampling makes the peaks of high frequencies more accurate, but
+        // doesn't much overestimate them
+        for frequency in [5000., 10000., 15000., 19000.] {
+            for phase in [0., 0.5, 1., 2.] {
+                let peaks = meter(&sine(frequency, phase, 0.5));
+                assert!(peaks.true_peak >= peaks.sample_peak);
+                assert_relative_eq!(peaks.true_peak, 0.5, max_relative = 0.02);
+            }
+        }
+    }
+
+    #[test]
+    fn clips() {
+        let mut signal = vec![0.; 200];
+        // Too short to count
+        signal[110..112].fill(1.);
+        // One clip each
+        signal[120..123].fill(1.);
+        signal[130..140].fill(-1.);
+        let peaks = meter(&signal);
+        assert_eq!(peaks.sample_peak, 1.);
+        assert_eq!(peaks.clips, 2);
+
+        // Clips are counted until reset, but peaks are per interval
+        let mut meter = PeakMeter::new(ChannelCount::new(2), SampleRate::new(FS));
+        let frame = |samples: Vec<f32>| Frame {
+            channels: ChannelCount::new(2),
+            sample_rate: SampleRate::new(FS),
+            samples,
+        };
+        meter.push(&frame([1., 0.5].repeat(4)));
+        meter.take();
+        meter.push(&frame([0.1, 0.2].repeat(4)));
+        let peaks = meter.take();
+        assert_eq!(peaks[0].clips, 1);
+        assert_eq!(peaks[0].sample_peak, 0.1);
+        assert_eq!(peaks[1].clips, 0);
+        meter.reset_clips();
+        assert_eq!(meter.take()[0].clips, 0);
+    }
+}
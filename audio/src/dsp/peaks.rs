@@ -0,0 +1,381 @@
+use std::fmt;
+
+use super::fft::FoldedFFT;
+use super::tuning::cents;
+use super::{Hz, Level};
+
+/// How a peak's frequency and amplitude are estimated from the bins
+/// around it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Just the peak bin's
+    None,
+    /// Fit a parabola to the magnitudes of the peak bin and its neighbours
+    Parabolic,
+    /// Fit a gaussian to the magnitudes, i.e. a parabola to the natural log
+    /// of the magnitudes, which is exact for gaussian windows and a good
+    /// approximation for Hann and similar
+    Gaussian,
+}
+
+impl Interpolation {
+    pub const ALL: [Interpolation; 3] = [
+        Interpolation::None,
+        Interpolation::Parabolic,
+        Interpolation::Gaussian,
+    ];
+}
+
+impl fmt::Display for Interpolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Interpolation::None => "None",
+            Interpolation::Parabolic => "Parabolic",
+            Interpolation::Gaussian => "Gaussian",
+        })
+    }
+}
+
+/// Criteria for which local maxima of a spectrum count as peaks
+#[derive(Clone, Debug, PartialEq)]
+pub struct PeakOptions {
+    /// Peaks must be at least this loud (dBFS)
+    pub threshold_db: f32,
+    /// Peaks must stand at least this far (dB) above the higher of the
+    /// lowest points between them and a louder peak on either side
+    pub min_prominence_db: f32,
+    /// Of peaks closer together than this, only the loudest is kept
+    pub min_separation: Hz,
+    /// Keep at most this many (of the loudest) peaks
+    pub max_peaks: Option<usize>,
+    pub interpolation: Interpolation,
+}
+
+impl Default for PeakOptions {
+    fn default() -> PeakOptions {
+        PeakOptions {
+            threshold_db: -80.,
+            min_prominence_db: 6.,
+            min_separation: Hz(0.),
+            max_peaks: None,
+            interpolation: Interpolation::Gaussian,
+        }
+    }
+}
+
+/// A peak in a spectrum
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Peak {
+    /// The bin of the local maximum
+    pub bin: usize,
+    /// The (interpolated) frequency
+    pub frequency: Hz,
+    /// The (interpolated) amplitude (FS). For windowed FFTs this includes
+    /// the window's coherent gain.
+    pub amplitude: f32,
+    /// See PeakOptions::min_prominence_db
+    pub prominence_db: f32,
+}
+
+impl Peak {
+    pub fn amplitude_db(&self) -> f32 {
+        Level::dbfs(self.amplitude).into()
+    }
+}
+
+/// Find the peaks of a spectrum, loudest first
+pub fn find_peaks(fft: &FoldedFFT, options: &PeakOptions) -> Vec<Peak> {
+    let db: Vec<f32> = fft
+        .values
+        .iter()
+        .map(|(r, _)| Level::dbfs(r.max(1e-12)).into())
+        .collect();
+    let bin_width = f32::from(fft.sample_rate()) / fft.unfolded_length() as f32;
+
+    let mut peaks: Vec<Peak> = (1..db.len().saturating_sub(1))
+        .filter(|i| db[*i] > db[i - 1] && db[*i] >= db[i + 1] && db[*i] >= options.threshold_db)
+        .filter_map(|i| {
+            let prominence_db = prominence(&db, i);
+            if prominence_db < options.min_prominence_db {
+                return None;
+            }
+            let (offset, amplitude) = interpolate(fft, i, options.interpolation);
+            Some(Peak {
+                bin: i,
+                frequency: Hz((i as f32 + offset) * bin_width),
+                amplitude,
+                prominence_db,
+            })
+        })
+        .collect();
+    peaks.sort_by(|a, b| b.amplitude.total_cmp(&a.amplitude));
+
+    let mut kept: Vec<Peak> = Vec::new();
+    for peak in peaks {
+        let separated = kept
+            .iter()
+            .all(|k| (k.frequency.0 - peak.frequency.0).abs() >= options.min_separation.0);
+        if separated {
+            kept.push(peak);
+        }
+        if options.max_peaks.is_some_and(|max| kept.len() >= max) {
+            break;
+        }
+    }
+    kept
+}
+
+/// How far (dB) the peak at i rises above the higher of the minima between
+/// it and the nearest higher values on each side (or the spectrum's ends)
+fn prominence(db: &[f32], i: usize) -> f32 {
+    let left = db[..i]
+        .iter()
+        .rev()
+        .take_while(|y| **y <= db[i])
+        .copied()
+        .fold(db[i], f32::min);
+    let right = db[i + 1..]
+        .iter()
+        .take_while(|y| **y <= db[i])
+        .copied()
+        .fold(db[i], f32::min);
+    db[i] - left.max(right)
+}
+
+/// The fractional bin offset, and amplitude, of the peak at bin i
+fn interpolate(fft: &FoldedFFT, i: usize, interpolation: Interpolation) -> (f32, f32) {
+    let r = |i: usize| fft.values[i].0.max(1e-12);
+    let scale: fn(f32) -> f32 = match interpolation {
+        Interpolation::None => return (0., r(i)),
+        Interpolation::Parabolic => |r| r,
+        Interpolation::Gaussian => f32::ln,
+    };
+    let (a, b, c) = (scale(r(i - 1)), scale(r(i)), scale(r(i + 1)));
+    let denominator = a - 2. * b + c;
+    if denominator >= 0. {
+        return (0., r(i));
+    }
+    let offset = 0.5 * (a - c) / denominator;
+    let peak = b - 0.25 * (a - c) * offset;
+    let amplitude = match interpolation {
+        Interpolation::Gaussian => peak.exp(),
+        _ => peak,
+    };
+    (offset, amplitude)
+}
+
+/// Peaks identified as partials of a fundamental
+#[derive(Clone, Debug, PartialEq)]
+pub struct HarmonicSeries {
+    /// Fitted to the partials' frequencies
+    pub fundamental: Hz,
+    /// (harmonic number, peak), in order of harmonic number
+    pub partials: Vec<(usize, Peak)>,
+}
+
+/// Find the harmonic series that best explains a set of peaks, within
+/// tolerance_cents of each harmonic's frequency. Series are scored by the
+/// total amplitude of their partials, times the fraction of their harmonics
+/// (up to the highest partial) that are present, which stops subharmonics of
+/// the fundamental (that include all the same partials and more, by chance)
+/// from winning. The fundamental itself needn't be one of the peaks. None
+/// unless at least two peaks are partials.
+pub fn harmonic_series(peaks: &[Peak], tolerance_cents: f32) -> Option<HarmonicSeries> {
+    // Candidate fundamentals are each peak's frequency divided by up to this
+    const MAX_MISSING: usize = 4;
+    const MAX_HARMONIC: usize = 64;
+
+    let partials = |f0: f32| -> Vec<(usize, Peak)> {
+        let mut partials: Vec<(usize, Peak)> = Vec::new();
+        for peak in peaks {
+            let n = (peak.frequency.0 / f0).round() as usize;
+            if n == 0 || n > MAX_HARMONIC {
+                continue;
+            }
+            if cents(peak.frequency, Hz(n as f32 * f0)).abs() > tolerance_cents {
+                continue;
+            }
+            // Only the loudest peak for each harmonic
+            match partials.iter_mut().find(|(m, _)| *m == n) {
+                Some(existing) if existing.1.amplitude < peak.amplitude => existing.1 = *peak,
+                Some(_) => (),
+                None => partials.push((n, *peak)),
+            }
+        }
+        partials
+    };
+    let score = |partials: &[(usize, Peak)]| -> f32 {
+        let amplitude: f32 = partials.iter().map(|(_, p)| p.amplitude).sum();
+        let highest = partials.iter().map(|(n, _)| *n).max().unwrap_or(1);
+        amplitude * partials.len() as f32 / highest as f32
+    };
+
+    let mut best: Option<(f32, Vec<(usize, Peak)>)> = None;
+    for peak in peaks {
+        for k in 1..=MAX_MISSING {
+            let f0 = peak.frequency.0 / k as f32;
+            let candidate = partials(f0);
+            if candidate.len() < 2 {
+                continue;
+            }
+            // (Candidates are compared at a small tolerance, so that higher
+            // fundamentals win ties with their subharmonics)
+            let better = match &best {
+                Some((best_f0, best_partials)) => {
+                    let (s, best_s) = (score(&candidate), score(best_partials));
+                    s > best_s * 1.001 || (s > best_s * 0.999 && f0 > *best_f0)
+                }
+                None => true,
+            };
+            if better {
+                best = Some((f0, candidate));
+            }
+        }
+    }
+
+    let (_, mut partials) = best?;
+    partials.sort_by_key(|(n, _)| *n);
+    // Least squares fit of f0 to the partials' frequencies, weighted by
+    // amplitude
+    let (numerator, denominator) = partials.iter().fold((0., 0.), |(num, den), (n, p)| {
+        let n = *n as f32;
+        (
+            num + p.amplitude * n * p.frequency.0,
+            den + p.amplitude * n * n,
+        )
+    });
+    Some(HarmonicSeries {
+        fundamental: Hz(numerator / denominator),
+        partials,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::f32::consts::PI;
+
+    use crate::dsp::fft::CartesianFFT;
+    use crate::dsp::window::Window;
+    use crate::stream::input::SampleRate;
+
+    const FS: u32 = 8000;
+    const LEN: usize = 1024;
+
+    /// The spectrum of a Hann windowed sum of sinusoids, as (frequency,
+    /// amplitude) pairs
+    fn spectrum(sinusoids: &[(f32, f32)]) -> FoldedFFT {
+        let window = Window::Hann.coefficients(LEN);
+        let signal: Vec<f32> = (0..LEN)
+            .zip(window)
+            .map(|(i, w)| {
+                let t = i as f32 / FS as f32;
+                let y: f32 = sinusoids
+                    .iter()
+                    .map(|(f, a)| a * (2. * PI * f * t).sin())
+                    .sum();
+                // Hann's coherent gain is 0.5, so this preserves amplitudes
+                2. * w * y
+            })
+            .collect();
+        CartesianFFT::from_real_signal(signal, SampleRate::new(FS))
+            .into_polar()
+            .into_folded()
+    }
+
+    #[test]
+    fn interpolated_frequency() {
+        // Between bins (which are 7.8125 Hz wide)
+        let fft = spectrum(&[(1003., 0.5)]);
+        let bin_centred = find_peaks(
+            &fft,
+            &PeakOptions {
+                interpolation: Interpolation::None,
+                ..PeakOptions::default()
+            },
+        );
+        assert_eq!(bin_centred.len(), 1);
+        assert_abs_diff_eq!(bin_centred[0].frequency.0, 1000., epsilon = 0.01);
+
+        let parabolic = find_peaks(
+            &fft,
+            &PeakOptions {
+                interpolation: Interpolation::Parabolic,
+                ..PeakOptions::default()
+            },
+        );
+        assert_abs_diff_eq!(parabolic[0].frequency.0, 1003., epsilon = 0.5);
+        assert_relative_eq!(parabolic[0].amplitude, 0.5, max_relative = 0.06);
+
+        let gaussian = find_peaks(&fft, &PeakOptions::default());
+        assert_abs_diff_eq!(gaussian[0].frequency.0, 1003., epsilon = 0.5);
+        assert_relative_eq!(gaussian[0].amplitude, 0.5, max_relative = 0.03);
+        // Which is closer than the peak bin's amplitude (scalloping)
+        assert!((gaussian[0].amplitude - 0.5).abs() < (bin_centred[0].amplitude - 0.5).abs());
+    }
+
+    #[test]
+    fn selection() {
+        let fft = spectrum(&[(500., 0.5), (530., 0.1), (1500., 0.01), (3000., 0.3)]);
+        let all = find_peaks(&fft, &PeakOptions::default());
+        let frequencies: Vec<f32> = all.iter().map(|p| p.frequency.0.round()).collect();
+        assert_eq!(frequencies, vec![500., 3000., 530., 1500.]);
+
+        let loud = find_peaks(
+            &fft,
+            &PeakOptions {
+                threshold_db: -30.,
+                ..PeakOptions::default()
+            },
+        );
+        assert_eq!(loud.len(), 3);
+
+        let separated = find_peaks(
+            &fft,
+            &PeakOptions {
+                min_separation: Hz(50.),
+                max_peaks: Some(2),
+                ..PeakOptions::default()
+            },
+        );
+        let frequencies: Vec<f32> = separated.iter().map(|p| p.frequency.0.round()).collect();
+        assert_eq!(frequencies, vec![500., 3000.]);
+    }
+
+    #[test]
+    fn prominence_of_shoulder() {
+        // A small ripple on the side of a large peak isn't prominent
+        let db = [-60., -20., 0., -20., -19., -40., -60.];
+        assert_eq!(prominence(&db, 2), 60.);
+        assert_eq!(prominence(&db, 4), 1.);
+    }
+
+    fn peak(frequency: f32, amplitude: f32) -> Peak {
+        Peak {
+            bin: 0,
+            frequency: Hz(frequency),
+            amplitude,
+            prominence_db: 20.,
+        }
+    }
+
+    #[test]
+    fn harmonics() {
+        // A slightly inharmonic series, missing its fundamental, with an
+        // unrelated peak
+        let peaks = [
+            peak(441., 0.5),
+            peak(661., 0.2),
+            peak(881., 0.3),
+            peak(1102., 0.2),
+            peak(1000., 0.25),
+        ];
+        let series = harmonic_series(&peaks, 20.).unwrap();
+        assert_relative_eq!(series.fundamental.0, 220.3, max_relative = 0.001);
+        let numbers: Vec<usize> = series.partials.iter().map(|(n, _)| *n).collect();
+        assert_eq!(numbers, vec![2, 3, 4, 5]);
+
+        assert!(harmonic_series(&[peak(440., 0.5)], 20.).is_none());
+    }
+}
@@ -0,0 +1,326 @@
+use std::collections::VecDeque;
+
+use super::Hz;
+use crate::stream::buffer::ChannelPeriod;
+use crate::stream::SampleRate;
+
+/// Periods quieter than this (RMS, FS) are unvoiced, i.e. around -60 dBFS
+const SILENCE: f32 = 0.001;
+
+/// The number of recent voiced estimates that PitchTracker's octave
+/// correction is based on
+const HISTORY_LEN: usize = 5;
+
+/// PitchTracker forgets its history after this many unvoiced periods in a row
+const MAX_GAP: usize = 3;
+
+/// The fundamental frequency of a period
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PitchEstimate {
+    /// The best estimate, even if the period isn't voiced
+    pub frequency: Hz,
+    /// From 0 (aperiodic) to 1 (perfectly periodic)
+    pub confidence: f32,
+    pub voiced: bool,
+}
+
+/// The YIN fundamental frequency estimator (de Cheveigné & Kawahara, 2002)
+pub struct Yin {
+    sample_rate: f32,
+    min_lag: usize,
+    max_lag: usize,
+    /// Dips in the normalized difference below this are voiced
+    threshold: f32,
+    /// The cumulative mean normalized difference, for each lag
+    difference: Vec<f32>,
+}
+
+impl Yin {
+    /// An estimator for fundamental frequencies between min_frequency and
+    /// max_frequency. Periods need to be at least two of the longest
+    /// fundamental period long.
+    pub fn new(sample_rate: SampleRate, min_frequency: Hz, max_frequency: Hz) -> Yin {
+        let fs = f32::from(sample_rate);
+        Yin {
+            sample_rate: fs,
+            min_lag: ((fs / max_frequency.0).floor() as usize).max(2),
+            max_lag: (fs / min_frequency.0).ceil() as usize,
+            threshold: 0.15,
+            difference: Vec::new(),
+        }
+    }
+
+    /// The shortest signal that can be estimated: two of the longest
+    /// fundamental period
+    pub fn min_signal_len(&self) -> usize {
+        2 * self.max_lag
+    }
+
+    /// Estimate the fundamental frequency of a signal
+    pub fn estimate(&mut self, signal: &[f32]) -> PitchEstimate {
+        self.normalize_difference(signal);
+        let lag = self.first_dip();
+        self.estimate_at(signal, lag)
+    }
+
+    /// Compute the cumulative mean normalized difference function of the
+    /// signal, for lags up to max_lag
+    fn normalize_difference(&mut self, signal: &[f32]) {
+        assert!(
+            signal.len() > self.max_lag,
+            "Signal too short for the minimum frequency"
+        );
+        let width = signal.len() - self.max_lag;
+        self.difference.clear();
+        self.difference.push(1.);
+        let mut total = 0.;
+        for lag in 1..=self.max_lag {
+            let d: f32 = signal[..width]
+                .iter()
+                .zip(&signal[lag..lag + width])
+                .map(|(a, b)| (a - b) * (a - b))
+                .sum();
+            total += d;
+            self.difference.push(if total > 0. {
+                d * lag as f32 / total
+            } else {
+                1.
+            });
+        }
+    }
+
+    /// The first lag at which the difference dips below the threshold (or
+    /// failing that, the lag of the smallest difference)
+    fn first_dip(&self) -> usize {
+        let d = &self.difference;
+        match (self.min_lag..=self.max_lag).find(|lag| d[*lag] < self.threshold) {
+            Some(lag) => self.local_minimum(lag),
+            None => (self.min_lag..=self.max_lag)
+                .min_by(|a, b| d[*a].total_cmp(&d[*b]))
+                .unwrap(),
+        }
+    }
+
+    /// Follow the difference downhill from the given lag
+    fn local_minimum(&self, mut lag: usize) -> usize {
+        let d = &self.difference;
+        while lag < self.max_lag && d[lag + 1] < d[lag] {
+            lag += 1;
+        }
+        while lag > self.min_lag && d[lag - 1] < d[lag] {
+            lag -= 1;
+        }
+        lag
+    }
+
+    /// The estimate for the given lag (which should be a local minimum),
+    /// refined with parabolic interpolation
+    fn estimate_at(&self, signal: &[f32], lag: usize) -> PitchEstimate {
+        let d = &self.difference;
+        let mut period = lag as f32;
+        if lag > 1 && lag < self.max_lag {
+            let (a, b, c) = (d[lag - 1], d[lag], d[lag + 1]);
+            let denominator = a - 2. * b + c;
+            if denominator > 0. {
+                period += 0.5 * (a - c) / denominator;
+            }
+        }
+        let rms = (signal.iter().map(|y| y * y).sum::<f32>() / signal.len() as f32).sqrt();
+        PitchEstimate {
+            frequency: Hz(self.sample_rate / period),
+            confidence: (1. - d[lag]).clamp(0., 1.),
+            voiced: d[lag] < self.threshold && rms > SILENCE,
+        }
+    }
+}
+
+/// Tracks the fundamental frequency of successive periods, with YIN,
+/// correcting octave errors by preferring (almost equally good) candidates
+/// that are close to recent estimates.
+pub struct PitchTracker {
+    yin: Yin,
+    /// Recent voiced frequencies
+    history: VecDeque<f32>,
+    /// The number of unvoiced periods since the last voiced one
+    gap: usize,
+}
+
+impl PitchTracker {
+    pub fn new(sample_rate: SampleRate, min_frequency: Hz, max_frequency: Hz) -> PitchTracker {
+        PitchTracker {
+            yin: Yin::new(sample_rate, min_frequency, max_frequency),
+            history: VecDeque::new(),
+            gap: 0,
+        }
+    }
+
+    /// A tracker for (adult and child) voices
+    pub fn for_voice(sample_rate: SampleRate) -> PitchTracker {
+        PitchTracker::new(sample_rate, Hz(60.), Hz(1000.))
+    }
+
+    /// The median of recent voiced estimates, if there are any
+    pub fn reference(&self) -> Option<Hz> {
+        let mut recent: Vec<f32> = self.history.iter().copied().collect();
+        recent.sort_by(f32::total_cmp);
+        recent.get(recent.len() / 2).map(|f| Hz(*f))
+    }
+
+    /// The shortest period that can be tracked, which grows with the sample
+    /// rate
+    pub fn min_period_len(&self) -> usize {
+        self.yin.min_signal_len()
+    }
+
+    pub fn reset(&mut self) {
+        self.history.clear();
+        self.gap = 0;
+    }
+
+    /// Estimate the fundamental frequency of the next period
+    pub fn push(&mut self, period: &ChannelPeriod) -> PitchEstimate {
+        let signal: Vec<f32> = period.iter().copied().collect();
+        self.push_signal(&signal)
+    }
+
+    fn push_signal(&mut self, signal: &[f32]) -> PitchEstimate {
+        let reference = self.reference();
+        let yin = &mut self.yin;
+        yin.normalize_difference(signal);
+        let mut lag = yin.first_dip();
+
+        if let Some(reference) = reference {
+            // Consider the octaves either side of the candidate, if they're
+            // closer to the reference and voiced too
+            let reference_lag = yin.sample_rate / reference.0;
+            let distance = |lag: usize| (lag as f32 / reference_lag).log2().abs();
+            for alternative in [lag * 2, lag / 2] {
+                if alternative < yin.min_lag || alternative > yin.max_lag {
+                    continue;
+                }
+                let alternative = yin.local_minimum(alternative);
+                if yin.difference[alternative] < yin.threshold
+                    && distance(alternative) < distance(lag)
+                {
+                    lag = alternative;
+                }
+            }
+        }
+
+        let estimate = yin.estimate_at(signal, lag);
+        if estimate.voiced {
+            self.history.push_back(estimate.frequency.0);
+            if self.history.len() > HISTORY_LEN {
+                self.history.pop_front();
+            }
+            self.gap = 0;
+        } else {
+            self.gap += 1;
+            if self.gap >= MAX_GAP {
+                self.history.clear();
+            }
+        }
+        estimate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::f32::consts::PI;
+
+    const FS: u32 = 16000;
+
+    /// A sum of harmonics of f0, with the given amplitudes
+    fn harmonics(f0: f32, amplitudes: &[f32], len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| {
+                let t = i as f32 / FS as f32;
+                amplitudes
+                    .iter()
+                    .enumerate()
+                    .map(|(h, a)| a * (2. * PI * f0 * (h + 1) as f32 * t).sin())
+                    .sum()
+            })
+            .collect()
+    }
+
+    fn yin() -> Yin {
+        Yin::new(SampleRate::new(FS), Hz(60.), Hz(1000.))
+    }
+
+    #[test]
+    fn sinusoid() {
+        let estimate = yin().estimate(&harmonics(220., &[0.5], 1024));
+        assert!(estimate.voiced);
+        assert_relative_eq!(estimate.frequency.0, 220., max_relative = 0.005);
+        assert!(estimate.confidence > 0.95);
+    }
+
+    #[test]
+    fn harmonic_complex() {
+        // A voice-like spectrum, with a weak fundamental
+        let signal = harmonics(130., &[0.1, 0.5, 0.4, 0.3, 0.2, 0.1], 1024);
+        let estimate = yin().estimate(&signal);
+        assert!(estimate.voiced);
+        assert_relative_eq!(estimate.frequency.0, 130., max_relative = 0.005);
+    }
+
+    #[test]
+    fn unvoiced() {
+        assert!(!yin().estimate(&[0.; 1024]).voiced);
+
+        let mut state = 12345u32;
+        let noise: Vec<f32> = (0..1024)
+            .map(|_| {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                (state >> 8) as f32 / (1u32 << 23) as f32 - 1.
+            })
+            .collect();
+        let estimate = yin().estimate(&noise);
+        assert!(!estimate.voiced);
+        assert!(estimate.confidence < 0.85);
+    }
+
+    #[test]
+    fn high_sample_rate() {
+        let sample_rate = SampleRate::new(192000);
+        let mut tracker = PitchTracker::for_voice(sample_rate);
+        assert!(tracker.min_period_len() >= 2 * 192000 / 60);
+        let signal: Vec<f32> = (0..tracker.min_period_len())
+            .map(|i| 0.5 * (2. * PI * 110. * i as f32 / 192000.).sin())
+            .collect();
+        let estimate = tracker.push_signal(&signal);
+        assert!(estimate.voiced);
+        assert_relative_eq!(estimate.frequency.0, 110., max_relative = 0.005);
+    }
+
+    #[test]
+    fn octave_correction() {
+        // Mostly even harmonics of 150 Hz, so YIN alone finds 300 Hz
+        let ambiguous = harmonics(150., &[0.02, 0.5, 0.02, 0.4, 0.0, 0.3], 1024);
+        assert_relative_eq!(
+            yin().estimate(&ambiguous).frequency.0,
+            300.,
+            max_relative = 0.01
+        );
+
+        // But after a run of clearly 150 Hz periods, the tracker stays there
+        let mut tracker = PitchTracker::new(SampleRate::new(FS), Hz(60.), Hz(1000.));
+        let clear = harmonics(150., &[0.5, 0.3, 0.2], 1024);
+        for _ in 0..3 {
+            tracker.push_signal(&clear);
+        }
+        assert_relative_eq!(tracker.reference().unwrap().0, 150., max_relative = 0.01);
+        let estimate = tracker.push_signal(&ambiguous);
+        assert!(estimate.voiced);
+        assert_relative_eq!(estimate.frequency.0, 150., max_relative = 0.01);
+
+        // And history is forgotten after a gap
+        for _ in 0..MAX_GAP {
+            tracker.push_signal(&[0.; 1024]);
+        }
+        assert!(tracker.reference().is_none());
+    }
+}
@@ -0,0 +1,225 @@
+use super::fft::{has_conjugate, FFTSequence};
+use super::window::Window;
+use super::{Decibels, Hz};
+use crate::stream::buffer::{ChannelPeriod, PeriodBuffer, SampleBuffer};
+use crate::stream::{ChannelCount, Frame, SampleRate};
+
+/// A one-sided power spectral density estimate, from DC to nyquist
+#[derive(Clone, Debug, PartialEq)]
+pub struct PowerSpectrum {
+    /// Power spectral density, in FS²/Hz (i.e. V²/Hz, if full scale is 1 V)
+    pub values: Vec<f32>,
+    sample_rate: SampleRate,
+    /// The length of the periods the estimate was computed from
+    period_len: usize,
+}
+
+impl PowerSpectrum {
+    pub fn frequencies(&self) -> Box<dyn Iterator<Item = Hz> + '_> {
+        Box::new((0..self.values.len()).map(|i| Hz(i as f32 * f32::from(self.bin_width()))))
+    }
+
+    /// The frequency resolution of the estimate
+    pub fn bin_width(&self) -> Hz {
+        Hz(f32::from(self.sample_rate) / self.period_len as f32)
+    }
+
+    pub fn nyquist_frequency(&self) -> Hz {
+        Hz(f32::from(self.sample_rate) / 2.0)
+    }
+
+    /// Power spectral density in dBFS/Hz, i.e. relative to a power of 1 FS²
+    pub fn to_db(&self) -> Vec<f32> {
+        self.values
+            .iter()
+            .map(|p| Decibels::from_power_ratio(*p).into())
+            .collect()
+    }
+
+    /// The total power of the signal (i.e. its mean square), which is the
+    /// integral of the power spectral density
+    pub fn total_power(&self) -> f32 {
+        self.values.iter().sum::<f32>() * f32::from(self.bin_width())
+    }
+}
+
+/// Estimates power spectral density with Welch's method, i.e. by averaging
+/// the periodograms of (typically overlapping) windowed periods, which gives
+/// a much less noisy estimate than a single FFT does.
+pub struct Welch {
+    window: Vec<f32>,
+    /// Normalization for the window's effect on power
+    window_power: f32,
+    fft: FFTSequence,
+    /// Sum of the periodograms pushed so far
+    sum: Vec<f32>,
+    count: usize,
+    sample_rate: Option<SampleRate>,
+}
+
+impl Welch {
+    pub fn new(window: Window, period_len: usize) -> Welch {
+        let window = window.coefficients(period_len);
+        Welch {
+            window_power: window.iter().map(|w| w * w).sum(),
+            window,
+            fft: FFTSequence::new(period_len),
+            sum: vec![0.; period_len / 2 + 1],
+            count: 0,
+            sample_rate: None,
+        }
+    }
+
+    /// Add a period's periodogram to the average
+    pub fn push(&mut self, period: &ChannelPeriod) {
+        let n = self.window.len();
+        let fs = f32::from(period.sample_rate());
+        let fft = self.fft.folded_windowed(period, &self.window);
+        for (i, (s, (r, _p))) in self.sum.iter_mut().zip(fft.values).enumerate() {
+            // Undo FoldedFFT's amplitude normalization to get |X|, and then
+            // scale |X|^2 to be a density. Bins that have had their conjugate
+            // folded into them have double the power, i.e. half of r^2.
+            let fold = if has_conjugate(i, n) { 0.5 } else { 1. };
+            *s += fold * r * r * (n * n) as f32 / (fs * self.window_power);
+        }
+        self.count += 1;
+        self.sample_rate = Some(period.sample_rate());
+    }
+
+    /// The number of periods in the current estimate
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// The estimate from all periods pushed so far, if any
+    pub fn estimate(&self) -> Option<PowerSpectrum> {
+        Some(PowerSpectrum {
+            values: self.sum.iter().map(|s| s / self.count as f32).collect(),
+            sample_rate: self.sample_rate?,
+            period_len: self.window.len(),
+        })
+    }
+
+    /// Get the current estimate, and start a new one
+    pub fn take(&mut self) -> Option<PowerSpectrum> {
+        let res = self.estimate();
+        self.sum.iter_mut().for_each(|s| *s = 0.);
+        self.count = 0;
+        self.sample_rate = None;
+        res
+    }
+}
+
+/// Estimate the power spectral density of a (single channel) signal, from
+/// periods of period_len, advancing by hop (i.e. overlapping if hop is less
+/// than period_len).
+pub fn welch(
+    signal: &[f32],
+    sample_rate: SampleRate,
+    window: Window,
+    period_len: usize,
+    hop: usize,
+) -> Option<PowerSpectrum> {
+    let mut periods = PeriodBuffer::new(
+        SampleBuffer::new(ChannelCount::new(1), sample_rate, signal.len()),
+        period_len,
+        hop,
+    );
+    periods.push(&Frame {
+        channels: ChannelCount::new(1),
+        sample_rate,
+        samples: Vec::from(signal),
+    });
+    let mut welch = Welch::new(window, period_len);
+    while let Some(p) = periods.next() {
+        welch.push(&p.get_channel(0));
+    }
+    welch.estimate()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::synth::SinIterator;
+
+    #[test]
+    fn sinusoid_power() {
+        // A sinusoid of amplitude A has power A^2 / 2
+        let signal: Vec<f32> = SinIterator::new(SampleRate::new(1000), 125., 0.)
+            .map(|y| 0.5 * y)
+            .take(4000)
+            .collect();
+        let psd = welch(&signal, SampleRate::new(1000), Window::Hann, 256, 128).unwrap();
+        assert_eq!(psd.values.len(), 129);
+        assert_eq!(psd.bin_width(), Hz(1000. / 256.));
+        assert_abs_diff_eq!(psd.total_power(), 0.125, epsilon = 1e-3);
+
+        // And that power is concentrated at its frequency:
+        let peak = psd
+            .values
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .unwrap()
+            .0;
+        assert_eq!(peak, 32);
+    }
+
+    #[test]
+    fn dc_power() {
+        let psd = welch(
+            &[0.5; 1000],
+            SampleRate::new(1000),
+            Window::Hamming,
+            100,
+            50,
+        )
+        .unwrap();
+        assert_abs_diff_eq!(psd.total_power(), 0.25, epsilon = 1e-4);
+    }
+
+    #[test]
+    fn noise_density() {
+        // Uniform noise on -1..1 has power 1/3, spread evenly from DC to
+        // nyquist, so its density is (1/3) / 500 Hz.
+        let mut state = 12345u32;
+        let noise: Vec<f32> = (0..100_000)
+            .map(|_| {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                (state >> 8) as f32 / (1u32 << 23) as f32 - 1.
+            })
+            .collect();
+        let psd = welch(&noise, SampleRate::new(1000), Window::Hann, 128, 64).unwrap();
+        let expect = 1. / 3. / 500.;
+        // (excluding DC and nyquist, which are half as dense)
+        for p in &psd.values[1..psd.values.len() - 1] {
+            assert_relative_eq!(*p, expect, max_relative = 0.2);
+        }
+        assert_relative_eq!(psd.total_power(), 1. / 3., max_relative = 0.02);
+        assert_abs_diff_eq!(psd.to_db()[10], 10. * expect.log10(), epsilon = 1.);
+    }
+
+    #[test]
+    fn take_resets() {
+        let signal = vec![0.5; 100];
+        let mut periods = PeriodBuffer::new(
+            SampleBuffer::new(ChannelCount::new(1), SampleRate::new(100), 100),
+            10,
+            10,
+        );
+        periods.push(&Frame {
+            channels: ChannelCount::new(1),
+            sample_rate: SampleRate::new(100),
+            samples: signal,
+        });
+        let mut welch = Welch::new(Window::Rectangular, 10);
+        assert!(welch.estimate().is_none());
+        welch.push(&periods.next().unwrap().get_channel(0));
+        welch.push(&periods.next().unwrap().get_channel(0));
+        assert_eq!(welch.count(), 2);
+        assert!(welch.take().is_some());
+        assert_eq!(welch.count(), 0);
+        assert!(welch.take().is_none());
+    }
+}
@@ -0,0 +1,129 @@
+use std::io;
+use std::io::{BufRead, Write};
+
+use super::fft::FoldedFFT;
+use super::Hz;
+
+/// A spectrum captured to compare live spectra against, e.g. before and
+/// after an EQ change, or one mic against another
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReferenceTrace {
+    pub name: String,
+    /// In increasing order
+    pub frequencies: Vec<Hz>,
+    /// Amplitude (FS) at each frequency
+    pub values: Vec<f32>,
+}
+
+impl ReferenceTrace {
+    /// Capture the amplitudes of a (e.g. averaged) spectrum
+    pub fn from_fft(name: String, fft: &FoldedFFT) -> ReferenceTrace {
+        ReferenceTrace {
+            name,
+            frequencies: fft.frequencies().collect(),
+            values: fft.values.iter().map(|(r, _)| *r).collect(),
+        }
+    }
+
+    /// The trace's amplitude at a frequency, interpolated between the
+    /// nearest frequencies either side, or None outside the trace
+    pub fn value_at(&self, frequency: Hz) -> Option<f32> {
+        let i = self.frequencies.partition_point(|f| f.0 < frequency.0);
+        let hi = *self.frequencies.get(i)?;
+        if hi.0 == frequency.0 {
+            return Some(self.values[i]);
+        }
+        let lo = self.frequencies[i.checked_sub(1)?];
+        let t = (frequency.0 - lo.0) / (hi.0 - lo.0);
+        Some(self.values[i - 1] * (1. - t) + self.values[i] * t)
+    }
+
+    /// Write the trace as CSV, with a frequency,amplitude row per point
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "frequency,amplitude")?;
+        for (f, value) in self.frequencies.iter().zip(&self.values) {
+            writeln!(writer, "{},{}", f.0, value)?;
+        }
+        writer.flush()
+    }
+
+    /// Read a trace written by write_csv
+    pub fn read_csv<R: BufRead>(name: String, reader: R) -> io::Result<ReferenceTrace> {
+        let invalid = |line: usize| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("expected frequency,amplitude on line {}", line + 1),
+            )
+        };
+        let mut trace = ReferenceTrace {
+            name,
+            frequencies: Vec::new(),
+            values: Vec::new(),
+        };
+        // The first line is the header
+        for (i, line) in reader.lines().enumerate().skip(1) {
+            let line = line?;
+            let (frequency, value) = line.split_once(',').ok_or_else(|| invalid(i))?;
+            let frequency: f32 = frequency.trim().parse().map_err(|_| invalid(i))?;
+            let value: f32 = value.trim().parse().map_err(|_| invalid(i))?;
+            if trace.frequencies.last().is_some_and(|f| f.0 >= frequency) {
+                return Err(invalid(i));
+            }
+            trace.frequencies.push(Hz(frequency));
+            trace.values.push(value);
+        }
+        Ok(trace)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dsp::fft::CartesianFFT;
+    use crate::stream::SampleRate;
+
+    fn trace() -> ReferenceTrace {
+        ReferenceTrace {
+            name: "mic".to_string(),
+            frequencies: vec![Hz(0.), Hz(100.), Hz(200.)],
+            values: vec![0., 1., 0.5],
+        }
+    }
+
+    #[test]
+    fn from_fft() {
+        let fft = CartesianFFT::from_real_signal(vec![0.5; 8], SampleRate::new(8))
+            .into_polar()
+            .into_folded();
+        let trace = ReferenceTrace::from_fft("dc".to_string(), &fft);
+        assert_eq!(trace.frequencies.len(), fft.values.len());
+        assert_eq!(trace.frequencies[1], Hz(1.));
+        assert_eq!(trace.values[0], fft.values[0].0);
+    }
+
+    #[test]
+    fn interpolation() {
+        let trace = trace();
+        assert_eq!(trace.value_at(Hz(100.)), Some(1.));
+        assert_relative_eq!(trace.value_at(Hz(50.)).unwrap(), 0.5);
+        assert_relative_eq!(trace.value_at(Hz(175.)).unwrap(), 0.625);
+        assert_eq!(trace.value_at(Hz(0.)), Some(0.));
+        assert_eq!(trace.value_at(Hz(-1.)), None);
+        assert_eq!(trace.value_at(Hz(201.)), None);
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let trace = trace();
+        let mut csv = Vec::new();
+        trace.write_csv(&mut csv).unwrap();
+        let read = ReferenceTrace::read_csv("mic".to_string(), csv.as_slice()).unwrap();
+        assert_eq!(read, trace);
+
+        let bad = "frequency,amplitude\n0,1\n100\n";
+        let error = ReferenceTrace::read_csv("bad".to_string(), bad.as_bytes()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("line 3"));
+    }
+}
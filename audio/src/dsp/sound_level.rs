@@ -0,0 +1,542 @@
+use std::f64::consts::PI;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+use super::filter::LTI;
+use super::{Level, Reference};
+use crate::stream::buffer::ChannelPeriod;
+use crate::stream::pipeline::Step;
+use crate::stream::SampleRate;
+
+/// The poles (Hz) of the analog A and C weightings, as in IEC 61672-1
+const F1: f64 = 20.598997;
+const F2: f64 = 107.65265;
+const F3: f64 = 737.86223;
+const F4: f64 = 12194.217;
+
+/// Weightings are normalized to 0 dB at this frequency (Hz)
+const REFERENCE_FREQUENCY: f64 = 1000.;
+
+/// The time constant of Impulse time weighting's fast rise
+const IMPULSE_RISE: f32 = 0.035;
+
+/// The time constant of Impulse time weighting's slow decay
+const IMPULSE_DECAY: f32 = 1.5;
+
+/// How often (seconds) the time-weighted level is sampled for statistics
+const STATISTICS_SECS: f32 = 0.01;
+
+/// The range (dBFS) of the histogram of sampled levels that statistics are
+/// read from, and the width of its bins (levels outside it are counted in
+/// the end bins)
+const STATISTICS_MIN_DB: f32 = -140.;
+const STATISTICS_MAX_DB: f32 = 20.;
+const STATISTICS_BIN_DB: f32 = 0.1;
+
+/// How a sound level meter weights frequencies, roughly modelling the ear's
+/// sensitivity at different loudnesses
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrequencyWeighting {
+    /// Quiet sounds: most of the bass is discounted (dBA)
+    A,
+    /// Loud sounds: only the extremes of the audible range are discounted
+    C,
+    /// Zero, i.e. unweighted
+    Z,
+}
+
+impl FrequencyWeighting {
+    pub const ALL: [FrequencyWeighting; 3] = [
+        FrequencyWeighting::A,
+        FrequencyWeighting::C,
+        FrequencyWeighting::Z,
+    ];
+}
+
+impl Display for FrequencyWeighting {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        f.write_str(match self {
+            FrequencyWeighting::A => "A",
+            FrequencyWeighting::C => "C",
+            FrequencyWeighting::Z => "Z",
+        })
+    }
+}
+
+/// How quickly a sound level meter's level follows the signal
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeWeighting {
+    /// A time constant of 125 ms
+    Fast,
+    /// A time constant of 1 s
+    Slow,
+    /// A rise time constant of 35 ms, and a decay time constant of 1.5 s,
+    /// which holds short impulsive sounds for long enough to read
+    Impulse,
+}
+
+impl TimeWeighting {
+    pub const ALL: [TimeWeighting; 3] = [
+        TimeWeighting::Fast,
+        TimeWeighting::Slow,
+        TimeWeighting::Impulse,
+    ];
+
+    /// The time constant (seconds) of the exponential average of power
+    fn time_constant(self) -> f32 {
+        match self {
+            TimeWeighting::Fast => 0.125,
+            TimeWeighting::Slow => 1.,
+            TimeWeighting::Impulse => IMPULSE_RISE,
+        }
+    }
+}
+
+impl Display for TimeWeighting {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        f.write_str(match self {
+            TimeWeighting::Fast => "Fast",
+            TimeWeighting::Slow => "Slow",
+            TimeWeighting::Impulse => "Impulse",
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SoundLevelOptions {
+    pub frequency_weighting: FrequencyWeighting,
+    pub time_weighting: TimeWeighting,
+    /// The duration of each Leq measurement
+    pub interval: Duration,
+    /// The level of a full scale signal (i.e. of 0 dBFS), e.g. in dB SPL for
+    /// a calibrated microphone. Levels are in dBFS until calibrated.
+    pub full_scale: Level,
+}
+
+impl Default for SoundLevelOptions {
+    fn default() -> SoundLevelOptions {
+        SoundLevelOptions {
+            frequency_weighting: FrequencyWeighting::A,
+            time_weighting: TimeWeighting::Fast,
+            interval: Duration::from_secs(1),
+            full_scale: Level::new(0., Reference::FullScale),
+        }
+    }
+}
+
+impl SoundLevelOptions {
+    /// Calibrate from a known level, e.g. a 94 dB SPL calibrator that
+    /// measures (unweighted) -30 dBFS makes full scale 124 dB SPL
+    pub fn calibrate(&mut self, measured: Level, actual: Level) {
+        self.full_scale = actual + (Level::new(0., Reference::FullScale) - measured);
+    }
+}
+
+/// The measurements of a SoundLevelMeter, in the reference of its
+/// calibration. Measurements that need more audio than has been metered are
+/// None, and silence is -inf dB.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SoundLevel {
+    /// The frequency and time weighted level, e.g. LAF
+    pub level: Level,
+    /// The highest time weighted level since the meter was reset
+    pub max: Level,
+    /// The equivalent continuous level (i.e. of the mean power, with
+    /// frequency weighting) over the latest complete interval
+    pub interval_leq: Option<Level>,
+    /// The equivalent continuous level since the meter was reset
+    pub leq: Option<Level>,
+    /// The time weighted level exceeded 10% of the time since the meter
+    /// was reset, i.e. typical peaks
+    pub l10: Option<Level>,
+    /// The time weighted level exceeded 90% of the time since the meter
+    /// was reset, i.e. the background level
+    pub l90: Option<Level>,
+}
+
+/// A frequency weighting: a cascade of first order sections, which are the
+/// bilinear transforms of the analog weighting's poles (so the response
+/// droops slightly below the standard's close to nyquist)
+struct WeightingFilter {
+    sections: Vec<LTI>,
+}
+
+impl WeightingFilter {
+    fn new(weighting: FrequencyWeighting, sample_rate: SampleRate) -> WeightingFilter {
+        let fs = f64::from(u32::from(sample_rate));
+        // Each high pass pole also has a zero at s = 0
+        let (high_passes, low_passes): (&[f64], &[f64]) = match weighting {
+            FrequencyWeighting::A => (&[F1, F1, F2, F3], &[F4, F4]),
+            FrequencyWeighting::C => (&[F1, F1], &[F4, F4]),
+            FrequencyWeighting::Z => (&[], &[]),
+        };
+        let k = 2. * fs;
+        let mut sections: Vec<(f64, [f64; 2])> = high_passes
+            .iter()
+            .map(|f| {
+                let w = 2. * PI * f;
+                ((w - k) / (k + w), [k / (k + w), -k / (k + w)])
+            })
+            .chain(low_passes.iter().map(|f| {
+                let w = 2. * PI * f;
+                ((w - k) / (k + w), [w / (k + w), w / (k + w)])
+            }))
+            .collect();
+
+        // Normalize the gain at the reference frequency
+        let w = 2. * PI * REFERENCE_FREQUENCY / fs;
+        let gain: f64 = sections
+            .iter()
+            .map(|(a, b)| {
+                let magnitude = |c0: f64, c1: f64| (c0 + c1 * w.cos()).hypot(c1 * w.sin());
+                magnitude(b[0], b[1]) / magnitude(1., *a)
+            })
+            .product();
+        if let Some((_, b)) = sections.first_mut() {
+            *b = b.map(|c| c / gain);
+        }
+
+        WeightingFilter {
+            sections: sections
+                .into_iter()
+                .map(|(a, b)| LTI::new(vec![1., a as f32], b.map(|c| c as f32).to_vec()))
+                .collect(),
+        }
+    }
+
+    fn filter(&mut self, x: f32) -> f32 {
+        self.sections.iter_mut().fold(x, |x, section| {
+            section.push_input(x);
+            section.pop_output().expect("LTI outputs each input")
+        })
+    }
+}
+
+/// A sound level meter (of one channel), as specified by IEC 61672-1: the
+/// power of the frequency weighted signal is averaged exponentially with
+/// the time weighting's time constant, and linearly over each Leq interval.
+///
+/// L10 and L90 are read from a histogram of the level every 10 ms since it
+/// was reset, so they're accurate to a bin (0.1 dB) however long it runs.
+pub struct SoundLevelMeter {
+    sample_rate: SampleRate,
+    options: SoundLevelOptions,
+    filter: WeightingFilter,
+    /// The per-sample smoothing coefficient of the time weighting, and of
+    /// Impulse's decay
+    alpha: f64,
+    decay: f64,
+    /// The time weighted power (and for Impulse, before the decay)
+    power: f64,
+    held_power: f64,
+    max_power: f64,
+    /// The frequency weighted sum of squares so far in the current interval
+    interval_sum: f64,
+    interval_count: usize,
+    interval_len: usize,
+    interval_power: Option<f64>,
+    /// ...and since the meter was reset
+    sum: f64,
+    count: usize,
+    statistics_len: usize,
+    /// Counts of the time weighted level (dBFS, so that recalibrating
+    /// doesn't invalidate them) at every statistics_len samples, by bin
+    histogram: Vec<usize>,
+}
+
+impl SoundLevelMeter {
+    pub fn new(sample_rate: SampleRate, options: &SoundLevelOptions) -> SoundLevelMeter {
+        let fs = f32::from(sample_rate);
+        let smoothing = |time_constant: f32| 1. - f64::from((-1. / (time_constant * fs)).exp());
+        SoundLevelMeter {
+            sample_rate,
+            options: *options,
+            filter: WeightingFilter::new(options.frequency_weighting, sample_rate),
+            alpha: smoothing(options.time_weighting.time_constant()),
+            decay: smoothing(IMPULSE_DECAY),
+            power: 0.,
+            held_power: 0.,
+            max_power: 0.,
+            interval_sum: 0.,
+            interval_count: 0,
+            interval_len: ((options.interval.as_secs_f32() * fs).round() as usize).max(1),
+            interval_power: None,
+            sum: 0.,
+            count: 0,
+            statistics_len: ((STATISTICS_SECS * fs).round() as usize).max(1),
+            histogram: vec![0; statistics_bin(STATISTICS_MAX_DB) + 1],
+        }
+    }
+
+    pub fn options(&self) -> &SoundLevelOptions {
+        &self.options
+    }
+
+    /// Change how levels are measured, which restarts metering (except when
+    /// only the calibration changes)
+    pub fn set_options(&mut self, options: &SoundLevelOptions) {
+        let recalibrated = SoundLevelOptions {
+            full_scale: options.full_scale,
+            ..self.options
+        };
+        if recalibrated == *options {
+            self.options.full_scale = options.full_scale;
+        } else {
+            *self = SoundLevelMeter::new(self.sample_rate, options);
+        }
+    }
+
+    /// Meter the samples of a period, which should follow on from the last
+    pub fn push(&mut self, period: &ChannelPeriod) {
+        for x in period.iter() {
+            let y = f64::from(self.filter.filter(*x));
+            let squared = y * y;
+
+            self.power += self.alpha * (squared - self.power);
+            if self.options.time_weighting == TimeWeighting::Impulse {
+                self.held_power += self.decay * (self.power.min(self.held_power) - self.held_power);
+                self.held_power = self.held_power.max(self.power);
+            } else {
+                self.held_power = self.power;
+            }
+            self.max_power = self.max_power.max(self.held_power);
+
+            self.interval_sum += squared;
+            self.interval_count += 1;
+            if self.interval_count == self.interval_len {
+                self.interval_power = Some(self.interval_sum / self.interval_len as f64);
+                self.sum += self.interval_sum;
+                self.count += self.interval_count;
+                self.interval_sum = 0.;
+                self.interval_count = 0;
+            }
+
+            if (self.count + self.interval_count).is_multiple_of(self.statistics_len) {
+                let dbfs = Level::from_power(self.held_power as f32, Reference::FullScale);
+                self.histogram[statistics_bin(f32::from(dbfs))] += 1;
+            }
+        }
+    }
+
+    /// The calibrated level of a (full scale) power
+    fn level(&self, power: f64) -> Level {
+        Level::from_power(power as f32, Reference::FullScale).calibrate(self.options.full_scale)
+    }
+
+    pub fn sound_level(&self) -> SoundLevel {
+        let samples: usize = self.histogram.iter().sum();
+        // The level exceeded the given fraction of the time, i.e. the middle
+        // of the bin that the sample of that rank (from the quietest) is in
+        let exceeded = |p: f32| {
+            let rank = (samples.checked_sub(1)? as f32 * (1. - p)).round() as usize;
+            let mut below = 0;
+            let bin = self.histogram.iter().position(|count| {
+                below += count;
+                below > rank
+            })?;
+            let dbfs = STATISTICS_MIN_DB + (bin as f32 + 0.5) * STATISTICS_BIN_DB;
+            Some(Level::new(dbfs, Reference::FullScale).calibrate(self.options.full_scale))
+        };
+        let count = self.count + self.interval_count;
+        SoundLevel {
+            level: self.level(self.held_power),
+            max: self.level(self.max_power),
+            interval_leq: self.interval_power.map(|p| self.level(p)),
+            leq: (count > 0).then(|| self.level((self.sum + self.interval_sum) / count as f64)),
+            l10: exceeded(0.1),
+            l90: exceeded(0.9),
+        }
+    }
+
+    /// Start metering afresh
+    pub fn reset(&mut self) {
+        *self = SoundLevelMeter::new(self.sample_rate, &self.options);
+    }
+}
+
+/// The histogram bin of a level (dBFS)
+fn statistics_bin(dbfs: f32) -> usize {
+    let bin = ((dbfs - STATISTICS_MIN_DB) / STATISTICS_BIN_DB).floor();
+    let max = ((STATISTICS_MAX_DB - STATISTICS_MIN_DB) / STATISTICS_BIN_DB).round();
+    // NaN (and -inf, i.e. silence) is counted in the bottom bin
+    if bin >= 0. {
+        bin.min(max) as usize
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::f32::consts::PI;
+
+    use crate::dsp::Decibels;
+    use crate::stream::buffer::{PeriodBuffer, SampleBuffer};
+    use crate::stream::{ChannelCount, Frame};
+
+    const FS: u32 = 48000;
+
+    /// A sine wave with the given RMS level (dBFS)
+    fn sine(frequency: f32, dbfs: f32, secs: f32) -> Vec<f32> {
+        let amplitude = Decibels::new(dbfs + 3.0103).amplitude_ratio();
+        (0..(secs * FS as f32) as usize)
+            .map(|i| amplitude * (2. * PI * frequency * i as f32 / FS as f32).sin())
+            .collect()
+    }
+
+    /// Meter a signal, in 100 ms periods from a SampleBuffer
+    fn meter(meter: &mut SoundLevelMeter, signal: &[f32]) {
+        let len = FS as usize / 10;
+        let mut periods = PeriodBuffer::new(
+            SampleBuffer::new(ChannelCount::new(1), SampleRate::new(FS), 2 * len),
+            len,
+            len,
+        );
+        for chunk in signal.chunks(len) {
+            periods.push(&Frame {
+                channels: ChannelCount::new(1),
+                sample_rate: SampleRate::new(FS),
+                samples: chunk.to_vec(),
+            });
+            while let Some(p) = periods.next() {
+                meter.push(&p.get_channel(0));
+            }
+        }
+    }
+
+    #[test]
+    fn frequency_weightings() {
+        // From the tables of IEC 61672-1 (bilinear transforms droop towards
+        // nyquist, so only up to 4 kHz are compared)
+        let responses = [
+            (31.5, -39.4, -3.0),
+            (63., -26.2, -0.8),
+            (125., -16.1, -0.2),
+            (250., -8.6, 0.),
+            (500., -3.2, 0.),
+            (1000., 0., 0.),
+            (2000., 1.2, -0.2),
+            (4000., 1.0, -0.8),
+        ];
+        for (frequency, a, c) in responses {
+            let signal = sine(frequency, -20., 2.);
+            for (weighting, expected) in [
+                (FrequencyWeighting::A, a),
+                (FrequencyWeighting::C, c),
+                (FrequencyWeighting::Z, 0.),
+            ] {
+                // The second interval, after the filters' startup transients
+                let options = SoundLevelOptions {
+                    frequency_weighting: weighting,
+                    ..SoundLevelOptions::default()
+                };
+                let mut m = SoundLevelMeter::new(SampleRate::new(FS), &options);
+                meter(&mut m, &signal);
+                let level = f32::from(m.sound_level().interval_leq.unwrap());
+                assert_abs_diff_eq!(level, -20. + expected, epsilon = 0.2);
+            }
+        }
+    }
+
+    #[test]
+    fn time_weightings() {
+        // While steady, levels match Leq; after the signal stops, they decay
+        // at 34.7, 4.3 and 2.9 dB/s
+        let mut signal = sine(1000., -20., 5.);
+        signal.extend(vec![0.; FS as usize / 2]);
+        for (weighting, decay) in [
+            (TimeWeighting::Fast, 34.7),
+            (TimeWeighting::Slow, 4.3),
+            (TimeWeighting::Impulse, 2.9),
+        ] {
+            let options = SoundLevelOptions {
+                time_weighting: weighting,
+                ..SoundLevelOptions::default()
+            };
+            let mut m = SoundLevelMeter::new(SampleRate::new(FS), &options);
+            meter(&mut m, &signal[..5 * FS as usize]);
+            let level = m.sound_level();
+            assert_abs_diff_eq!(f32::from(level.level), -20., epsilon = 0.2);
+            assert_abs_diff_eq!(f32::from(level.max), -20., epsilon = 0.2);
+            meter(&mut m, &signal[5 * FS as usize..]);
+            let decayed = f32::from(m.sound_level().level);
+            assert_abs_diff_eq!(decayed, -20. - decay / 2., epsilon = 0.2);
+        }
+    }
+
+    #[test]
+    fn leq_and_statistics() {
+        // 8 s at -40 dB and 2 s at -20 dB (which dominates the mean power)
+        let mut signal = sine(1000., -40., 8.);
+        signal.extend(sine(1000., -20., 2.));
+        let options = SoundLevelOptions {
+            interval: Duration::from_secs(2),
+            ..SoundLevelOptions::default()
+        };
+        let mut m = SoundLevelMeter::new(SampleRate::new(FS), &options);
+        meter(&mut m, &signal);
+        let level = m.sound_level();
+        assert_abs_diff_eq!(f32::from(level.interval_leq.unwrap()), -20., epsilon = 0.1);
+        let expected = f32::from(Level::from_power(
+            (0.8 * Decibels::new(-40.).power_ratio()) + (0.2 * Decibels::new(-20.).power_ratio()),
+            Reference::FullScale,
+        ));
+        assert_abs_diff_eq!(f32::from(level.leq.unwrap()), expected, epsilon = 0.1);
+        assert_abs_diff_eq!(f32::from(level.l90.unwrap()), -40., epsilon = 0.2);
+        assert_abs_diff_eq!(f32::from(level.l10.unwrap()), -20., epsilon = 0.5);
+
+        m.reset();
+        let level = m.sound_level();
+        assert_eq!(level.leq, None);
+        assert_eq!(level.l10, None);
+    }
+
+    #[test]
+    fn calibration() {
+        // A 94 dB SPL calibrator, which measures -30 dBFS
+        let mut options = SoundLevelOptions::default();
+        options.calibrate(
+            Level::new(-30., Reference::FullScale),
+            Level::new(94., Reference::SPL),
+        );
+        assert_eq!(options.full_scale, Level::new(124., Reference::SPL));
+
+        let mut m = SoundLevelMeter::new(SampleRate::new(FS), &options);
+        meter(&mut m, &sine(1000., -30., 2.));
+        let level = m.sound_level().leq.unwrap();
+        assert_eq!(level.reference(), Reference::SPL);
+        assert_abs_diff_eq!(f32::from(level), 94., epsilon = 0.1);
+
+        // Recalibrating doesn't restart metering
+        options.full_scale = Level::new(120., Reference::SPL);
+        m.set_options(&options);
+        let level = m.sound_level();
+        assert_abs_diff_eq!(f32::from(level.leq.unwrap()), 90., epsilon = 0.1);
+        assert_abs_diff_eq!(f32::from(level.l10.unwrap()), 90., epsilon = 0.2);
+    }
+
+    #[test]
+    fn low_sample_rate() {
+        // Too low to sample levels every 10 ms, so they're sampled every
+        // sample instead
+        let mut m = SoundLevelMeter::new(SampleRate::new(40), &SoundLevelOptions::default());
+        let mut periods = PeriodBuffer::new(
+            SampleBuffer::new(ChannelCount::new(1), SampleRate::new(40), 10),
+            5,
+            5,
+        );
+        periods.push(&Frame {
+            channels: ChannelCount::new(1),
+            sample_rate: SampleRate::new(40),
+            samples: vec![0.; 5],
+        });
+        while let Some(p) = periods.next() {
+            m.push(&p.get_channel(0));
+        }
+        let l90 = f32::from(m.sound_level().l90.unwrap());
+        assert_abs_diff_eq!(l90, STATISTICS_MIN_DB, epsilon = STATISTICS_BIN_DB);
+    }
+}
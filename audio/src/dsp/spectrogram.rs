@@ -0,0 +1,194 @@
+use std::collections::VecDeque;
+use std::iter::zip;
+use std::time::Duration;
+
+use super::constant_q::ConstantQSpectrum;
+use super::fft::FoldedFFT;
+use super::{Hz, Level};
+use crate::stream::Instant;
+
+/// A bounded history of spectra, i.e. a time x frequency matrix of
+/// magnitudes, in dB relative to full scale.
+pub struct Spectrogram {
+    /// The width of the history
+    max_history: Duration,
+    /// The frequency of each row (which needn't be evenly spaced)
+    frequencies: Vec<Hz>,
+    /// The (end) time of each column
+    times: VecDeque<Instant>,
+    /// Magnitudes (dBFS), by column then row
+    columns: VecDeque<Vec<f32>>,
+}
+
+impl Spectrogram {
+    pub fn new(max_history: Duration) -> Spectrogram {
+        Spectrogram {
+            max_history,
+            frequencies: Vec::new(),
+            times: VecDeque::new(),
+            columns: VecDeque::new(),
+        }
+    }
+
+    /// Add a column for a FFT of a period ending at the given time
+    pub fn push(&mut self, time: Instant, fft: &FoldedFFT) {
+        self.push_column(
+            time,
+            || fft.frequencies(),
+            fft.values.iter().map(|(r, _p)| amplitude_db(*r)).collect(),
+        );
+    }
+
+    /// Add a column for a constant-Q transform of a period ending at the
+    /// given time
+    pub fn push_constant_q(&mut self, time: Instant, spectrum: &ConstantQSpectrum) {
+        self.push_column(
+            time,
+            || spectrum.frequencies.iter().copied(),
+            spectrum.values.iter().map(|r| amplitude_db(*r)).collect(),
+        );
+    }
+
+    fn push_column<F, I>(&mut self, time: Instant, frequencies: F, column: Vec<f32>)
+    where
+        F: Fn() -> I,
+        I: Iterator<Item = Hz>,
+    {
+        if !self.frequencies.iter().copied().eq(frequencies()) {
+            // The transform changed, so the existing history can't be
+            // displayed alongside the new column
+            self.clear();
+            self.frequencies = frequencies().collect();
+        }
+
+        self.times.push_back(time);
+        self.columns.push_back(column);
+
+        // Truncate the beginning of history as it ages out
+        while time - *self.times.front().unwrap() > self.max_history {
+            self.times.pop_front();
+            self.columns.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.times.clear();
+        self.columns.clear();
+    }
+
+    pub fn max_history(&self) -> Duration {
+        self.max_history
+    }
+
+    /// The frequency of each row (i.e. each element of each column)
+    pub fn frequencies(&self) -> &[Hz] {
+        &self.frequencies
+    }
+
+    /// The time of the newest column, if any
+    pub fn latest_time(&self) -> Option<Instant> {
+        self.times.back().copied()
+    }
+
+    /// The (end) time and magnitudes (dBFS) of each column, oldest first
+    pub fn columns(&self) -> impl Iterator<Item = (&Instant, &Vec<f32>)> {
+        zip(self.times.iter(), self.columns.iter())
+    }
+
+    pub fn len(&self) -> usize {
+        self.times.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.times.is_empty()
+    }
+}
+
+fn amplitude_db(amplitude: f32) -> f32 {
+    Level::dbfs(amplitude).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dsp::fft::CartesianFFT;
+    use crate::stream::SampleRate;
+
+    fn fft(signal: Vec<f32>) -> FoldedFFT {
+        CartesianFFT::from_real_signal(signal, SampleRate::new(4))
+            .into_polar()
+            .into_folded()
+    }
+
+    #[test]
+    fn columns() {
+        let mut spec = Spectrogram::new(Duration::from_secs(2));
+        assert!(spec.is_empty());
+        spec.push(
+            Instant::from_sample_num(4, SampleRate::new(4)),
+            &fft(vec![1.; 4]),
+        );
+        spec.push(
+            Instant::from_sample_num(8, SampleRate::new(4)),
+            &fft(vec![0.1, 0., -0.1, 0.]),
+        );
+        assert_eq!(spec.frequencies(), [Hz(0.), Hz(1.), Hz(2.)]);
+
+        let cols: Vec<(f32, Vec<f32>)> = spec
+            .columns()
+            .map(|(t, c)| (f32::from(*t), c.clone()))
+            .collect();
+        assert_eq!(cols.len(), 2);
+        assert_eq!(cols[0].0, 1.);
+        assert_abs_diff_eq!(cols[0].1[0], 0., epsilon = 1e-5);
+        assert!(cols[0].1[1] < -100.);
+        assert_eq!(cols[1].0, 2.);
+        assert_abs_diff_eq!(cols[1].1[1], -20., epsilon = 1e-5);
+    }
+
+    #[test]
+    fn history_ages_out() {
+        let mut spec = Spectrogram::new(Duration::from_secs(2));
+        for i in 1..=5 {
+            spec.push(
+                Instant::from_sample_num(4 * i, SampleRate::new(4)),
+                &fft(vec![1.; 4]),
+            );
+        }
+        assert_eq!(spec.len(), 3);
+        assert_eq!(
+            spec.latest_time(),
+            Some(Instant::from_sample_num(20, SampleRate::new(4)))
+        );
+
+        // Changing the FFT width resets the history
+        spec.push(
+            Instant::from_sample_num(24, SampleRate::new(4)),
+            &fft(vec![1.; 8]),
+        );
+        assert_eq!(spec.len(), 1);
+        assert_eq!(spec.frequencies().len(), 5);
+    }
+
+    #[test]
+    fn constant_q_columns() {
+        let mut spec = Spectrogram::new(Duration::from_secs(2));
+        spec.push(
+            Instant::from_sample_num(4, SampleRate::new(4)),
+            &fft(vec![1.; 4]),
+        );
+        let spectrum = ConstantQSpectrum {
+            frequencies: vec![Hz(0.5), Hz(1.), Hz(2.)],
+            values: vec![1., 0.1, 0.01],
+        };
+        // The same number of rows, but at different frequencies
+        spec.push_constant_q(Instant::from_sample_num(8, SampleRate::new(4)), &spectrum);
+        assert_eq!(spec.len(), 1);
+        spec.push_constant_q(Instant::from_sample_num(12, SampleRate::new(4)), &spectrum);
+        assert_eq!(spec.len(), 2);
+        assert_eq!(spec.frequencies(), spectrum.frequencies);
+        let (_, column) = spec.columns().last().unwrap();
+        assert_abs_diff_eq!(column[2], -40., epsilon = 1e-4);
+    }
+}
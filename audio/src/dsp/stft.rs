@@ -0,0 +1,308 @@
+use std::collections::VecDeque;
+
+use super::fft::{FFTSequence, FoldedFFT};
+use super::window::{overlap_add_constant, Window};
+use crate::stream::buffer::{PeriodBuffer, SampleBuffer};
+use crate::stream::pipeline::Step;
+use crate::stream::{ChannelCount, Frame, SampleRate};
+
+/// Modifies the spectrum of each period of a `STFT`, i.e. a spectral effect
+pub trait SpectralProcessor {
+    /// `channel` is the index of the channel the spectrum was computed from
+    fn process(&mut self, channel: usize, spectrum: &mut FoldedFFT);
+}
+
+impl<F: FnMut(usize, &mut FoldedFFT)> SpectralProcessor for F {
+    fn process(&mut self, channel: usize, spectrum: &mut FoldedFFT) {
+        self(channel, spectrum)
+    }
+}
+
+#[derive(Debug)]
+pub enum STFTError {
+    /// The window doesn't satisfy the constant overlap-add constraint at the
+    /// requested hop, so the signal can't be reconstructed. Since the window
+    /// is applied at both analysis and synthesis, it's the square of the
+    /// window that needs to overlap-add to a constant (e.g. Hann is fine with
+    /// a hop of 1/4 of its length, but not 1/2).
+    NotCOLA,
+    /// The hop is 0, or longer than the window
+    InvalidHop,
+}
+
+/// A `Step` that does streaming STFT -> modify -> ISTFT processing:
+/// - input `Frame`s are split into overlapping periods (advancing by `hop`
+///   samples), which are windowed and transformed,
+/// - the spectrum of each period is modified by a `SpectralProcessor`,
+/// - the results are inverse transformed, windowed again, and overlap-added
+///   back together, and output as `Frame`s of `hop` samples.
+///
+/// With a processor that does nothing, the output is the input delayed by
+/// `latency()` samples.
+pub struct STFT<P: SpectralProcessor> {
+    channels: ChannelCount,
+    sample_rate: SampleRate,
+    window: Vec<f32>,
+    hop: usize,
+    periods: PeriodBuffer,
+    fft: FFTSequence,
+    processor: P,
+    /// By channel, the output samples that are still having periods added
+    /// to them (front / 0 is the oldest)
+    overlap: Vec<VecDeque<f32>>,
+    outputs: VecDeque<Frame>,
+}
+
+impl<P: SpectralProcessor> STFT<P> {
+    pub fn new(
+        channels: ChannelCount,
+        sample_rate: SampleRate,
+        window: Window,
+        len: usize,
+        hop: usize,
+        processor: P,
+    ) -> Result<STFT<P>, STFTError> {
+        if hop == 0 || hop > len {
+            return Err(STFTError::InvalidHop);
+        }
+        let mut window = window.coefficients(len);
+        let squared: Vec<f32> = window.iter().map(|w| w * w).collect();
+        let gain = overlap_add_constant(&squared, hop).ok_or(STFTError::NotCOLA)?;
+        // Fold the overlap-add normalization into the synthesis window, which
+        // is then no longer identical to the analysis window, but only by a
+        // constant factor:
+        let scale = 1. / gain.sqrt();
+        for w in &mut window {
+            *w *= scale;
+        }
+
+        // Enough space for a period and a second of input, which push_input
+        // splits longer Frames into.
+        let mut periods = PeriodBuffer::new(
+            SampleBuffer::new(channels, sample_rate, len + usize::from(sample_rate)),
+            len,
+            hop,
+        );
+        // Pretend the input was preceded by silence, so that the first output
+        // samples have the full complement of overlapping periods:
+        periods.push(&Frame {
+            channels,
+            sample_rate,
+            samples: vec![0.; (len - hop) * usize::from(channels)],
+        });
+
+        Ok(STFT {
+            channels,
+            sample_rate,
+            window,
+            hop,
+            periods,
+            fft: FFTSequence::new(len),
+            processor,
+            overlap: (0..usize::from(channels))
+                .map(|_| VecDeque::from(vec![0.; len]))
+                .collect(),
+            outputs: VecDeque::new(),
+        })
+    }
+
+    /// The number of samples by which the output lags the input
+    pub fn latency(&self) -> usize {
+        self.window.len() - self.hop
+    }
+
+    pub fn processor_mut(&mut self) -> &mut P {
+        &mut self.processor
+    }
+
+    /// Process every complete period in the buffer
+    fn process_periods(&mut self) {
+        while let Some(period) = self.periods.next() {
+            for (i, ch) in period.channels().iter().enumerate() {
+                let mut spectrum = self.fft.folded_windowed(ch, &self.window);
+                self.processor.process(i, &mut spectrum);
+                let signal = self.fft.ifft_folded(spectrum);
+                for (j, y) in signal.into_iter().enumerate() {
+                    self.overlap[i][j] += y * self.window[j];
+                }
+            }
+
+            // The oldest hop samples won't overlap with any further periods,
+            // so they're done:
+            let mut samples = Vec::with_capacity(self.hop * usize::from(self.channels));
+            for _ in 0..self.hop {
+                for ch in &mut self.overlap {
+                    samples.push(ch.pop_front().unwrap());
+                    ch.push_back(0.);
+                }
+            }
+            self.outputs.push_back(Frame {
+                channels: self.channels,
+                sample_rate: self.sample_rate,
+                samples,
+            });
+        }
+    }
+}
+
+impl<P: SpectralProcessor> Step for STFT<P> {
+    type Input = Frame;
+    type Output = Frame;
+
+    fn push_input(&mut self, input: Frame) {
+        // A second at a time, so that the buffer never overflows
+        let chunk_len = usize::from(self.sample_rate) * usize::from(self.channels);
+        for samples in input.samples.chunks(chunk_len) {
+            self.periods.push(&Frame {
+                channels: self.channels,
+                sample_rate: self.sample_rate,
+                samples: samples.to_vec(),
+            });
+            self.process_periods();
+        }
+    }
+
+    fn pop_output(&mut self) -> Option<Frame> {
+        self.outputs.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::synth::SinIterator;
+
+    /// Run a (two channel) signal through a STFT, in Frames of frame_len
+    fn process<P: SpectralProcessor>(
+        stft: &mut STFT<P>,
+        signal: &[f32],
+        frame_len: usize,
+    ) -> Vec<f32> {
+        let mut res = Vec::new();
+        for chunk in signal.chunks(frame_len) {
+            stft.push_input(Frame {
+                channels: ChannelCount::new(2),
+                sample_rate: SampleRate::new(1000),
+                samples: Vec::from(chunk),
+            });
+            while let Some(f) = stft.pop_output() {
+                res.extend(f.samples);
+            }
+        }
+        res
+    }
+
+    fn test_signal(len: usize) -> Vec<f32> {
+        // Interlaced: a sinusoid on channel 0, and a ramp on channel 1
+        SinIterator::new(SampleRate::new(1000), 37., 0.)
+            .take(len)
+            .enumerate()
+            .flat_map(|(i, y)| [y, i as f32 / len as f32])
+            .collect()
+    }
+
+    #[test]
+    fn perfect_reconstruction() {
+        let signal = test_signal(1000);
+        let mut stft = STFT::new(
+            ChannelCount::new(2),
+            SampleRate::new(1000),
+            Window::Hann,
+            64,
+            16,
+            |_: usize, _: &mut FoldedFFT| (),
+        )
+        .unwrap();
+        let output = process(&mut stft, &signal, 50);
+
+        // Output comes in whole hops, so at most a partial hop is missing
+        assert!(output.len() > signal.len() - 2 * 16);
+        // (x2 for interlacing)
+        let latency = 2 * stft.latency();
+        for y in &output[..latency] {
+            assert_abs_diff_eq!(*y, 0., epsilon = 1e-5);
+        }
+        for (y, expect) in output[latency..].iter().zip(signal.iter()) {
+            assert_abs_diff_eq!(y, expect, epsilon = 1e-4);
+        }
+    }
+
+    /// Halve the amplitude of channel 0
+    fn attenuate_left(ch: usize, fft: &mut FoldedFFT) {
+        if ch == 0 {
+            for y in &mut fft.values {
+                y.0 *= 0.5;
+            }
+        }
+    }
+
+    #[test]
+    fn long_frame() {
+        // More than a second (plus a period) in one Frame is processed just
+        // as if it came in smaller Frames
+        let signal = test_signal(3000);
+        let new = || {
+            STFT::new(
+                ChannelCount::new(2),
+                SampleRate::new(1000),
+                Window::Hann,
+                64,
+                16,
+                |_: usize, _: &mut FoldedFFT| (),
+            )
+            .unwrap()
+        };
+        let output = process(&mut new(), &signal, signal.len());
+        assert_eq!(output, process(&mut new(), &signal, 50));
+    }
+
+    #[test]
+    fn not_cola() {
+        // Hann^2 isn't COLA at 50% overlap
+        assert!(STFT::new(
+            ChannelCount::new(2),
+            SampleRate::new(1000),
+            Window::Hann,
+            32,
+            16,
+            attenuate_left,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn invalid_hop() {
+        for hop in [0, 33] {
+            let stft = STFT::new(
+                ChannelCount::new(2),
+                SampleRate::new(1000),
+                Window::Hann,
+                32,
+                hop,
+                attenuate_left,
+            );
+            assert!(matches!(stft, Err(STFTError::InvalidHop)));
+        }
+    }
+
+    #[test]
+    fn spectral_gain() {
+        let signal = test_signal(1000);
+        let mut stft = STFT::new(
+            ChannelCount::new(2),
+            SampleRate::new(1000),
+            Window::Hann,
+            32,
+            8,
+            attenuate_left,
+        )
+        .unwrap();
+        let output = process(&mut stft, &signal, 64);
+        let latency = 2 * stft.latency();
+        let gains = [0.5, 1.].iter().cycle();
+        for ((y, expect), gain) in output[latency..].iter().zip(signal.iter()).zip(gains) {
+            assert_abs_diff_eq!(*y, gain * expect, epsilon = 1e-4);
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use std::fmt;
+
+use super::Hz;
+
+/// Note names, by pitch class (i.e. semitones above C)
+pub const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "Eb", "E", "F", "F#", "G", "G#", "A", "Bb", "B",
+];
+
+/// The MIDI note number of A4
+const A4_NOTE: i32 = 69;
+
+/// The interval from `reference` to `frequency`, in cents
+pub fn cents(frequency: Hz, reference: Hz) -> f32 {
+    1200. * (frequency.0 / reference.0).log2()
+}
+
+/// How the notes of an octave are tuned
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Temperament {
+    /// Twelve equal semitones
+    Equal,
+    /// 5-limit just intonation, relative to the tonic
+    Just,
+    /// Stacked pure fifths, relative to the tonic
+    Pythagorean,
+}
+
+impl Temperament {
+    pub const ALL: [Temperament; 3] = [
+        Temperament::Equal,
+        Temperament::Just,
+        Temperament::Pythagorean,
+    ];
+
+    /// The frequency ratio of each semitone above the tonic
+    fn ratios(&self) -> [f32; 12] {
+        match self {
+            Temperament::Equal => std::array::from_fn(|i| 2f32.powf(i as f32 / 12.)),
+            Temperament::Just => [
+                1.,
+                16. / 15.,
+                9. / 8.,
+                6. / 5.,
+                5. / 4.,
+                4. / 3.,
+                45. / 32.,
+                3. / 2.,
+                8. / 5.,
+                5. / 3.,
+                9. / 5.,
+                15. / 8.,
+            ],
+            Temperament::Pythagorean => [
+                1.,
+                256. / 243.,
+                9. / 8.,
+                32. / 27.,
+                81. / 64.,
+                4. / 3.,
+                729. / 512.,
+                3. / 2.,
+                128. / 81.,
+                27. / 16.,
+                16. / 9.,
+                243. / 128.,
+            ],
+        }
+    }
+}
+
+impl fmt::Display for Temperament {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Temperament::Equal => "Equal",
+            Temperament::Just => "Just",
+            Temperament::Pythagorean => "Pythagorean",
+        })
+    }
+}
+
+/// A note, and how far some frequency is from it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Note {
+    /// Semitones above C
+    pub pitch_class: usize,
+    /// In scientific pitch notation, i.e. middle C is C4
+    pub octave: i32,
+    /// The note's frequency in the tuning
+    pub frequency: Hz,
+    /// How far the measured frequency is above the note
+    pub cents: f32,
+}
+
+impl Note {
+    pub fn name(&self) -> &'static str {
+        NOTE_NAMES[self.pitch_class]
+    }
+}
+
+impl fmt::Display for Note {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.name(), self.octave)
+    }
+}
+
+/// The frequencies of notes, given a reference pitch and temperament
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tuning {
+    /// The frequency of A4 (the tonic is tuned from it in equal temperament)
+    pub a4: Hz,
+    pub temperament: Temperament,
+    /// The pitch class that non-equal temperaments are relative to
+    pub tonic: usize,
+}
+
+impl Default for Tuning {
+    fn default() -> Tuning {
+        Tuning {
+            a4: Hz(440.),
+            temperament: Temperament::Equal,
+            tonic: 0,
+        }
+    }
+}
+
+impl Tuning {
+    /// The frequency of a MIDI note number
+    pub fn frequency(&self, note: i32) -> Hz {
+        let degree = (note - self.tonic as i32).rem_euclid(12);
+        let tonic = note - degree;
+        let tonic_frequency = self.a4.0 * 2f32.powf((tonic - A4_NOTE) as f32 / 12.);
+        Hz(tonic_frequency * self.temperament.ratios()[degree as usize])
+    }
+
+    /// The (fractional) MIDI note number of a frequency, in equal temperament
+    pub fn note_number(&self, frequency: Hz) -> f32 {
+        A4_NOTE as f32 + cents(frequency, self.a4) / 100.
+    }
+
+    /// The note closest to a frequency
+    pub fn nearest(&self, frequency: Hz) -> Note {
+        let guess = self.note_number(frequency).round() as i32;
+        let (note, note_frequency) = (guess - 1..=guess + 1)
+            .map(|n| (n, self.frequency(n)))
+            .min_by(|a, b| {
+                cents(frequency, a.1)
+                    .abs()
+                    .total_cmp(&cents(frequency, b.1).abs())
+            })
+            .unwrap();
+        Note {
+            pitch_class: note.rem_euclid(12) as usize,
+            octave: note.div_euclid(12) - 1,
+            frequency: note_frequency,
+            cents: cents(frequency, note_frequency),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_temperament() {
+        let tuning = Tuning::default();
+        assert_relative_eq!(tuning.frequency(69).0, 440.);
+        assert_relative_eq!(tuning.frequency(60).0, 261.6256, max_relative = 1e-5);
+        assert_relative_eq!(tuning.note_number(Hz(880.)), 81.);
+
+        let note = tuning.nearest(Hz(445.));
+        assert_eq!(note.to_string(), "A4");
+        assert_relative_eq!(note.cents, 19.56, epsilon = 0.01);
+
+        let note = tuning.nearest(Hz(257.));
+        assert_eq!(note.to_string(), "C4");
+        assert!(note.cents < 0.);
+        assert_eq!(tuning.nearest(Hz(250.)).to_string(), "B3");
+    }
+
+    #[test]
+    fn reference_pitch() {
+        let tuning = Tuning {
+            a4: Hz(415.),
+            ..Tuning::default()
+        };
+        let note = tuning.nearest(Hz(415.));
+        assert_eq!(note.to_string(), "A4");
+        assert_abs_diff_eq!(note.cents, 0., epsilon = 1e-3);
+    }
+
+    #[test]
+    fn just_intonation() {
+        // In C, a just E is a 5:4 above C, i.e. ~14 cents flat of equal
+        let just = Tuning {
+            temperament: Temperament::Just,
+            ..Tuning::default()
+        };
+        let c4 = just.frequency(60);
+        assert_relative_eq!(just.frequency(64).0, c4.0 * 1.25);
+        let equal_e = Tuning::default().frequency(64);
+        let note = just.nearest(equal_e);
+        assert_eq!(note.to_string(), "E4");
+        assert_relative_eq!(note.cents, 13.69, epsilon = 0.01);
+
+        // And relative to A, A is unchanged, with E a pure fifth above
+        let just_a = Tuning { tonic: 9, ..just };
+        assert_relative_eq!(just_a.frequency(69).0, 440.);
+        assert_relative_eq!(just_a.frequency(76).0, 660., max_relative = 1e-6);
+    }
+}
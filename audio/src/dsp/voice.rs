@@ -0,0 +1,381 @@
+use super::cepstrum::normalized_autocorrelation;
+use super::fft::FFTSequence;
+use super::pitch::PitchTracker;
+use super::window::Window;
+use super::{Decibels, Hz};
+use crate::stream::buffer::{BufferedInput, ChannelPeriod};
+use crate::stream::input::{Input, InputError};
+use crate::stream::{Frame, SampleRate};
+
+/// Successive cycle peaks are searched for this far (as a fraction of the
+/// fundamental period) either side of one period after the last
+const SEARCH_RANGE: f32 = 0.2;
+
+/// Windows with fewer cycles than this aren't measured
+const MIN_CYCLES: usize = 4;
+
+/// HNR is capped at this (dB), which is reached at an autocorrelation peak
+/// of 1 - 1e-6
+const MAX_HNR: f32 = 60.;
+
+/// Voice quality measures of one analysis window of a sustained vowel
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VoiceQuality {
+    pub f0: Hz,
+    /// The number of glottal cycles the measures are based on
+    pub cycles: usize,
+    /// The mean absolute difference between consecutive periods, as a
+    /// fraction of the mean period (Praat's "jitter (local)", which is in
+    /// percent)
+    pub jitter_local: f32,
+    /// The mean absolute difference between each period and the average of
+    /// it and its neighbours, as a fraction of the mean period (relative
+    /// average perturbation)
+    pub jitter_rap: f32,
+    /// The mean absolute difference between consecutive cycles' peak
+    /// amplitudes, as a fraction of the mean amplitude
+    pub shimmer_local: f32,
+    /// The mean absolute ratio (dB) of consecutive cycles' peak amplitudes
+    pub shimmer_db: f32,
+    /// Harmonics-to-noise ratio (dB), from the normalized autocorrelation at
+    /// the fundamental period
+    pub hnr_db: f32,
+}
+
+/// Voice quality over a session: the means of each measure over voiced
+/// windows (weighted by their number of cycles)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VoiceSummary {
+    pub windows: usize,
+    pub voiced_windows: usize,
+    /// Over all voiced windows, or None if there weren't any
+    pub mean: Option<VoiceQuality>,
+    /// The standard deviation of f0 between voiced windows, weighted by
+    /// their number of cycles like the means (Hz)
+    pub f0_sd: f32,
+}
+
+/// Measures jitter, shimmer and HNR of successive periods of a sustained
+/// vowel, and summarizes them over the session
+pub struct VoiceAnalyzer {
+    pitch: PitchTracker,
+    min_frequency: Hz,
+    max_frequency: Hz,
+    /// For autocorrelation, so twice the period length
+    ffts: FFTSequence,
+    window: Vec<f32>,
+    windows: usize,
+    /// The measures of each voiced window so far
+    history: Vec<VoiceQuality>,
+}
+
+impl VoiceAnalyzer {
+    /// An analyzer for voices (60-1000 Hz) in periods of period_len samples,
+    /// which should span at least a few cycles of the lowest voice expected
+    pub fn new(sample_rate: SampleRate, period_len: usize) -> VoiceAnalyzer {
+        VoiceAnalyzer {
+            pitch: PitchTracker::for_voice(sample_rate),
+            min_frequency: Hz(60.),
+            max_frequency: Hz(1000.),
+            ffts: FFTSequence::new(2 * period_len),
+            window: Window::Hann.coefficients(period_len),
+            windows: 0,
+            history: Vec::new(),
+        }
+    }
+
+    /// Measure the next analysis window, if it's voiced
+    pub fn analyze(&mut self, period: &ChannelPeriod) -> Option<VoiceQuality> {
+        self.windows += 1;
+        let pitch = self.pitch.push(period);
+        if !pitch.voiced {
+            return None;
+        }
+        let signal: Vec<f32> = period.iter().copied().collect();
+        let fs = f32::from(period.sample_rate());
+        let peaks = cycle_peaks(&signal, fs / pitch.frequency.0);
+        if peaks.len() < MIN_CYCLES + 1 {
+            return None;
+        }
+        let (jitter_local, jitter_rap) = jitter(&peaks)?;
+        let (shimmer_local, shimmer_db) = shimmer(&peaks)?;
+
+        let autocorrelation = normalized_autocorrelation(&mut self.ffts, period, &self.window);
+        let (_, r) = autocorrelation.fundamental(self.min_frequency, self.max_frequency)?;
+        let r = r.clamp(0., 1. - 1e-6);
+        let hnr_db = f32::from(Decibels::from_power_ratio(r / (1. - r))).min(MAX_HNR);
+
+        let quality = VoiceQuality {
+            f0: pitch.frequency,
+            cycles: peaks.len() - 1,
+            jitter_local,
+            jitter_rap,
+            shimmer_local,
+            shimmer_db,
+            hnr_db,
+        };
+        self.history.push(quality);
+        Some(quality)
+    }
+
+    /// Measure every period of an input (the first channel), until it ends,
+    /// returning each period's measures
+    pub fn analyze_input<T: Input<Item = Frame>>(
+        &mut self,
+        input: &mut BufferedInput<T>,
+    ) -> Result<Vec<Option<VoiceQuality>>, InputError> {
+        let mut windows = Vec::new();
+        loop {
+            match input.next() {
+                Ok(period) => windows.push(self.analyze(&period.get_channel(0))),
+                Err(InputError::StreamEnded) => return Ok(windows),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    pub fn summary(&self) -> VoiceSummary {
+        let cycles: usize = self.history.iter().map(|q| q.cycles).sum();
+        let mean = |measure: fn(&VoiceQuality) -> f32| {
+            self.history
+                .iter()
+                .map(|q| measure(q) * q.cycles as f32)
+                .sum::<f32>()
+                / cycles as f32
+        };
+        let f0 = mean(|q| q.f0.0);
+        let f0_variance = self
+            .history
+            .iter()
+            .map(|q| (q.f0.0 - f0).powi(2) * q.cycles as f32)
+            .sum::<f32>()
+            / cycles.max(1) as f32;
+        VoiceSummary {
+            windows: self.windows,
+            voiced_windows: self.history.len(),
+            mean: (cycles > 0).then(|| VoiceQuality {
+                f0: Hz(f0),
+                cycles,
+                jitter_local: mean(|q| q.jitter_local),
+                jitter_rap: mean(|q| q.jitter_rap),
+                shimmer_local: mean(|q| q.shimmer_local),
+                shimmer_db: mean(|q| q.shimmer_db),
+                hnr_db: mean(|q| q.hnr_db),
+            }),
+            f0_sd: f0_variance.sqrt(),
+        }
+    }
+
+    /// Start a new session
+    pub fn reset(&mut self) {
+        self.pitch.reset();
+        self.windows = 0;
+        self.history.clear();
+    }
+}
+
+/// The (parabolically interpolated) times (in samples) and amplitudes of the
+/// peak of each cycle of a signal with the given fundamental period, found by
+/// working outwards from the signal's maximum
+fn cycle_peaks(signal: &[f32], period: f32) -> Vec<(f32, f32)> {
+    let Some(start) =
+        (1..signal.len().saturating_sub(1)).max_by(|a, b| signal[*a].total_cmp(&signal[*b]))
+    else {
+        return Vec::new();
+    };
+    let search = |from: usize, direction: f32| -> Option<usize> {
+        let lo = from as f32 + direction * period * (1. - SEARCH_RANGE);
+        let hi = from as f32 + direction * period * (1. + SEARCH_RANGE);
+        let (lo, hi) = (lo.min(hi).floor(), lo.max(hi).ceil());
+        if lo < 1. || hi > (signal.len() - 2) as f32 {
+            return None;
+        }
+        (lo as usize..=hi as usize).max_by(|a, b| signal[*a].total_cmp(&signal[*b]))
+    };
+
+    let mut peaks = vec![start];
+    while let Some(next) = search(*peaks.last().unwrap(), 1.) {
+        peaks.push(next);
+    }
+    let mut earlier = vec![];
+    let mut from = start;
+    while let Some(previous) = search(from, -1.) {
+        earlier.push(previous);
+        from = previous;
+    }
+    earlier.reverse();
+    earlier.extend(peaks);
+
+    earlier
+        .into_iter()
+        .map(|i| {
+            let (a, b, c) = (signal[i - 1], signal[i], signal[i + 1]);
+            let denominator = a - 2. * b + c;
+            if denominator >= 0. {
+                return (i as f32, b);
+            }
+            let offset = 0.5 * (a - c) / denominator;
+            (i as f32 + offset, b - 0.25 * (a - c) * offset)
+        })
+        .collect()
+}
+
+/// Local and RAP jitter of the periods between successive peaks
+fn jitter(peaks: &[(f32, f32)]) -> Option<(f32, f32)> {
+    let periods: Vec<f32> = peaks.windows(2).map(|w| w[1].0 - w[0].0).collect();
+    if periods.len() < 3 {
+        return None;
+    }
+    let mean_period = periods.iter().sum::<f32>() / periods.len() as f32;
+    let local = mean_abs(periods.windows(2).map(|w| w[1] - w[0]));
+    let rap = mean_abs(periods.windows(3).map(|w| w[1] - (w[0] + w[1] + w[2]) / 3.));
+    Some((local / mean_period, rap / mean_period))
+}
+
+/// Local and dB shimmer of successive peaks' amplitudes
+fn shimmer(peaks: &[(f32, f32)]) -> Option<(f32, f32)> {
+    if peaks.iter().any(|(_, a)| *a <= 0.) {
+        return None;
+    }
+    let mean_amplitude = peaks.iter().map(|(_, a)| a).sum::<f32>() / peaks.len() as f32;
+    let local = mean_abs(peaks.windows(2).map(|w| w[1].1 - w[0].1));
+    let db = mean_abs(
+        peaks
+            .windows(2)
+            .map(|w| Decibels::from_amplitude_ratio(w[1].1 / w[0].1).into()),
+    );
+    Some((local / mean_amplitude, db))
+}
+
+fn mean_abs<I: Iterator<Item = f32>>(values: I) -> f32 {
+    let (sum, count) = values.fold((0., 0), |(sum, count), y| (sum + y.abs(), count + 1));
+    sum / count as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::f32::consts::PI;
+
+    use crate::stream::ChannelCount;
+
+    const FS: u32 = 44100;
+    const PERIOD_LEN: usize = 4096;
+
+    /// A sustained vowel-like signal at f0, whose successive cycles alternate
+    /// between being longer and shorter by the jitter fraction, and louder
+    /// and quieter by the shimmer fraction, with additive white noise. Each
+    /// cycle runs from one peak to the next, so the perturbations are exactly
+    /// those between peaks.
+    fn perturbed(f0: f32, jitter: f32, shimmer: f32, noise: f32, len: usize) -> Vec<f32> {
+        let mut state = 12345u32;
+        let mut signal = Vec::with_capacity(len);
+        let (mut t, mut cycle) = (0., 0);
+        let sign = |i: usize| if i.is_multiple_of(2) { 1. } else { -1. };
+        while signal.len() < len {
+            let period = FS as f32 / f0 * (1. + jitter * sign(cycle));
+            let amplitude = |i: usize| 0.5 * (1. + shimmer * sign(i));
+            while t < period && signal.len() < len {
+                // The first half of the cycle falls from this cycle's peak, and
+                // the second half rises to the next's
+                let phase = t / period;
+                let a = amplitude(if phase < 0.5 { cycle } else { cycle + 1 });
+                let harmonic = (2. * PI * phase).cos() + 0.3 * (4. * PI * phase).cos();
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                let n = (state >> 8) as f32 / (1u32 << 23) as f32 - 1.;
+                signal.push(a * harmonic / 1.3 + noise * n);
+                t += 1.;
+            }
+            t -= period;
+            cycle += 1;
+        }
+        signal
+    }
+
+    fn analyze(signal: Vec<f32>) -> (Vec<Option<VoiceQuality>>, VoiceSummary) {
+        let sample_rate = SampleRate::new(FS);
+        let mut input = BufferedInput::from_sample_input(
+            signal.into_iter(),
+            ChannelCount::new(1),
+            sample_rate,
+            PERIOD_LEN,
+        )
+        .unwrap();
+        let mut analyzer = VoiceAnalyzer::new(sample_rate, PERIOD_LEN);
+        let windows = analyzer.analyze_input(&mut input).unwrap();
+        (windows, analyzer.summary())
+    }
+
+    #[test]
+    fn steady_voice() {
+        let (windows, summary) = analyze(perturbed(150., 0., 0., 0., 4 * PERIOD_LEN));
+        assert_eq!(windows.len(), 4);
+        assert_eq!(summary.voiced_windows, 4);
+        let mean = summary.mean.unwrap();
+        assert_relative_eq!(mean.f0.0, 150., max_relative = 0.005);
+        assert!(mean.jitter_local < 0.001);
+        assert!(mean.shimmer_local < 0.001);
+        assert!(mean.hnr_db > 40.);
+    }
+
+    #[test]
+    fn jitter_and_shimmer() {
+        // Alternating periods differ by twice the jitter fraction, and each
+        // differs from the average of it and its neighbours by 4/3 of it
+        let (windows, summary) = analyze(perturbed(150., 0.01, 0.05, 0., 4 * PERIOD_LEN));
+        for window in windows {
+            let window = window.unwrap();
+            assert_relative_eq!(window.jitter_local, 0.02, max_relative = 0.05);
+            assert_relative_eq!(window.jitter_rap, 0.04 / 3., max_relative = 0.05);
+            assert_relative_eq!(window.shimmer_local, 0.1, max_relative = 0.05);
+            assert_relative_eq!(
+                window.shimmer_db,
+                20. * (1.05f32 / 0.95).log10(),
+                max_relative = 0.05
+            );
+        }
+        assert_relative_eq!(
+            summary.mean.unwrap().jitter_local,
+            0.02,
+            max_relative = 0.05
+        );
+    }
+
+    #[test]
+    fn harmonics_to_noise() {
+        // The periodic part's power is 0.5^2 * (1 + 0.3^2) / 2 / 1.3^2, and
+        // uniform noise's is noise^2 / 3
+        let periodic: f32 = 0.25 * 1.09 / 2. / 1.69;
+        let noise = (3. * periodic / 10f32.powf(10. / 10.)).sqrt();
+        let (_, summary) = analyze(perturbed(150., 0., 0., noise, 4 * PERIOD_LEN));
+        assert_abs_diff_eq!(summary.mean.unwrap().hnr_db, 10., epsilon = 1.5);
+    }
+
+    #[test]
+    fn weighted_f0_sd() {
+        let window = |f0: f32, cycles: usize| VoiceQuality {
+            f0: Hz(f0),
+            cycles,
+            jitter_local: 0.,
+            jitter_rap: 0.,
+            shimmer_local: 0.,
+            shimmer_db: 0.,
+            hnr_db: 0.,
+        };
+        let mut analyzer = VoiceAnalyzer::new(SampleRate::new(FS), PERIOD_LEN);
+        analyzer.history = vec![window(100., 10), window(200., 30)];
+        let summary = analyzer.summary();
+        assert_relative_eq!(summary.mean.unwrap().f0.0, 175.);
+        // sqrt((10 * 75^2 + 30 * 25^2) / 40)
+        assert_relative_eq!(summary.f0_sd, 1875f32.sqrt());
+    }
+
+    #[test]
+    fn silence() {
+        let (windows, summary) = analyze(vec![0.; 2 * PERIOD_LEN]);
+        assert!(windows.iter().all(Option::is_none));
+        assert_eq!(summary.windows, 2);
+        assert!(summary.mean.is_none());
+    }
+}
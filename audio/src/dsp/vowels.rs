@@ -0,0 +1,202 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Duration;
+
+use super::lpc::Formant;
+use super::Hz;
+use crate::stream::Instant;
+
+/// A vowel's position in the F1/F2 plane, at some time
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VowelPoint {
+    pub time: Instant,
+    pub f1: Hz,
+    pub f2: Hz,
+}
+
+impl VowelPoint {
+    /// The point for a set of formants, if there are at least two of them
+    pub fn from_formants(time: Instant, formants: &[Formant]) -> Option<VowelPoint> {
+        match formants {
+            [f1, f2, ..] => Some(VowelPoint {
+                time,
+                f1: f1.frequency,
+                f2: f2.frequency,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// The recent history of vowel points, over some duration
+pub struct VowelTrail {
+    max_history: Duration,
+    points: VecDeque<VowelPoint>,
+}
+
+impl VowelTrail {
+    pub fn new(max_history: Duration) -> VowelTrail {
+        VowelTrail {
+            max_history,
+            points: VecDeque::new(),
+        }
+    }
+
+    /// Add the formants estimated for a period ending at the given time.
+    /// Periods with fewer than two formants are skipped, but still age out
+    /// older points.
+    pub fn push(&mut self, time: Instant, formants: &[Formant]) {
+        if let Some(point) = VowelPoint::from_formants(time, formants) {
+            self.points.push_back(point);
+        }
+        while self
+            .points
+            .front()
+            .is_some_and(|p| time - p.time > self.max_history)
+        {
+            self.points.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.points.clear();
+    }
+
+    pub fn max_history(&self) -> Duration {
+        self.max_history
+    }
+
+    /// Oldest first
+    pub fn points(&self) -> impl Iterator<Item = &VowelPoint> {
+        self.points.iter()
+    }
+
+    pub fn latest_time(&self) -> Option<Instant> {
+        self.points.back().map(|p| p.time)
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+}
+
+/// A set of vowel points recorded from one speaker
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpeakerVowels {
+    pub speaker: String,
+    pub points: Vec<VowelPoint>,
+}
+
+/// The average formants of a vowel, for some population of speakers
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReferenceVowel {
+    /// The vowel's IPA symbol
+    pub symbol: &'static str,
+    pub f1: Hz,
+    pub f2: Hz,
+}
+
+const fn reference(symbol: &'static str, f1: f32, f2: f32) -> ReferenceVowel {
+    ReferenceVowel {
+        symbol,
+        f1: Hz(f1),
+        f2: Hz(f2),
+    }
+}
+
+const PETERSON_BARNEY_MEN: [ReferenceVowel; 10] = [
+    reference("i", 270., 2290.),
+    reference("ɪ", 390., 1990.),
+    reference("ɛ", 530., 1840.),
+    reference("æ", 660., 1720.),
+    reference("ɑ", 730., 1090.),
+    reference("ɔ", 570., 840.),
+    reference("ʊ", 440., 1020.),
+    reference("u", 300., 870.),
+    reference("ʌ", 640., 1190.),
+    reference("ɝ", 490., 1350.),
+];
+
+const PETERSON_BARNEY_WOMEN: [ReferenceVowel; 10] = [
+    reference("i", 310., 2790.),
+    reference("ɪ", 430., 2480.),
+    reference("ɛ", 610., 2330.),
+    reference("æ", 860., 2050.),
+    reference("ɑ", 850., 1220.),
+    reference("ɔ", 590., 920.),
+    reference("ʊ", 470., 1160.),
+    reference("u", 370., 950.),
+    reference("ʌ", 760., 1400.),
+    reference("ɝ", 500., 1640.),
+];
+
+/// Sets of reference vowels, i.e. targets to compare a speaker's vowels to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VowelReferences {
+    /// Peterson & Barney (1952), American English, adult men
+    Men,
+    /// Peterson & Barney (1952), American English, adult women
+    Women,
+}
+
+impl VowelReferences {
+    pub const ALL: [VowelReferences; 2] = [VowelReferences::Men, VowelReferences::Women];
+
+    pub fn vowels(&self) -> &'static [ReferenceVowel] {
+        match self {
+            VowelReferences::Men => &PETERSON_BARNEY_MEN,
+            VowelReferences::Women => &PETERSON_BARNEY_WOMEN,
+        }
+    }
+}
+
+impl fmt::Display for VowelReferences {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            VowelReferences::Men => "Men (Peterson & Barney)",
+            VowelReferences::Women => "Women (Peterson & Barney)",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn formants(frequencies: &[f32]) -> Vec<Formant> {
+        frequencies
+            .iter()
+            .map(|f| Formant {
+                frequency: Hz(*f),
+                bandwidth: Hz(100.),
+            })
+            .collect()
+    }
+
+    fn at(secs: f32) -> Instant {
+        Instant::ZERO + Duration::from_secs_f32(secs)
+    }
+
+    #[test]
+    fn trail() {
+        let mut trail = VowelTrail::new(Duration::from_secs(1));
+        trail.push(at(0.), &formants(&[300., 900., 2200.]));
+        // Too few formants to place
+        trail.push(at(0.5), &formants(&[300.]));
+        assert_eq!(trail.len(), 1);
+        let point = trail.points().next().unwrap();
+        assert_eq!((point.f1, point.f2), (Hz(300.), Hz(900.)));
+
+        trail.push(at(1.2), &formants(&[700., 1100.]));
+        assert_eq!(trail.len(), 1);
+        assert_eq!(trail.latest_time(), Some(at(1.2)));
+
+        // Even without a new point, old ones age out
+        trail.push(at(2.5), &[]);
+        assert!(trail.is_empty());
+    }
+}
@@ -0,0 +1,74 @@
+use std::f32::consts::PI;
+
+/// Window functions, for tapering periods before spectral analysis
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Window {
+    Rectangular,
+    Hann,
+    Hamming,
+    Blackman,
+}
+
+impl Window {
+    /// The window coefficients, in periodic (rather than symmetric) form,
+    /// which is the form that overlap-adds to a constant (and so is what you
+    /// want for both spectral analysis and resynthesis).
+    pub fn coefficients(&self, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| {
+                let x = 2. * PI * i as f32 / len as f32;
+                match self {
+                    Window::Rectangular => 1.,
+                    Window::Hann => 0.5 - 0.5 * x.cos(),
+                    Window::Hamming => 0.54 - 0.46 * x.cos(),
+                    Window::Blackman => 0.42 - 0.5 * x.cos() + 0.08 * (2. * x).cos(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// If copies of the given window, advancing by hop samples, overlap-add to a
+/// constant, return that constant.
+pub fn overlap_add_constant(window: &[f32], hop: usize) -> Option<f32> {
+    assert!(hop > 0 && hop <= window.len());
+    // Each sample of the steady-state output is the sum of the window values
+    // that are an integer number of hops apart:
+    let sums: Vec<f32> = (0..hop)
+        .map(|offset| window.iter().skip(offset).step_by(hop).sum())
+        .collect();
+    let first = sums[0];
+    if sums
+        .iter()
+        .all(|s| (s - first).abs() <= 1e-4 * first.abs().max(1.))
+    {
+        Some(first)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hann_coefficients() {
+        let w = Window::Hann.coefficients(4);
+        assert_abs_diff_eq!(w.as_slice(), [0., 0.5, 1., 0.5].as_slice(), epsilon = 1e-6);
+    }
+
+    #[test]
+    fn cola() {
+        assert_eq!(
+            overlap_add_constant(&Window::Rectangular.coefficients(8), 8),
+            Some(1.)
+        );
+        assert_abs_diff_eq!(
+            overlap_add_constant(&Window::Hann.coefficients(64), 32).unwrap(),
+            1.,
+            epsilon = 1e-5
+        );
+        assert!(overlap_add_constant(&Window::Hann.coefficients(64), 24).is_none());
+    }
+}
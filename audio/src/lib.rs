@@ -6,8 +6,16 @@ pub mod dsp;
 pub mod stream;
 pub mod synth;
 
+use dsp::loudness::Loudness;
+use dsp::lpc::{Formant, SpectralEnvelope};
+use dsp::mel::MelFrame;
+pub use dsp::oscilloscope::ScopeTrace;
+use dsp::peak_meter::ChannelPeaks;
+use dsp::pitch::PitchEstimate;
+use dsp::psd::PowerSpectrum;
+use dsp::sound_level::SoundLevel;
 use stream::input::Instant;
-pub use stream::transform::FFTResult;
+pub use stream::transform::{ConstantQResult, FFTResult};
 
 #[derive(Clone, Debug)]
 pub struct RMSLevels {
@@ -17,10 +25,85 @@ pub struct RMSLevels {
     pub values: Vec<f32>,
 }
 
+#[derive(Clone, Debug)]
+pub struct PeakLevels {
+    /// The end time of the measurement period, as for RMSLevels
+    pub time: Instant,
+    /// Sample and true peaks, crest factor and clips, for each channel
+    pub peaks: Vec<ChannelPeaks>,
+}
+
+#[derive(Clone, Debug)]
+pub struct PSDResult {
+    /// The end time of the measurement period
+    pub end_time: Instant,
+    /// Welch power spectral density estimates, for each channel
+    pub psds: Vec<PowerSpectrum>,
+}
+
+#[derive(Clone, Debug)]
+pub struct FormantResult {
+    /// The end time of the analysis period
+    pub end_time: Instant,
+    /// The lowest formants (up to dsp::lpc::MAX_FORMANTS), for each channel
+    pub formants: Vec<Vec<Formant>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct EnvelopeResult {
+    /// The end time of the analysis period
+    pub end_time: Instant,
+    /// LPC spectral envelopes, for each channel (None for silent channels)
+    pub envelopes: Vec<Option<SpectralEnvelope>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct PitchResult {
+    /// The end time of the analysis period
+    pub end_time: Instant,
+    /// Fundamental frequency estimates, for each channel
+    pub pitches: Vec<PitchEstimate>,
+}
+
+#[derive(Clone, Debug)]
+pub struct LoudnessResult {
+    /// The end time of the measurement
+    pub time: Instant,
+    /// Of all channels together
+    pub loudness: Loudness,
+}
+
+#[derive(Clone, Debug)]
+pub struct MelResult {
+    /// The end time of the latest frame
+    pub end_time: Instant,
+    /// The mel frames (with deltas) completed since the last MelResult, in
+    /// order, for each channel
+    pub frames: Vec<Vec<MelFrame>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SoundLevelResult {
+    /// The end time of the latest period
+    pub end_time: Instant,
+    /// Sound level meter readings, for each channel
+    pub levels: Vec<SoundLevel>,
+}
+
 // The message type that is used to update iced application state
 #[derive(Debug, Clone)]
 pub enum Message {
     AudioStreamClosed,
+    ConstantQResult(ConstantQResult),
+    EnvelopeResult(EnvelopeResult),
     FFTResult(FFTResult),
+    FormantResult(FormantResult),
+    LoudnessResult(LoudnessResult),
+    MelResult(MelResult),
+    PSDResult(PSDResult),
+    PeakLevels(PeakLevels),
+    PitchResult(PitchResult),
     RMSLevels(RMSLevels),
+    ScopeTrace(ScopeTrace),
+    SoundLevelResult(SoundLevelResult),
 }
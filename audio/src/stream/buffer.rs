@@ -281,12 +281,22 @@ pub struct BufferedInput<T: Input<Item = Frame>> {
 
 impl<T: Input<Item = Frame>> BufferedInput<T> {
     /// The BufferedInput will get its sample rate and channel count from the input
-    pub fn new(mut input: T, period_len: usize) -> Result<BufferedInput<T>, InputError> {
+    pub fn new(input: T, period_len: usize) -> Result<BufferedInput<T>, InputError> {
+        BufferedInput::overlapping(input, period_len, period_len)
+    }
+
+    /// Like new, but with periods starting every period_stride samples (see
+    /// PeriodBuffer::new)
+    pub fn overlapping(
+        mut input: T,
+        period_len: usize,
+        period_stride: usize,
+    ) -> Result<BufferedInput<T>, InputError> {
         let frame = input.read()?;
         let mut buffer = PeriodBuffer::new(
             SampleBuffer::new(frame.channels, frame.sample_rate, 2 * period_len),
             period_len,
-            period_len,
+            period_stride,
         );
         buffer.push(&frame);
         Ok(BufferedInput { input, buffer })
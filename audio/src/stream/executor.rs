@@ -7,35 +7,121 @@ use super::buffer::{PeriodBuffer, SampleBuffer};
 use super::input::{Input, InputDevice};
 use super::output::OutputDevice;
 use super::pipeline::{Pipeline, Step};
-use super::transform::FFT;
+use super::transform::{CQT, FFT};
 use super::wav::WavWriter;
-use super::{ChannelCount, Frame, SampleRate};
-use crate::{dsp, Message, RMSLevels};
+use super::{ChannelCount, Frame, Instant, SampleRate};
+use crate::dsp::constant_q::ConstantQOptions;
+use crate::dsp::loudness::LoudnessMeter;
+use crate::dsp::lpc::{FormantAnalyzer, SpectralEnvelope};
+use crate::dsp::mel::{MelAnalyzer, MelFrame, MelOptions, MelStream};
+use crate::dsp::oscilloscope::{Oscilloscope, ScopeOptions};
+use crate::dsp::peak_meter::PeakMeter;
+use crate::dsp::pitch::PitchTracker;
+use crate::dsp::psd::Welch;
+use crate::dsp::sound_level::{SoundLevelMeter, SoundLevelOptions};
+use crate::dsp::window::Window;
+use crate::{
+    dsp, EnvelopeResult, FormantResult, LoudnessResult, MelResult, Message, PSDResult, PeakLevels,
+    PitchResult, RMSLevels, SoundLevelResult,
+};
 
 // The maximum length of channels passing audio data amongst threads
 // This shouldn't be large; if a consumer isn't keeping up long channels are
 // just going to add latency to the situation.
 pub const CHANNEL_MAX: usize = 16;
 
+/// The number of frequencies at which spectral envelopes are evaluated
+const ENVELOPE_LEN: usize = 512;
+
+/// Requests from the UI thread to change how audio is analyzed
+#[derive(Clone, Debug)]
+pub enum Command {
+    /// Set the order of the LPC model, for both formants and envelopes
+    LPCOrder(usize),
+    /// Set the bins of the constant-Q transform
+    ConstantQ(ConstantQOptions),
+    /// Restart integrated loudness and loudness range measurement
+    ResetLoudness,
+    /// Restart counting clips
+    ResetClips,
+    /// Set the weightings, Leq interval and calibration of sound level
+    /// metering (which restarts it, unless only the calibration changes)
+    SoundLevel(SoundLevelOptions),
+    /// Restart sound level metering
+    ResetSoundLevel,
+    /// Set the oscilloscope's timebase and triggering (which re-arms it)
+    Oscilloscope(ScopeOptions),
+    /// Capture the next trigger, in the oscilloscope's Single mode
+    ArmTrigger,
+}
+
 pub struct Executor {
     channels: ChannelCount,
     sample_rate: SampleRate,
     writer: WavWriter,
     periods: PeriodBuffer,
     fft: FFT,
+    /// Long, overlapping periods for the constant-Q transform, whose lowest
+    /// bins need long kernels
+    cq_periods: PeriodBuffer,
+    cqt: CQT,
+    /// Shorter, overlapping periods for power spectral density estimation
+    psd_periods: PeriodBuffer,
+    /// By channel
+    psds: Vec<Welch>,
+    /// Short periods, over which speech is roughly stationary, for formant
+    /// and pitch estimation
+    formant_periods: PeriodBuffer,
+    formants: FormantAnalyzer,
+    /// The envelopes of the latest formant period, which are sent with each
+    /// FFT (as the FFT's periods are too long for LPC to model well)
+    envelopes: Option<EnvelopeResult>,
+    /// By channel
+    pitches: Vec<PitchTracker>,
+    /// Short, overlapping periods for mel features
+    mel_periods: PeriodBuffer,
+    mel: MelAnalyzer,
+    /// By channel
+    mel_streams: Vec<MelStream>,
+    /// The frames completed since the last FFT period, by channel
+    mel_frames: Vec<Vec<MelFrame>>,
+    loudness: LoudnessMeter,
+    /// Peaks of every sample, sent (and restarted) with each FFT period
+    peaks: PeakMeter,
+    /// Short, consecutive periods that are sound level metered
+    sound_level_periods: PeriodBuffer,
+    /// By channel
+    sound_levels: Vec<SoundLevelMeter>,
+    /// Long, overlapping periods, in which the oscilloscope looks for
+    /// triggers
+    scope_periods: PeriodBuffer,
+    scope: Oscilloscope,
+    /// The number of samples (per channel) received from the input device
+    sample_count: usize,
     sender: Sender<Message>,
+    commands: Receiver<Command>,
 }
 
 /// Encapsulates the audio processing thread, which waits for samples from the
 /// input device, computes the results we want, and sends those results to
 /// the UI thread for display.
 impl Executor {
-    /// Create an executor that will send display updates via the given Sender
+    /// Create an executor that will send display updates via the given Sender,
+    /// and take commands from the given Receiver
     pub fn new(
         sender: Sender<Message>,
+        commands: Receiver<Command>,
         channels: ChannelCount,
         sample_rate: SampleRate,
     ) -> Executor {
+        let scope = Oscilloscope::new(sample_rate, &ScopeOptions::default());
+        let pitches: Vec<PitchTracker> = (0..usize::from(channels))
+            .map(|_| PitchTracker::for_voice(sample_rate))
+            .collect();
+        // Long enough for the lowest pitches at high sample rates
+        let formant_len = pitches
+            .first()
+            .map_or(2048, |p| p.min_period_len().max(2048));
         Executor {
             channels,
             sample_rate,
@@ -46,7 +132,78 @@ impl Executor {
                 8192,
             ),
             fft: FFT::new(8192),
+            cq_periods: PeriodBuffer::new(
+                SampleBuffer::new(channels, sample_rate, usize::from(sample_rate) * 2),
+                16384,
+                4096,
+            ),
+            cqt: CQT::new(sample_rate, 16384, ConstantQOptions::default()),
+            psd_periods: PeriodBuffer::new(
+                SampleBuffer::new(channels, sample_rate, usize::from(sample_rate) * 2),
+                2048,
+                1024,
+            ),
+            psds: (0..usize::from(channels))
+                .map(|_| Welch::new(Window::Hann, 2048))
+                .collect(),
+            formant_periods: PeriodBuffer::new(
+                SampleBuffer::new(channels, sample_rate, usize::from(sample_rate) * 2),
+                formant_len,
+                formant_len,
+            ),
+            formants: FormantAnalyzer::new(sample_rate),
+            envelopes: None,
+            pitches,
+            mel_periods: PeriodBuffer::new(
+                SampleBuffer::new(channels, sample_rate, usize::from(sample_rate) * 2),
+                1024,
+                512,
+            ),
+            mel: MelAnalyzer::new(sample_rate, 1024, MelOptions::default()),
+            mel_streams: (0..usize::from(channels))
+                .map(|_| MelStream::new(MelOptions::default().delta_width))
+                .collect(),
+            mel_frames: vec![Vec::new(); usize::from(channels)],
+            loudness: LoudnessMeter::new(channels, sample_rate),
+            peaks: PeakMeter::new(channels, sample_rate),
+            sound_level_periods: PeriodBuffer::new(
+                SampleBuffer::new(channels, sample_rate, usize::from(sample_rate) * 2),
+                usize::from(sample_rate) / 10,
+                usize::from(sample_rate) / 10,
+            ),
+            sound_levels: (0..usize::from(channels))
+                .map(|_| SoundLevelMeter::new(sample_rate, &SoundLevelOptions::default()))
+                .collect(),
+            scope_periods: PeriodBuffer::new(
+                SampleBuffer::new(channels, sample_rate, 2 * scope.period_len()),
+                scope.period_len(),
+                scope.period_stride(),
+            ),
+            scope,
+            sample_count: 0,
             sender,
+            commands,
+        }
+    }
+
+    fn apply(&mut self, command: Command) {
+        match command {
+            Command::LPCOrder(order) => self.formants.set_order(order),
+            Command::ConstantQ(options) => self.cqt.set_options(options),
+            Command::ResetLoudness => self.loudness.reset(),
+            Command::ResetClips => self.peaks.reset_clips(),
+            Command::SoundLevel(options) => {
+                for meter in &mut self.sound_levels {
+                    meter.set_options(&options);
+                }
+            }
+            Command::ResetSoundLevel => {
+                for meter in &mut self.sound_levels {
+                    meter.reset();
+                }
+            }
+            Command::Oscilloscope(options) => self.scope.set_options(&options),
+            Command::ArmTrigger => self.scope.arm(),
         }
     }
 
@@ -54,13 +211,109 @@ impl Executor {
     fn process(&mut self, frame: &Frame) -> Vec<Message> {
         let mut res = Vec::new();
         self.writer.push(frame).expect("session.wav write error");
+        self.sample_count += frame.samples.len() / usize::from(self.channels);
+        if self.loudness.push(frame) {
+            res.push(Message::LoudnessResult(LoudnessResult {
+                time: Instant::from_sample_num(self.sample_count, self.sample_rate),
+                loudness: self.loudness.loudness(),
+            }));
+        }
+        self.peaks.push(frame);
         self.periods.push(frame);
+        self.cq_periods.push(frame);
+        self.psd_periods.push(frame);
+        self.formant_periods.push(frame);
+        self.mel_periods.push(frame);
+        self.sound_level_periods.push(frame);
+        self.scope_periods.push(frame);
+        while let Some(p) = self.mel_periods.next() {
+            for (i, ch) in p.channels().iter().enumerate() {
+                let features = self.mel.analyze(p.end_time(), ch);
+                if let Some(frame) = self.mel_streams[i].push(features) {
+                    self.mel_frames[i].push(frame);
+                }
+            }
+        }
+        while let Some(p) = self.sound_level_periods.next() {
+            res.push(Message::SoundLevelResult(SoundLevelResult {
+                end_time: p.end_time(),
+                levels: self
+                    .sound_levels
+                    .iter_mut()
+                    .zip(p.channels())
+                    .map(|(meter, ch)| {
+                        meter.push(&ch);
+                        meter.sound_level()
+                    })
+                    .collect(),
+            }));
+        }
+        while let Some(p) = self.scope_periods.next() {
+            if let Some(trace) = self.scope.capture(&p) {
+                res.push(Message::ScopeTrace(trace));
+            }
+        }
+        while let Some(p) = self.cq_periods.next() {
+            res.push(Message::ConstantQResult(self.cqt.transform(&p)));
+        }
+        while let Some(p) = self.psd_periods.next() {
+            for (psd, ch) in self.psds.iter_mut().zip(p.channels()) {
+                psd.push(&ch);
+            }
+        }
+        while let Some(p) = self.formant_periods.next() {
+            let envelopes: Vec<Option<SpectralEnvelope>> = p
+                .channels()
+                .iter()
+                .map(|ch| self.formants.envelope(ch, ENVELOPE_LEN))
+                .collect();
+            res.push(Message::FormantResult(FormantResult {
+                end_time: p.end_time(),
+                formants: envelopes
+                    .iter()
+                    .map(|e| e.as_ref().map_or(Vec::new(), |e| e.formants.clone()))
+                    .collect(),
+            }));
+            res.push(Message::PitchResult(PitchResult {
+                end_time: p.end_time(),
+                pitches: self
+                    .pitches
+                    .iter_mut()
+                    .zip(p.channels())
+                    .map(|(tracker, ch)| tracker.push(&ch))
+                    .collect(),
+            }));
+            self.envelopes = Some(EnvelopeResult {
+                end_time: p.end_time(),
+                envelopes,
+            });
+        }
         while let Some(p) = self.periods.next() {
             res.push(Message::FFTResult(self.fft.transform(&p)));
+            if let Some(envelopes) = self.envelopes.take() {
+                res.push(Message::EnvelopeResult(envelopes));
+            }
+            // i.e. the average of the PSD periods since the last FFT period
+            if let Some(psds) = self.psds.iter_mut().map(|w| w.take()).collect() {
+                res.push(Message::PSDResult(PSDResult {
+                    end_time: p.end_time(),
+                    psds,
+                }));
+            }
+            if let Some(end_time) = self.mel_frames[0].last().map(|f| f.end_time) {
+                res.push(Message::MelResult(MelResult {
+                    end_time,
+                    frames: self.mel_frames.iter_mut().map(std::mem::take).collect(),
+                }));
+            }
             res.push(Message::RMSLevels(RMSLevels {
-                time: p.start_time(),
+                time: p.end_time(),
                 values: p.channels().into_iter().map(|c| dsp::rms(&c)).collect(),
             }));
+            res.push(Message::PeakLevels(PeakLevels {
+                time: p.end_time(),
+                peaks: self.peaks.take(),
+            }));
         }
         res
     }
@@ -68,6 +321,9 @@ impl Executor {
     /// The main loop of the audio processing thread
     fn run<T: Input<Item = Frame>>(mut self, mut input: T) {
         loop {
+            while let Ok(cmd) = self.commands.try_recv() {
+                self.apply(cmd);
+            }
             match input.read() {
                 Ok(f) => {
                     for m in self.process(&f) {
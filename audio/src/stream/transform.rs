@@ -1,3 +1,4 @@
+use crate::dsp::constant_q::{ConstantQ, ConstantQOptions, ConstantQSpectrum};
 use crate::dsp::fft::{FFTSequence, FoldedFFT};
 use crate::stream::buffer::Period;
 use crate::stream::SampleRate;
@@ -24,7 +25,7 @@ impl FFT {
         }
     }
 
-    pub fn transform(&self, period: &Period) -> FFTResult {
+    pub fn transform(&mut self, period: &Period) -> FFTResult {
         assert!(self.width == period.len());
         let mut res = FFTResult {
             end_time: period.end_time(),
@@ -33,8 +34,56 @@ impl FFT {
             ffts: Vec::new(),
         };
         for ch in period.channels() {
-            res.ffts.push(self.fft.fft(&ch).into_polar().into_folded())
+            res.ffts.push(self.fft.folded(&ch))
         }
         res
     }
 }
+
+#[derive(Clone, Debug)]
+pub struct ConstantQResult {
+    pub end_time: Instant,
+    pub sample_rate: SampleRate,
+    pub spectra: Vec<ConstantQSpectrum>,
+}
+
+/// A constant-Q transform of each channel of a period
+pub struct CQT {
+    width: usize,
+    options: ConstantQOptions,
+    transform: ConstantQ,
+}
+
+impl CQT {
+    pub fn new(sample_rate: SampleRate, width: usize, options: ConstantQOptions) -> CQT {
+        CQT {
+            width,
+            transform: ConstantQ::new(sample_rate, width, &options),
+            options,
+        }
+    }
+
+    pub fn options(&self) -> &ConstantQOptions {
+        &self.options
+    }
+
+    /// Rebuild the transform's kernels for new options
+    pub fn set_options(&mut self, options: ConstantQOptions) {
+        self.transform = ConstantQ::new(self.transform.sample_rate(), self.width, &options);
+        self.options = options;
+    }
+
+    pub fn transform(&mut self, period: &Period) -> ConstantQResult {
+        assert!(self.width == period.len());
+        assert!(self.transform.sample_rate() == period.sample_rate());
+        ConstantQResult {
+            end_time: period.end_time(),
+            sample_rate: period.sample_rate(),
+            spectra: period
+                .channels()
+                .iter()
+                .map(|ch| self.transform.transform(ch))
+                .collect(),
+        }
+    }
+}
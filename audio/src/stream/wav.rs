@@ -1,5 +1,6 @@
 use std::fs::File;
 use std::io::BufWriter;
+use std::path::Path;
 
 use hound; // (provides .wav encoding)
 pub use hound::Result;
@@ -57,3 +58,24 @@ impl WavWriter {
         Ok(())
     }
 }
+
+/// Read all the (interlaced) samples of a .wav file, scaled to -1..1
+pub fn read_wav<P: AsRef<Path>>(path: P) -> Result<(ChannelCount, SampleRate, Vec<f32>)> {
+    let reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+    let samples = match spec.sample_format {
+        hound::SampleFormat::Float => reader.into_samples::<f32>().collect::<Result<_>>()?,
+        hound::SampleFormat::Int => {
+            let scale = 2f32.powi(spec.bits_per_sample as i32 - 1);
+            reader
+                .into_samples::<i32>()
+                .map(|s| s.map(|s| s as f32 / scale))
+                .collect::<Result<_>>()?
+        }
+    };
+    Ok((
+        ChannelCount::new(spec.channels),
+        SampleRate::new(spec.sample_rate),
+        samples,
+    ))
+}
@@ -71,14 +71,13 @@ pub struct Gain {
 impl Gain {
     pub fn new(gain: Decibels) -> Gain {
         Gain {
-            // sqrt converts from power ratio to amplitude ratio
-            gain: gain.into_full_scale().sqrt(),
+            gain: gain.amplitude_ratio(),
             next: None,
         }
     }
 
     pub fn set_gain(&mut self, gain: Decibels) {
-        self.gain = gain.into_full_scale().sqrt();
+        self.gain = gain.amplitude_ratio();
     }
 }
 
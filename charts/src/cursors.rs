@@ -0,0 +1,115 @@
+use std::ops::Range;
+
+use audio::dsp::tuning::Tuning;
+use audio::dsp::Hz;
+use plotters::coord::ranged1d::Ranged;
+use plotters::prelude::*;
+
+use crate::FrequencyScale;
+
+/// Mouse cursors over a chart, in backend (i.e. pixel) coordinates, so they
+/// stay put on the screen as the chart's data changes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Cursors {
+    /// Where the mouse is, if it's over the chart
+    pub hover: Option<(i32, i32)>,
+    /// Where the mouse was clicked, to measure the hover cursor from
+    pub anchor: Option<(i32, i32)>,
+}
+
+/// The value an axis maps to a pixel, found by bisection, since not all
+/// axes (e.g. log scales) can be inverted directly. None if the pixel is off
+/// the axis.
+fn unmap(map: impl Fn(f32) -> i32, range: Range<f32>, pixel: i32) -> Option<f32> {
+    let (start, end) = (map(range.start), map(range.end));
+    if pixel < start.min(end) || pixel > start.max(end) {
+        return None;
+    }
+    let (mut lo, mut hi) = (range.start, range.end);
+    for _ in 0..32 {
+        let mid = (lo + hi) / 2.;
+        if (map(mid) < pixel) == (start < end) {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    Some((lo + hi) / 2.)
+}
+
+/// The chart coordinates of a pixel, if it's in the chart's plotting area
+fn chart_coord<DB, X, Y>(
+    chart: &ChartContext<DB, Cartesian2d<X, Y>>,
+    pixel: (i32, i32),
+) -> Option<(f32, f32)>
+where
+    DB: DrawingBackend,
+    X: Ranged<ValueType = f32>,
+    Y: Ranged<ValueType = f32>,
+{
+    let (x_range, y_range) = (chart.x_range(), chart.y_range());
+    let (x0, y0) = (x_range.start, y_range.start);
+    let x = unmap(|x| chart.backend_coord(&(x, y0)).0, x_range, pixel.0)?;
+    let y = unmap(|y| chart.backend_coord(&(x0, y)).1, y_range, pixel.1)?;
+    Some((x, y))
+}
+
+/// Draw crosshairs at the hover cursor, if it's over the chart, and a cross
+/// at the anchor, with readouts at the top left.
+///
+/// `readout` gives the point to mark for a cursor's chart coordinates (e.g.
+/// snapped to a trace) and describes it; `delta` describes the difference
+/// from the anchor's point to the hover cursor's.
+pub fn draw_cursors<DB, X, Y>(
+    chart: &mut ChartContext<DB, Cartesian2d<X, Y>>,
+    cursors: &Cursors,
+    readout: impl Fn((f32, f32)) -> ((f32, f32), String),
+    delta: impl Fn((f32, f32), (f32, f32)) -> String,
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>>
+where
+    DB: DrawingBackend,
+    X: Ranged<ValueType = f32>,
+    Y: Ranged<ValueType = f32>,
+{
+    let hover = cursors
+        .hover
+        .and_then(|p| chart_coord(chart, p))
+        .map(&readout);
+    let anchor = cursors
+        .anchor
+        .and_then(|p| chart_coord(chart, p))
+        .map(&readout);
+    let (x_range, y_range) = (chart.x_range(), chart.y_range());
+    let color = BLACK.mix(0.6);
+
+    if let Some((point, _)) = &anchor {
+        chart.draw_series([Cross::new(*point, 6, color.stroke_width(2))])?;
+    }
+    let Some(((x, y), text)) = hover else {
+        return Ok(());
+    };
+    chart.draw_series([
+        PathElement::new(vec![(x, y_range.start), (x, y_range.end)], color),
+        PathElement::new(vec![(x_range.start, y), (x_range.end, y)], color),
+    ])?;
+    chart.draw_series([Circle::new((x, y), 4, color)])?;
+
+    let mut lines = vec![text];
+    if let Some((from, _)) = anchor {
+        lines.push(delta(from, (x, y)));
+    }
+    chart.draw_series(lines.into_iter().enumerate().map(|(i, line)| {
+        EmptyElement::at((x_range.start, y_range.end))
+            + Text::new(line, (8, 8 + 16 * i as i32), ("sans-serif", 14).into_font())
+    }))?;
+    Ok(())
+}
+
+/// A frequency, and the nearest note to it (if it's audible), for readouts
+pub(crate) fn describe_frequency(frequency: f32, tuning: &Tuning) -> String {
+    if frequency < FrequencyScale::LOG_MIN_FREQUENCY {
+        return format!("{:.1} Hz", frequency);
+    }
+    let note = tuning.nearest(Hz(frequency));
+    format!("{:.1} Hz ({} {:+.0}c)", frequency, note, note.cents)
+}
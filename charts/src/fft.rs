@@ -0,0 +1,772 @@
+use std::f32::consts::PI;
+use std::fmt;
+use std::ops::Range;
+
+use audio::dsp::fft::FoldedFFT;
+use audio::dsp::lpc::SpectralEnvelope;
+use audio::dsp::peaks::{HarmonicSeries, Peak};
+use audio::dsp::reference_trace::ReferenceTrace;
+use audio::dsp::tuning::Tuning;
+use audio::dsp::{Decibels, Hz, Level};
+use plotters::coord::ranged1d::{Ranged, ValueFormatter};
+use plotters::coord::types::RangedCoordf32;
+use plotters::coord::Shift;
+use plotters::prelude::*;
+
+use crate::cursors::{describe_frequency, draw_cursors, Cursors};
+use crate::{channel_color, FrequencyScale};
+
+/// Bins this far below the loudest (dB) have no meaningful phase, so their
+/// phases and group delays aren't plotted
+const PHASE_FLOOR_DB: f32 = -60.;
+
+/// Fitted tops of the magnitude axis are multiples of this (dBFS)
+const TOP_STEP_DB: f32 = 10.;
+
+/// The lowest top that's fitted (dBFS), so silence isn't blown up to fill
+/// the chart
+const MIN_TOP_DB: f32 = -120.;
+
+/// AutoRange only lowers the top once the loudest bin has stayed at least
+/// this far (dB) below it...
+const AUTO_RANGE_HYSTERESIS_DB: f32 = 20.;
+
+/// ...for this many spectra in a row
+const AUTO_RANGE_HOLD: usize = 20;
+
+/// With fewer notes than this across the frequency axis, every note gets a
+/// gridline, otherwise only the Cs do
+const MAX_NOTE_GRIDLINES: i32 = 48;
+
+/// What to draw over FFTs' spectra
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FFTAnnotations<'a> {
+    /// Read out in whichever pane they're over
+    pub cursors: Cursors,
+    /// An LPC spectral envelope, and its formants
+    pub envelope: Option<&'a SpectralEnvelope>,
+    /// Peaks to mark and label with their frequencies
+    pub peaks: &'a [Peak],
+    /// Peaks that are partials of this series are labelled with their
+    /// harmonic numbers too
+    pub harmonics: Option<&'a HarmonicSeries>,
+    /// A trace to overlay, and to compare every spectrum with in the
+    /// difference pane
+    pub reference: Option<&'a ReferenceTrace>,
+}
+
+/// How the phases of FFTs are shown, in a pane below their amplitudes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PhaseDisplay {
+    #[default]
+    Hidden,
+    /// In -PI..=PI
+    Wrapped,
+    /// Accumulated from DC, so that a delay is a straight line
+    Unwrapped,
+}
+
+impl PhaseDisplay {
+    pub const ALL: [PhaseDisplay; 3] = [
+        PhaseDisplay::Hidden,
+        PhaseDisplay::Wrapped,
+        PhaseDisplay::Unwrapped,
+    ];
+}
+
+impl fmt::Display for PhaseDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            PhaseDisplay::Hidden => "No phase",
+            PhaseDisplay::Wrapped => "Wrapped phase",
+            PhaseDisplay::Unwrapped => "Unwrapped phase",
+        })
+    }
+}
+
+/// How magnitude axes are scaled
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MagnitudeScale {
+    /// In FS
+    Linear,
+    /// In dBFS
+    Decibels,
+}
+
+impl MagnitudeScale {
+    pub const ALL: [MagnitudeScale; 2] = [MagnitudeScale::Linear, MagnitudeScale::Decibels];
+}
+
+impl fmt::Display for MagnitudeScale {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            MagnitudeScale::Linear => "Linear",
+            MagnitudeScale::Decibels => "dB",
+        })
+    }
+}
+
+/// How to draw FFTs, with draw_fft_chart or the functions for its panes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FFTChartOptions {
+    pub frequency_scale: FrequencyScale,
+    /// The lowest frequency to show (Hz), or None to show from 0 Hz (or
+    /// FrequencyScale::LOG_MIN_FREQUENCY on a log axis)
+    pub min_frequency: Option<f32>,
+    /// The highest frequency to show (Hz), or None to show up to nyquist
+    pub max_frequency: Option<f32>,
+    pub magnitude_scale: MagnitudeScale,
+    /// The bottom of the magnitude axis when it's in decibels (dBFS)
+    pub floor_db: f32,
+    /// The top of the magnitude axis (FS, whatever its scale), or None to fit
+    /// the loudest bin shown. AutoRange fits a stream of spectra more calmly.
+    pub top: Option<f32>,
+    /// Draw gridlines at the notes of this tuning, labelling each C
+    pub notes: Option<Tuning>,
+    pub phase: PhaseDisplay,
+    pub group_delay: bool,
+    /// Show how far each spectrum is above the reference trace (dB), if
+    /// there is one
+    pub difference: bool,
+}
+
+impl Default for FFTChartOptions {
+    fn default() -> FFTChartOptions {
+        FFTChartOptions {
+            frequency_scale: FrequencyScale::Linear,
+            min_frequency: None,
+            max_frequency: None,
+            magnitude_scale: MagnitudeScale::Decibels,
+            floor_db: -100.,
+            top: None,
+            notes: None,
+            phase: PhaseDisplay::Hidden,
+            group_delay: false,
+            difference: false,
+        }
+    }
+}
+
+impl FFTChartOptions {
+    /// Where an amplitude (FS) is on the magnitude axis
+    fn magnitude(&self, amplitude: f32) -> f32 {
+        match self.magnitude_scale {
+            MagnitudeScale::Linear => amplitude,
+            MagnitudeScale::Decibels => f32::from(Level::dbfs(amplitude).max(self.floor_db)),
+        }
+    }
+
+    /// The magnitude axis, up to a top (FS)
+    fn magnitude_range(&self, top: f32) -> Range<f32> {
+        match self.magnitude_scale {
+            MagnitudeScale::Linear => 0f32..top,
+            MagnitudeScale::Decibels => {
+                self.floor_db..f32::from(Level::dbfs(top).max(self.floor_db + TOP_STEP_DB))
+            }
+        }
+    }
+
+    fn magnitude_desc(&self) -> &'static str {
+        match self.magnitude_scale {
+            MagnitudeScale::Linear => "Amplitude (FS)",
+            MagnitudeScale::Decibels => "Amplitude (dBFS)",
+        }
+    }
+}
+
+/// The lowest multiple of TOP_STEP_DB at or above an amplitude, in FS
+fn fit_top(amplitude: f32) -> f32 {
+    let db = f32::from(Level::dbfs(amplitude).max(MIN_TOP_DB));
+    Decibels::new((db / TOP_STEP_DB).ceil() * TOP_STEP_DB).amplitude_ratio()
+}
+
+/// The frequency axis
+fn frequency_range(ffts: &[(usize, &FoldedFFT)], options: &FFTChartOptions) -> Range<f32> {
+    let nyquist = ffts
+        .first()
+        .map_or(1., |(_, fft)| f32::from(fft.nyquist_frequency()));
+    let min = match options.frequency_scale {
+        FrequencyScale::Linear => 0.,
+        FrequencyScale::Log => FrequencyScale::LOG_MIN_FREQUENCY,
+    };
+    let lo = options.min_frequency.map_or(min, |f| f.max(min));
+    let hi = options.max_frequency.map_or(nyquist, |f| f.min(nyquist));
+    if lo < hi {
+        lo..hi
+    } else {
+        min..nyquist
+    }
+}
+
+/// The amplitude of the loudest bin shown, of any of the FFTs
+fn loudest(ffts: &[(usize, &FoldedFFT)], options: &FFTChartOptions) -> f32 {
+    let range = frequency_range(ffts, options);
+    ffts.iter()
+        .flat_map(|(_, fft)| fft.frequencies().zip(fft.values.iter()))
+        .filter(|(f, _)| range.contains(&f32::from(*f)))
+        .map(|(_, (r, _))| *r)
+        .fold(0., f32::max)
+}
+
+/// Follows a stream of spectra to choose the top of their magnitude axis,
+/// with hysteresis so that it doesn't jump about: it rises as soon as the
+/// loudest bin doesn't fit, but only falls once the loudest bin has stayed
+/// well below it for a while.
+#[derive(Clone, Debug, Default)]
+pub struct AutoRange {
+    top: Option<f32>,
+    /// The number of spectra in a row that have been well below the top
+    quiet: usize,
+}
+
+impl AutoRange {
+    pub fn new() -> AutoRange {
+        AutoRange::default()
+    }
+
+    /// For FFTChartOptions::top, or None before any spectra
+    pub fn top(&self) -> Option<f32> {
+        self.top
+    }
+
+    /// Follow the latest spectra, which will be drawn with the given options
+    pub fn push(&mut self, ffts: &[(usize, &FoldedFFT)], options: &FFTChartOptions) {
+        let fit = fit_top(loudest(ffts, options));
+        match self.top {
+            Some(top) if fit <= top => {
+                if f32::from(Level::dbfs(top) - Level::dbfs(fit)) < AUTO_RANGE_HYSTERESIS_DB {
+                    self.quiet = 0;
+                } else {
+                    self.quiet += 1;
+                    if self.quiet >= AUTO_RANGE_HOLD {
+                        self.top = Some(fit);
+                        self.quiet = 0;
+                    }
+                }
+            }
+            _ => {
+                self.top = Some(fit);
+                self.quiet = 0;
+            }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = AutoRange::default();
+    }
+}
+
+/// Plot the amplitude spectra of FFTs of some channels (numbered, for their
+/// colors and labels), with phase, group delay and difference panes below if
+/// enabled. Annotations (but the reference trace) are of the first FFT.
+pub fn draw_fft_chart<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    ffts: &[(usize, &FoldedFFT)],
+    annotations: &FFTAnnotations,
+    options: &FFTChartOptions,
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    let reference = annotations.reference.filter(|_| options.difference);
+    let panes = (options.phase != PhaseDisplay::Hidden) as u32
+        + options.group_delay as u32
+        + reference.is_some() as u32;
+    if panes == 0 {
+        return build_fft_chart(ChartBuilder::on(root), ffts, annotations, options);
+    }
+
+    // The amplitude pane is twice the height of each of the others
+    let height = root.dim_in_pixel().1;
+    let (upper, lower) = root.split_vertically(height * 2 / (panes + 2));
+    build_fft_chart(ChartBuilder::on(&upper), ffts, annotations, options)?;
+    let lower = lower.split_evenly((panes as usize, 1));
+    let mut lower = lower.iter();
+    if options.phase != PhaseDisplay::Hidden {
+        let area = lower.next().unwrap();
+        build_phase_chart(ChartBuilder::on(area), ffts, &annotations.cursors, options)?;
+    }
+    if options.group_delay {
+        let area = lower.next().unwrap();
+        build_group_delay_chart(ChartBuilder::on(area), ffts, &annotations.cursors, options)?;
+    }
+    if let Some(reference) = reference {
+        let area = lower.next().unwrap();
+        let cursors = &annotations.cursors;
+        build_difference_chart(ChartBuilder::on(area), ffts, reference, cursors, options)?;
+    }
+    Ok(())
+}
+
+/// Plot the amplitude spectra of FFTs of some channels (numbered, for their
+/// colors and labels), with annotations of the first
+pub fn build_fft_chart<DB: DrawingBackend>(
+    mut builder: ChartBuilder<DB>,
+    ffts: &[(usize, &FoldedFFT)],
+    annotations: &FFTAnnotations,
+    options: &FFTChartOptions,
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    let x_range = frequency_range(ffts, options);
+    let top = options
+        .top
+        .unwrap_or_else(|| fit_top(loudest(ffts, options)));
+    let y_range = options.magnitude_range(top);
+
+    builder
+        .margin(20)
+        .x_label_area_size(40)
+        .y_label_area_size(60);
+    match options.frequency_scale {
+        FrequencyScale::Linear => draw_amplitudes(
+            builder.build_cartesian_2d(x_range, y_range)?,
+            ffts,
+            annotations,
+            options,
+        ),
+        FrequencyScale::Log => draw_amplitudes(
+            builder.build_cartesian_2d(x_range.log_scale(), y_range)?,
+            ffts,
+            annotations,
+            options,
+        ),
+    }
+}
+
+fn draw_amplitudes<'a, DB, X>(
+    mut chart: ChartContext<'a, DB, Cartesian2d<X, RangedCoordf32>>,
+    ffts: &[(usize, &FoldedFFT)],
+    annotations: &FFTAnnotations,
+    options: &FFTChartOptions,
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>>
+where
+    DB: DrawingBackend + 'a,
+    X: Ranged<ValueType = f32> + ValueFormatter<f32>,
+{
+    chart
+        .configure_mesh()
+        .x_max_light_lines(0)
+        .y_max_light_lines(0)
+        .y_desc(options.magnitude_desc())
+        .x_desc("Frequency (Hz)")
+        .draw()?;
+    if let Some(tuning) = &options.notes {
+        draw_notes(&mut chart, tuning, true)?;
+    }
+
+    let x_range = chart.x_range();
+    for (channel, fft) in ffts {
+        let color = channel_color(*channel);
+        let magnitudes = fft
+            .frequencies()
+            .zip(fft.values.iter())
+            .map(|(f, (r, _p))| (f32::from(f), options.magnitude(*r)))
+            .filter(|(f, _)| x_range.contains(f));
+        chart
+            .draw_series(LineSeries::new(magnitudes, color))?
+            .label(format!("ch{}", channel))
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+    }
+
+    if let Some(reference) = annotations.reference {
+        let color = BLACK.mix(0.5);
+        let magnitudes = reference
+            .frequencies
+            .iter()
+            .zip(reference.values.iter())
+            .map(|(f, r)| (f32::from(*f), options.magnitude(*r)))
+            .filter(|(f, _)| x_range.contains(f));
+        chart
+            .draw_series(LineSeries::new(magnitudes, color))?
+            .label(&reference.name)
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+    }
+
+    if let (Some(envelope), Some((_, fft))) = (annotations.envelope, ffts.first()) {
+        // The envelope's scale is arbitrary, so match it to the spectrum at
+        // the spectrum's peak (over the envelope's frequencies)
+        let max_frequency = envelope.frequencies.last().map_or(0., |f| f32::from(*f));
+        let scale = fft
+            .frequencies()
+            .zip(fft.values.iter())
+            .take_while(|(f, _)| f32::from(*f) <= max_frequency)
+            .max_by(|a, b| a.1 .0.total_cmp(&b.1 .0))
+            .and_then(|(f, (r, _))| Some(r / envelope.value_at(f)?))
+            .filter(|scale| scale.is_finite())
+            .unwrap_or(0.);
+
+        let values = envelope
+            .frequencies
+            .iter()
+            .zip(envelope.values.iter())
+            .map(|(f, y)| (f32::from(*f), options.magnitude(y * scale)))
+            .filter(|(f, _)| x_range.contains(f));
+        chart
+            .draw_series(LineSeries::new(values, BLUE.stroke_width(2)))?
+            .label("LPC envelope")
+            .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], BLUE));
+
+        // Mark each formant on the envelope, and label it at the top of the
+        // chart (staggered, since formants can be close together)
+        let markers = envelope.formants.iter().filter_map(|f| {
+            let y = options.magnitude(envelope.value_at(f.frequency)? * scale);
+            Some(Circle::new((f32::from(f.frequency), y), 4, BLUE.filled()))
+        });
+        chart.draw_series(markers)?;
+        let top = chart.y_range().end;
+        let labels = envelope.formants.iter().enumerate().map(|(i, f)| {
+            EmptyElement::at((f32::from(f.frequency), top))
+                + Text::new(
+                    format!("F{} {:.0} Hz", i + 1, f32::from(f.frequency)),
+                    (4, 4 + 16 * i as i32),
+                    ("sans-serif", 14).into_font().color(&BLUE),
+                )
+        });
+        chart.draw_series(labels)?;
+    }
+
+    // Peaks above the top of the chart are marked at the top
+    let top = chart.y_range().end;
+    let peak_at = |p: &Peak| {
+        (
+            f32::from(p.frequency),
+            options.magnitude(p.amplitude).min(top),
+        )
+    };
+    chart.draw_series(
+        annotations
+            .peaks
+            .iter()
+            .map(|p| TriangleMarker::new(peak_at(p), 5, MAGENTA.filled())),
+    )?;
+    let labels = annotations.peaks.iter().map(|p| {
+        let harmonic = annotations
+            .harmonics
+            .and_then(|h| h.partials.iter().find(|(_, partial)| partial == p))
+            .map_or(String::new(), |(n, _)| format!("h{} ", n));
+        EmptyElement::at(peak_at(p))
+            + Text::new(
+                format!("{}{:.0} Hz", harmonic, f32::from(p.frequency)),
+                (6, -8),
+                ("sans-serif", 12).into_font().color(&MAGENTA),
+            )
+    });
+    chart.draw_series(labels)?;
+
+    if let Some((_, fft)) = ffts.first() {
+        draw_spectrum_cursors(&mut chart, fft, &annotations.cursors, options)?;
+    }
+
+    chart
+        .configure_series_labels()
+        .position(SeriesLabelPosition::UpperRight)
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
+
+    Ok(())
+}
+
+/// Draw cursors snapped to the nearest bins of a spectrum
+fn draw_spectrum_cursors<DB, X>(
+    chart: &mut ChartContext<DB, Cartesian2d<X, RangedCoordf32>>,
+    fft: &FoldedFFT,
+    cursors: &Cursors,
+    options: &FFTChartOptions,
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>>
+where
+    DB: DrawingBackend,
+    X: Ranged<ValueType = f32> + ValueFormatter<f32>,
+{
+    if fft.values.is_empty() {
+        return Ok(());
+    }
+    let tuning = options.notes.unwrap_or_default();
+    let bin_width = f32::from(fft.sample_rate()) / fft.unfolded_length() as f32;
+    // The frequency of the nearest bin, and its amplitude (FS)
+    let nearest = |frequency: f32| {
+        let bin = ((frequency / bin_width).round() as usize).min(fft.values.len() - 1);
+        (bin as f32 * bin_width, fft.values[bin].0)
+    };
+    // Clamped to the floor, so that silence doesn't read -inf
+    let level = |amplitude: f32| Level::dbfs(amplitude).max(options.floor_db);
+    let describe = |amplitude: f32| match options.magnitude_scale {
+        MagnitudeScale::Linear => format!("{:.4} FS", amplitude),
+        MagnitudeScale::Decibels => format!("{:.1} dBFS", f32::from(level(amplitude))),
+    };
+    draw_cursors(
+        chart,
+        cursors,
+        |(x, _)| {
+            let (frequency, amplitude) = nearest(x);
+            let text = format!(
+                "{}, {}",
+                describe_frequency(frequency, &tuning),
+                describe(amplitude)
+            );
+            ((frequency, options.magnitude(amplitude)), text)
+        },
+        |(from, _), (to, _)| {
+            let (a, b) = (level(nearest(from).1), level(nearest(to).1));
+            format!("Δ {:.1} Hz, {:+.1} dB", to - from, f32::from(b - a))
+        },
+    )
+}
+
+/// Draw a gridline at each note on the frequency axis (or only at each C, if
+/// there are too many notes to tell apart), optionally labelling the Cs
+fn draw_notes<DB, X>(
+    chart: &mut ChartContext<DB, Cartesian2d<X, RangedCoordf32>>,
+    tuning: &Tuning,
+    labels: bool,
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>>
+where
+    DB: DrawingBackend,
+    X: Ranged<ValueType = f32> + ValueFormatter<f32>,
+{
+    let x_range = chart.x_range();
+    let y_range = chart.y_range();
+    let lowest = x_range.start.max(FrequencyScale::LOG_MIN_FREQUENCY);
+    let lo = tuning.note_number(Hz(lowest)).ceil() as i32;
+    let hi = tuning.note_number(Hz(x_range.end)).floor() as i32;
+    let every_note = hi - lo < MAX_NOTE_GRIDLINES;
+    let notes: Vec<(bool, f32)> = (lo..=hi)
+        .map(|n| (n.rem_euclid(12) == 0, tuning.frequency(n).0))
+        .filter(|(c, f)| (every_note || *c) && x_range.contains(f))
+        .collect();
+
+    chart.draw_series(notes.iter().map(|(c, f)| {
+        let color = if *c { BLUE.mix(0.4) } else { BLUE.mix(0.15) };
+        PathElement::new(vec![(*f, y_range.start), (*f, y_range.end)], color)
+    }))?;
+    if labels {
+        chart.draw_series(notes.iter().filter(|(c, _)| *c).map(|(_, f)| {
+            EmptyElement::at((*f, y_range.start))
+                + Text::new(
+                    tuning.nearest(Hz(*f)).to_string(),
+                    (3, -14),
+                    ("sans-serif", 12).into_font().color(&BLUE.mix(0.6)),
+                )
+        }))?;
+    }
+    Ok(())
+}
+
+/// Whether each bin of an FFT is loud enough for its phase to mean something
+fn loud_bins(fft: &FoldedFFT) -> Vec<bool> {
+    let loudest = fft.values.iter().map(|(r, _)| *r).fold(0., f32::max);
+    let floor = loudest * Decibels::new(PHASE_FLOOR_DB).amplitude_ratio();
+    fft.values
+        .iter()
+        .map(|(r, _)| *r > 0. && *r >= floor)
+        .collect()
+}
+
+/// The (frequency, value) of each loud bin of an FFT on the frequency axis
+fn loud_points(
+    fft: &FoldedFFT,
+    values: &[f32],
+    loud: &[bool],
+    x_range: &Range<f32>,
+) -> Vec<(f32, f32)> {
+    fft.frequencies()
+        .zip(values)
+        .zip(loud)
+        .filter(|(_, loud)| **loud)
+        .map(|((f, v), _)| (f32::from(f), *v))
+        .filter(|(f, _)| x_range.contains(f))
+        .collect()
+}
+
+/// The range of the values of some channels' points, or a default if there
+/// are none
+fn value_range(points: &[(usize, Vec<(f32, f32)>)], default: f32) -> Range<f32> {
+    let (lo, hi) = points
+        .iter()
+        .flat_map(|(_, points)| points)
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), (_, v)| {
+            (lo.min(*v), hi.max(*v))
+        });
+    if lo < hi {
+        let margin = (hi - lo) * 0.05;
+        lo - margin..hi + margin
+    } else if lo == hi {
+        lo - default..hi + default
+    } else {
+        -default..default
+    }
+}
+
+/// Plot the phase spectra of FFTs of some channels, as in build_fft_chart,
+/// leaving out bins too quiet to have a meaningful phase
+pub fn build_phase_chart<DB: DrawingBackend>(
+    builder: ChartBuilder<DB>,
+    ffts: &[(usize, &FoldedFFT)],
+    cursors: &Cursors,
+    options: &FFTChartOptions,
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    let x_range = frequency_range(ffts, options);
+    let points: Vec<(usize, Vec<(f32, f32)>)> = ffts
+        .iter()
+        .map(|(channel, fft)| {
+            let phases = match options.phase {
+                PhaseDisplay::Unwrapped => fft.unwrapped_phases(),
+                _ => fft.values.iter().map(|(_, p)| *p).collect(),
+            };
+            let points = loud_points(fft, &phases, &loud_bins(fft), &x_range);
+            (*channel, points)
+        })
+        .collect();
+    let y_range = match options.phase {
+        PhaseDisplay::Unwrapped => value_range(&points, PI),
+        _ => -PI..PI,
+    };
+    build_points_chart(
+        builder,
+        x_range,
+        y_range,
+        ("Phase (radians)", "rad"),
+        &points,
+        cursors,
+        options,
+    )
+}
+
+/// Plot the group delays of FFTs of some channels (in ms), as in
+/// build_phase_chart
+pub fn build_group_delay_chart<DB: DrawingBackend>(
+    builder: ChartBuilder<DB>,
+    ffts: &[(usize, &FoldedFFT)],
+    cursors: &Cursors,
+    options: &FFTChartOptions,
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    let x_range = frequency_range(ffts, options);
+    let points: Vec<(usize, Vec<(f32, f32)>)> = ffts
+        .iter()
+        .map(|(channel, fft)| {
+            let delays: Vec<f32> = fft.group_delays().iter().map(|d| d * 1000.).collect();
+            // Group delays are differences, so both neighbours need to be loud too
+            let loud = loud_bins(fft);
+            let loud: Vec<bool> = (0..loud.len())
+                .map(|k| {
+                    loud[k.saturating_sub(1)..=(k + 1).min(loud.len() - 1)]
+                        .iter()
+                        .all(|l| *l)
+                })
+                .collect();
+            (*channel, loud_points(fft, &delays, &loud, &x_range))
+        })
+        .collect();
+    let y_range = value_range(&points, 1.);
+    build_points_chart(
+        builder,
+        x_range,
+        y_range,
+        ("Group delay (ms)", "ms"),
+        &points,
+        cursors,
+        options,
+    )
+}
+
+/// Plot how far the FFTs of some channels are above a reference trace (dB),
+/// as in build_phase_chart, leaving out bins where either is below the floor
+pub fn build_difference_chart<DB: DrawingBackend>(
+    builder: ChartBuilder<DB>,
+    ffts: &[(usize, &FoldedFFT)],
+    reference: &ReferenceTrace,
+    cursors: &Cursors,
+    options: &FFTChartOptions,
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    let x_range = frequency_range(ffts, options);
+    let points: Vec<(usize, Vec<(f32, f32)>)> = ffts
+        .iter()
+        .map(|(channel, fft)| {
+            let points = fft
+                .frequencies()
+                .zip(fft.values.iter())
+                .filter(|(f, _)| x_range.contains(&f32::from(*f)))
+                .filter_map(|(f, (r, _))| {
+                    let (level, reference_level) =
+                        (Level::dbfs(*r), Level::dbfs(reference.value_at(f)?));
+                    let audible = [level, reference_level]
+                        .iter()
+                        .all(|l| f32::from(*l) >= options.floor_db);
+                    audible.then_some((f32::from(f), f32::from(level - reference_level)))
+                })
+                .collect();
+            (*channel, points)
+        })
+        .collect();
+    let y_range = value_range(&points, TOP_STEP_DB);
+    let y_axis = ("Difference (dB)", "dB");
+    build_points_chart(builder, x_range, y_range, y_axis, &points, cursors, options)
+}
+
+/// Plot points of some channels against frequency, with a y axis described
+/// by its name and unit
+fn build_points_chart<DB: DrawingBackend>(
+    mut builder: ChartBuilder<DB>,
+    x_range: Range<f32>,
+    y_range: Range<f32>,
+    y_axis: (&str, &str),
+    points: &[(usize, Vec<(f32, f32)>)],
+    cursors: &Cursors,
+    options: &FFTChartOptions,
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    builder
+        .margin(20)
+        .x_label_area_size(40)
+        .y_label_area_size(60);
+    match options.frequency_scale {
+        FrequencyScale::Linear => draw_points(
+            builder.build_cartesian_2d(x_range, y_range)?,
+            y_axis,
+            points,
+            cursors,
+            options,
+        ),
+        FrequencyScale::Log => draw_points(
+            builder.build_cartesian_2d(x_range.log_scale(), y_range)?,
+            y_axis,
+            points,
+            cursors,
+            options,
+        ),
+    }
+}
+
+fn draw_points<DB, X>(
+    mut chart: ChartContext<DB, Cartesian2d<X, RangedCoordf32>>,
+    (y_desc, unit): (&str, &str),
+    points: &[(usize, Vec<(f32, f32)>)],
+    cursors: &Cursors,
+    options: &FFTChartOptions,
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>>
+where
+    DB: DrawingBackend,
+    X: Ranged<ValueType = f32> + ValueFormatter<f32>,
+{
+    chart
+        .configure_mesh()
+        .x_max_light_lines(0)
+        .y_max_light_lines(0)
+        .y_desc(y_desc)
+        .x_desc("Frequency (Hz)")
+        .draw()?;
+    if let Some(tuning) = &options.notes {
+        draw_notes(&mut chart, tuning, false)?;
+    }
+
+    for (channel, points) in points {
+        let color = channel_color(*channel);
+        chart.draw_series(points.iter().map(|p| Circle::new(*p, 1, color.filled())))?;
+    }
+
+    let tuning = options.notes.unwrap_or_default();
+    draw_cursors(
+        &mut chart,
+        cursors,
+        |(x, y)| {
+            let text = format!("{}, {:.2} {}", describe_frequency(x, &tuning), y, unit);
+            ((x, y), text)
+        },
+        |(x0, y0), (x1, y1)| format!("Δ {:.1} Hz, {:+.2} {}", x1 - x0, y1 - y0, unit),
+    )
+}
@@ -1,59 +1,117 @@
-use audio::dsp::fft::FoldedFFT;
+use audio::dsp::constant_q::ConstantQSpectrum;
+use audio::dsp::psd::PowerSpectrum;
 use plotters::prelude::*;
-use std::f32::consts::PI;
+use std::fmt;
 
-pub fn build_fft_chart<DB: DrawingBackend>(
+mod cursors;
+mod fft;
+mod spectrogram;
+mod vowels;
+
+pub use cursors::{draw_cursors, Cursors};
+pub use fft::{
+    build_difference_chart, build_fft_chart, build_group_delay_chart, build_phase_chart,
+    draw_fft_chart, AutoRange, FFTAnnotations, FFTChartOptions, MagnitudeScale, PhaseDisplay,
+};
+pub use spectrogram::{build_spectrogram_chart, SpectrogramOptions};
+pub use vowels::build_vowel_chart;
+
+/// How frequency axes are scaled
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrequencyScale {
+    Linear,
+    Log,
+}
+
+impl FrequencyScale {
+    pub const ALL: [FrequencyScale; 2] = [FrequencyScale::Linear, FrequencyScale::Log];
+
+    /// A log axis can't start at 0 Hz, so this is where it starts instead
+    /// (roughly the bottom of human hearing).
+    pub const LOG_MIN_FREQUENCY: f32 = 20.;
+}
+
+impl fmt::Display for FrequencyScale {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            FrequencyScale::Linear => "Linear",
+            FrequencyScale::Log => "Log",
+        })
+    }
+}
+
+/// A distinct color for each channel
+pub fn channel_color(channel: usize) -> RGBAColor {
+    Palette99::pick(channel).to_rgba()
+}
+
+/// Plot power spectral density estimates of some channels (numbered, for
+/// their colors), in dBFS/Hz
+pub fn build_psd_chart<DB: DrawingBackend>(
     mut builder: ChartBuilder<DB>,
-    fft: &FoldedFFT,
+    psds: &[(usize, &PowerSpectrum)],
 ) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    let nyquist = psds
+        .first()
+        .map_or(0., |(_, psd)| f32::from(psd.nyquist_frequency()));
     let mut chart = builder
         .margin(20)
         .x_label_area_size(40)
         .y_label_area_size(40)
-        .right_y_label_area_size(40)
-        // TODO: Y axis hackery
-        .build_cartesian_2d(0f32..f32::from(fft.nyquist_frequency()), 0f32..0.1f32)?
-        .set_secondary_coord(0f32..f32::from(fft.nyquist_frequency()), -PI..PI);
+        .build_cartesian_2d(0f32..nyquist, -140f32..0f32)?;
 
     chart
         .configure_mesh()
         .x_max_light_lines(0)
         .y_max_light_lines(0)
-        .y_desc("Amplitude (FS)")
+        .y_desc("PSD (dBFS/Hz)")
         .x_desc("Frequency (Hz)")
         .draw()?;
-    chart
-        .configure_secondary_axes()
-        .y_desc("Phase (radians)")
-        .draw()?;
 
-    let magnitudes = fft
-        .frequencies()
-        .zip(fft.values.iter())
-        .map(|(f, (r, _p))| (f32::from(f), *r));
-    chart
-        .draw_series(LineSeries::new(magnitudes, &RED))
-        .unwrap()
-        .label("Amplitude")
-        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], &RED));
-
-    // TODO: include phase in notebooks, but not UI :/
-    // let phases = fft
-    //     .frequencies()
-    //     .zip(fft.values.iter())
-    //     .map(|(f, (_r, p))| (f32::from(f), *p));
-    // chart
-    //     .draw_secondary_series(LineSeries::new(phases, &BLUE))
-    //     .unwrap()
-    //     .label("Phase")
-    //     .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], &BLUE));
+    for (channel, psd) in psds {
+        let densities = psd
+            .frequencies()
+            .zip(psd.to_db())
+            .map(|(f, db)| (f32::from(f), db.max(-140.)));
+        chart.draw_series(LineSeries::new(densities, channel_color(*channel)))?;
+    }
+
+    Ok(())
+}
+
+/// Plot constant-Q amplitude spectra of some channels (numbered, for their
+/// colors), in dBFS, on a log frequency axis
+pub fn build_constant_q_chart<DB: DrawingBackend>(
+    mut builder: ChartBuilder<DB>,
+    spectra: &[(usize, &ConstantQSpectrum)],
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    let frequencies = spectra.first().map_or(&[][..], |(_, s)| &s.frequencies[..]);
+    let (fmin, fmax) = match (frequencies.first(), frequencies.last()) {
+        (Some(lo), Some(hi)) if lo.0 < hi.0 => (lo.0, hi.0),
+        _ => (FrequencyScale::LOG_MIN_FREQUENCY, 20000.),
+    };
+    let mut chart = builder
+        .margin(20)
+        .x_label_area_size(40)
+        .y_label_area_size(40)
+        .build_cartesian_2d((fmin..fmax).log_scale(), -100f32..0f32)?;
 
     chart
-        .configure_series_labels()
-        .position(SeriesLabelPosition::UpperRight)
-        .background_style(&WHITE.mix(0.8))
-        .border_style(&BLACK)
+        .configure_mesh()
+        .x_max_light_lines(0)
+        .y_max_light_lines(0)
+        .y_desc("Amplitude (dBFS)")
+        .x_desc("Frequency (Hz)")
         .draw()?;
 
+    for (channel, spectrum) in spectra {
+        let amplitudes = spectrum
+            .frequencies
+            .iter()
+            .zip(spectrum.to_db())
+            .map(|(f, db)| (f32::from(*f), db.max(-100.)));
+        chart.draw_series(LineSeries::new(amplitudes, channel_color(*channel)))?;
+    }
+
     Ok(())
 }
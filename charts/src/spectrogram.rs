@@ -0,0 +1,198 @@
+use audio::dsp::spectrogram::Spectrogram;
+use audio::dsp::tuning::Tuning;
+use plotters::coord::ranged1d::{Ranged, ValueFormatter};
+use plotters::coord::types::RangedCoordf32;
+use plotters::prelude::*;
+
+use crate::cursors::{describe_frequency, draw_cursors, Cursors};
+use crate::FrequencyScale;
+
+/// How to render a spectrogram
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpectrogramOptions {
+    /// Magnitudes at or above this are drawn with the top of the colour map
+    pub max_db: f32,
+    /// The range of magnitudes (below max_db) spanned by the colour map
+    pub dynamic_range_db: f32,
+    pub frequency_scale: FrequencyScale,
+    /// The highest frequency to show (Hz), or None to show up to nyquist
+    pub max_frequency: Option<f32>,
+}
+
+impl Default for SpectrogramOptions {
+    fn default() -> SpectrogramOptions {
+        SpectrogramOptions {
+            max_db: 0.,
+            dynamic_range_db: 80.,
+            frequency_scale: FrequencyScale::Linear,
+            max_frequency: None,
+        }
+    }
+}
+
+/// Draw a scrolling heatmap of a spectrogram, with time on the x axis
+pub fn build_spectrogram_chart<DB: DrawingBackend>(
+    mut builder: ChartBuilder<DB>,
+    spectrogram: &Spectrogram,
+    cursors: &Cursors,
+    options: &SpectrogramOptions,
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    let history = spectrogram.max_history().as_secs_f32();
+    let tmax = spectrogram
+        .latest_time()
+        .map(f32::from)
+        .unwrap_or(0.)
+        .max(history);
+    let fmax = options
+        .max_frequency
+        .or(spectrogram.frequencies().last().map(|f| f32::from(*f)))
+        .unwrap_or(1.);
+
+    builder
+        .margin(20)
+        .x_label_area_size(40)
+        .y_label_area_size(60);
+    match options.frequency_scale {
+        FrequencyScale::Linear => draw_spectrogram(
+            builder.build_cartesian_2d(tmax - history..tmax, 0f32..fmax)?,
+            spectrogram,
+            cursors,
+            options,
+        ),
+        FrequencyScale::Log => draw_spectrogram(
+            builder.build_cartesian_2d(
+                tmax - history..tmax,
+                (FrequencyScale::LOG_MIN_FREQUENCY..fmax).log_scale(),
+            )?,
+            spectrogram,
+            cursors,
+            options,
+        ),
+    }
+}
+
+fn draw_spectrogram<DB, Y>(
+    mut chart: ChartContext<DB, Cartesian2d<RangedCoordf32, Y>>,
+    spectrogram: &Spectrogram,
+    cursors: &Cursors,
+    options: &SpectrogramOptions,
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>>
+where
+    DB: DrawingBackend,
+    Y: Ranged<ValueType = f32> + ValueFormatter<f32>,
+{
+    chart
+        .configure_mesh()
+        .disable_mesh()
+        .x_desc("Time (s)")
+        .y_desc("Frequency (Hz)")
+        .draw()?;
+
+    let frequencies = spectrogram.frequencies();
+    if frequencies.len() < 2 {
+        return Ok(());
+    }
+    // Each bin spans halfway to its neighbours, so that unevenly spaced
+    // (e.g. constant-Q) bins are drawn in proportion
+    let centers: Vec<f32> = frequencies.iter().map(|f| f32::from(*f)).collect();
+    let edges: Vec<f32> = std::iter::once(1.5 * centers[0] - 0.5 * centers[1])
+        .chain(centers.windows(2).map(|w| (w[0] + w[1]) / 2.))
+        .chain(std::iter::once(
+            1.5 * centers[centers.len() - 1] - 0.5 * centers[centers.len() - 2],
+        ))
+        .collect();
+    let y_range = chart.y_range();
+    let min_db = options.max_db - options.dynamic_range_db;
+    let color = |db: f32| {
+        ViridisRGB
+            .get_color_normalized(db.clamp(min_db, options.max_db), min_db, options.max_db)
+            .filled()
+    };
+
+    let mut prev_time: Option<f32> = None;
+    for (i, (time, column)) in spectrogram.columns().enumerate() {
+        // Each column spans from the previous column (or, for the oldest
+        // column, the same width as the next column)
+        let t1 = f32::from(*time);
+        let t0 = match prev_time {
+            Some(t) => t,
+            None => spectrogram
+                .columns()
+                .nth(i + 1)
+                .map(|(next, _)| 2. * t1 - f32::from(*next))
+                .unwrap_or(t1 - spectrogram.max_history().as_secs_f32()),
+        };
+        prev_time = Some(t1);
+
+        // There are typically many more frequency bins than there are pixels,
+        // so draw one rectangle per run of bins that land on the same pixel
+        // row (with the loudest bin's magnitude).
+        let mut rects = Vec::new();
+        // (pixel row, lowest frequency, magnitude)
+        let mut band: Option<(i32, f32, f32)> = None;
+        let mut top = y_range.end;
+        for ((f, edges), db) in centers.iter().zip(edges.windows(2)).zip(column) {
+            let lo = edges[0].max(y_range.start);
+            let hi = edges[1];
+            if hi <= y_range.start {
+                continue;
+            }
+            if lo >= y_range.end {
+                break;
+            }
+            top = hi.min(y_range.end);
+            let row = chart.backend_coord(&(t1, *f)).1;
+            band = match band {
+                Some((r, band_lo, band_db)) if r == row => Some((r, band_lo, band_db.max(*db))),
+                Some((_, band_lo, band_db)) => {
+                    rects.push(Rectangle::new([(t0, band_lo), (t1, lo)], color(band_db)));
+                    Some((row, lo, *db))
+                }
+                None => Some((row, lo, *db)),
+            };
+        }
+        if let Some((_, band_lo, band_db)) = band {
+            rects.push(Rectangle::new([(t0, band_lo), (t1, top)], color(band_db)));
+        }
+        chart.draw_series(rects)?;
+    }
+
+    draw_spectrogram_cursors(&mut chart, spectrogram, &centers, cursors)
+}
+
+/// Draw cursors, reading out the magnitude of the column and bin under each
+fn draw_spectrogram_cursors<DB, Y>(
+    chart: &mut ChartContext<DB, Cartesian2d<RangedCoordf32, Y>>,
+    spectrogram: &Spectrogram,
+    centers: &[f32],
+    cursors: &Cursors,
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>>
+where
+    DB: DrawingBackend,
+    Y: Ranged<ValueType = f32> + ValueFormatter<f32>,
+{
+    let tuning = Tuning::default();
+    // Columns span back from their times, so the one under a time is the
+    // first that ends at or after it
+    let magnitude = |(t, f): (f32, f32)| {
+        let (_, column) = spectrogram
+            .columns()
+            .find(|(time, _)| f32::from(**time) >= t)?;
+        let bin = centers
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| (*a - f).abs().total_cmp(&(*b - f).abs()))?
+            .0;
+        column.get(bin).copied()
+    };
+    draw_cursors(
+        chart,
+        cursors,
+        |(t, f)| {
+            let db = magnitude((t, f)).map_or(String::new(), |db| format!(", {:.1} dBFS", db));
+            let text = format!("{:.2} s, {}{}", t, describe_frequency(f, &tuning), db);
+            ((t, f), text)
+        },
+        |(t0, f0), (t1, f1)| format!("Δ {:.3} s, {:.1} Hz", t1 - t0, f1 - f0),
+    )
+}
@@ -0,0 +1,83 @@
+use audio::dsp::vowels::{SpeakerVowels, VowelReferences, VowelTrail};
+use plotters::prelude::*;
+
+/// The F2 axis range (Hz), which covers most adult speakers' vowels
+const F2_RANGE: (f32, f32) = (500., 3000.);
+/// The F1 axis range (Hz)
+const F1_RANGE: (f32, f32) = (150., 1000.);
+
+/// Plot vowels in the F1/F2 plane, in the usual vowel chart orientation,
+/// i.e. with both axes inverted so that front/close vowels are top left.
+/// The trail fades with age; saved speakers' points are drawn in their own
+/// colours.
+pub fn build_vowel_chart<DB: DrawingBackend>(
+    mut builder: ChartBuilder<DB>,
+    trail: &VowelTrail,
+    references: Option<VowelReferences>,
+    speakers: &[SpeakerVowels],
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    let mut chart = builder
+        .margin(20)
+        .x_label_area_size(40)
+        .y_label_area_size(50)
+        .build_cartesian_2d(F2_RANGE.1..F2_RANGE.0, F1_RANGE.1..F1_RANGE.0)?;
+
+    chart
+        .configure_mesh()
+        .x_desc("F2 (Hz)")
+        .y_desc("F1 (Hz)")
+        .draw()?;
+
+    if let Some(references) = references {
+        let style = ("sans-serif", 20).into_font().color(&BLACK.mix(0.6));
+        chart.draw_series(references.vowels().iter().map(|v| {
+            EmptyElement::at((v.f2.0, v.f1.0))
+                + Cross::new((0, 0), 4, BLACK.mix(0.6))
+                + Text::new(v.symbol, (6, -20), style.clone())
+        }))?;
+    }
+
+    for (i, speaker) in speakers.iter().enumerate() {
+        let color = Palette99::pick(i + 1).to_rgba();
+        chart
+            .draw_series(
+                speaker
+                    .points
+                    .iter()
+                    .map(|p| Circle::new((p.f2.0, p.f1.0), 3, color.mix(0.5).filled())),
+            )?
+            .label(speaker.speaker.as_str())
+            .legend(move |(x, y)| Circle::new((x + 10, y), 4, color.filled()));
+    }
+
+    if let Some(latest) = trail.latest_time() {
+        let history = trail.max_history().as_secs_f32();
+        let alpha = |p: &audio::dsp::vowels::VowelPoint| {
+            let age = f32::from(latest) - f32::from(p.time);
+            (1. - age / history).clamp(0., 1.) as f64
+        };
+        let points: Vec<_> = trail.points().collect();
+        chart.draw_series(points.windows(2).map(|pair| {
+            PathElement::new(
+                vec![(pair[0].f2.0, pair[0].f1.0), (pair[1].f2.0, pair[1].f1.0)],
+                BLUE.mix(alpha(pair[1]) * 0.5),
+            )
+        }))?;
+        chart.draw_series(
+            points
+                .iter()
+                .map(|p| Circle::new((p.f2.0, p.f1.0), 4, BLUE.mix(alpha(p)).filled())),
+        )?;
+    }
+
+    if !speakers.is_empty() {
+        chart
+            .configure_series_labels()
+            .position(SeriesLabelPosition::LowerLeft)
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .draw()?;
+    }
+
+    Ok(())
+}
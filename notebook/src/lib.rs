@@ -9,10 +9,28 @@
 pub use std::f32::consts::PI;
 
 pub use audio;
-pub use audio::dsp::fft::FoldedFFT;
+pub use audio::dsp::cepstrum::{
+    normalized_autocorrelation, real_cepstrum, Autocorrelation, Cepstrum, Lifter,
+};
+pub use audio::dsp::constant_q::{ConstantQ, ConstantQOptions, ConstantQSpectrum};
+pub use audio::dsp::fft::{FFTSequence, FoldedFFT};
+pub use audio::dsp::loudness::{Loudness, LoudnessMeter};
+pub use audio::dsp::lpc::{FormantAnalyzer, SpectralEnvelope, LPC};
+pub use audio::dsp::mel::{deltas, MelAnalyzer, MelFilterbank, MelFrame, MelOptions, MelScale};
+pub use audio::dsp::oscilloscope::{Edge, Oscilloscope, ScopeOptions, ScopeTrace, TriggerMode};
+pub use audio::dsp::peak_meter::{ChannelPeaks, PeakMeter};
+pub use audio::dsp::peaks::{harmonic_series, Interpolation, Peak, PeakOptions};
+pub use audio::dsp::sound_level::{
+    FrequencyWeighting, SoundLevel, SoundLevelMeter, SoundLevelOptions, TimeWeighting,
+};
+pub use audio::dsp::spectrogram::Spectrogram;
+pub use audio::dsp::voice::{VoiceAnalyzer, VoiceQuality, VoiceSummary};
 pub use audio::stream::buffer::{BufferedInput, Period};
 pub use audio::stream::input::SampleRate;
 pub use charts;
+pub use charts::{
+    FFTAnnotations, FFTChartOptions, FrequencyScale, PhaseDisplay, SpectrogramOptions,
+};
 pub use num_complex::Complex;
 pub use plotters;
 use plotters::evcxr::SVGWrapper;
@@ -44,10 +62,119 @@ pub fn plot_period(period: &Period) -> SVGWrapper {
     })
 }
 
+/// Plot a spectrum, with its (wrapped) phase below
 pub fn plot_fft(fft: &FoldedFFT) -> SVGWrapper {
+    let options = FFTChartOptions {
+        phase: PhaseDisplay::Wrapped,
+        ..FFTChartOptions::default()
+    };
+    plot_fft_with(&[fft], &options)
+}
+
+/// Plot the spectra of several channels overlaid, with the given panes
+pub fn plot_fft_with(ffts: &[&FoldedFFT], options: &FFTChartOptions) -> SVGWrapper {
+    let ffts: Vec<(usize, &FoldedFFT)> = ffts.iter().copied().enumerate().collect();
+    evcxr_figure((640, 480), |root| {
+        root.fill(&WHITE)?;
+        charts::draw_fft_chart(&root, &ffts, &FFTAnnotations::default(), options)?;
+        Ok(())
+    })
+}
+
+/// Plot a constant-Q spectrum, on a log frequency axis
+pub fn plot_constant_q(spectrum: &ConstantQSpectrum) -> SVGWrapper {
+    evcxr_figure((640, 480), |root| {
+        root.fill(&WHITE)?;
+        charts::build_constant_q_chart(ChartBuilder::on(&root), &[(0, spectrum)])?;
+        Ok(())
+    })
+}
+
+/// Plot a spectrum, with the LPC envelope of the period it came from
+pub fn plot_fft_envelope(fft: &FoldedFFT, envelope: &SpectralEnvelope) -> SVGWrapper {
+    evcxr_figure((640, 480), |root| {
+        root.fill(&WHITE)?;
+        let annotations = FFTAnnotations {
+            envelope: Some(envelope),
+            ..FFTAnnotations::default()
+        };
+        charts::build_fft_chart(
+            ChartBuilder::on(&root),
+            &[(0, fft)],
+            &annotations,
+            &FFTChartOptions::default(),
+        )?;
+        Ok(())
+    })
+}
+
+/// Plot a spectrum with its peaks labelled, including the harmonic numbers of
+/// any that are partials of a harmonic series
+pub fn plot_fft_peaks(fft: &FoldedFFT, options: &PeakOptions) -> SVGWrapper {
+    let peaks = fft.peaks(options);
+    let harmonics = harmonic_series(&peaks, 30.);
+    evcxr_figure((640, 480), |root| {
+        root.fill(&WHITE)?;
+        let annotations = FFTAnnotations {
+            peaks: &peaks,
+            harmonics: harmonics.as_ref(),
+            ..FFTAnnotations::default()
+        };
+        charts::build_fft_chart(
+            ChartBuilder::on(&root),
+            &[(0, fft)],
+            &annotations,
+            &FFTChartOptions::default(),
+        )?;
+        Ok(())
+    })
+}
+
+/// Plot a cepstrum against quefrency (ms), leaving out quefrency 0 (which
+/// is just the mean log amplitude, and dwarfs the rest)
+pub fn plot_cepstrum(cepstrum: &Cepstrum) -> SVGWrapper {
+    let points: Vec<(f32, f32)> = cepstrum
+        .points()
+        .skip(1)
+        .map(|(q, c)| (q * 1000., c))
+        .collect();
+    plot_lags(&points, "Quefrency (ms)", "Cepstrum")
+}
+
+/// Plot a normalized autocorrelation against lag (ms)
+pub fn plot_autocorrelation(autocorrelation: &Autocorrelation) -> SVGWrapper {
+    let points: Vec<(f32, f32)> = autocorrelation
+        .points()
+        .map(|(lag, r)| (lag * 1000., r))
+        .collect();
+    plot_lags(&points, "Lag (ms)", "Autocorrelation")
+}
+
+fn plot_lags(points: &[(f32, f32)], x_desc: &str, y_desc: &str) -> SVGWrapper {
+    evcxr_figure((640, 480), |root| {
+        root.fill(&WHITE)?;
+        let xmax = points.last().map_or(1., |(x, _)| *x);
+        let ymax = points.iter().fold(0f32, |max, (_, y)| max.max(y.abs()));
+        let mut chart = ChartBuilder::on(&root)
+            .margin(20)
+            .x_label_area_size(40)
+            .y_label_area_size(40)
+            .build_cartesian_2d(0f32..xmax, -ymax..ymax)?;
+        chart
+            .configure_mesh()
+            .x_desc(x_desc)
+            .y_desc(y_desc)
+            .draw()?;
+        chart.draw_series(LineSeries::new(points.iter().copied(), &RED))?;
+        Ok(())
+    })
+}
+
+pub fn plot_spectrogram(spectrogram: &Spectrogram, options: &SpectrogramOptions) -> SVGWrapper {
     evcxr_figure((640, 480), |root| {
         root.fill(&WHITE)?;
-        charts::build_fft_chart(ChartBuilder::on(&root), fft)?;
+        let cursors = charts::Cursors::default();
+        charts::build_spectrogram_chart(ChartBuilder::on(&root), spectrogram, &cursors, options)?;
         Ok(())
     })
 }